@@ -0,0 +1,38 @@
+-- 构建历史
+CREATE TABLE IF NOT EXISTS build_history
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id   TEXT     NOT NULL,
+    pipeline_id  TEXT     NOT NULL,
+    build_id     TEXT     NOT NULL,
+    build_num    INTEGER  NOT NULL,
+    status       TEXT     NOT NULL,
+    trigger      TEXT     NOT NULL DEFAULT '',
+    start_user   TEXT     NOT NULL DEFAULT '',
+    start_time   DATETIME NOT NULL,
+    end_time     DATETIME,
+    duration     INTEGER,
+    stages       TEXT     NOT NULL DEFAULT '[]',
+    aggregated   BOOLEAN  NOT NULL DEFAULT FALSE,
+    created_at   DATETIME NOT NULL,
+    updated_at   DATETIME NOT NULL,
+    UNIQUE (project_id, pipeline_id, build_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_build_history_pipeline ON build_history (project_id, pipeline_id, build_num);
+CREATE INDEX IF NOT EXISTS idx_build_history_start_time ON build_history (start_time);
+
+-- 构建按天聚合, 原始记录清理后保留趋势
+CREATE TABLE IF NOT EXISTS build_daily_stats
+(
+    project_id     TEXT    NOT NULL,
+    pipeline_id    TEXT    NOT NULL,
+    day            DATE    NOT NULL,
+    total_count    INTEGER NOT NULL DEFAULT 0,
+    succeed_count  INTEGER NOT NULL DEFAULT 0,
+    failed_count   INTEGER NOT NULL DEFAULT 0,
+    canceled_count INTEGER NOT NULL DEFAULT 0,
+    total_duration INTEGER NOT NULL DEFAULT 0,
+    max_duration   INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (project_id, pipeline_id, day)
+);
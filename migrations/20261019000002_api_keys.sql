@@ -0,0 +1,16 @@
+-- 机器调用方的 API Key, 只保存哈希
+CREATE TABLE IF NOT EXISTS api_keys
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    name         TEXT     NOT NULL,
+    prefix       TEXT     NOT NULL,
+    key_hash     TEXT     NOT NULL UNIQUE,
+    scopes       TEXT     NOT NULL DEFAULT '[]',
+    created_by   TEXT     NOT NULL DEFAULT '',
+    created_at   DATETIME NOT NULL,
+    expires_at   DATETIME,
+    revoked_at   DATETIME,
+    rotated_at   DATETIME,
+    last_used_at DATETIME,
+    usage_count  INTEGER  NOT NULL DEFAULT 0
+);
@@ -0,0 +1,13 @@
+-- 项目成员及角色, source 区分手工维护和从 DevOps 同步
+CREATE TABLE IF NOT EXISTS project_members
+(
+    project_id TEXT     NOT NULL,
+    subject    TEXT     NOT NULL,
+    role       TEXT     NOT NULL,
+    source     TEXT     NOT NULL DEFAULT 'manual',
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (project_id, subject)
+);
+
+CREATE INDEX IF NOT EXISTS idx_project_members_subject ON project_members (subject);
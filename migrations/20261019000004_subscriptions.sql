@@ -0,0 +1,18 @@
+-- 通知订阅: 项目/流水线的构建事件推送到指定渠道
+CREATE TABLE IF NOT EXISTS subscriptions
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    name        TEXT     NOT NULL,
+    project_id  TEXT     NOT NULL,
+    pipeline_id TEXT,
+    events      TEXT     NOT NULL DEFAULT '[]',
+    channel_id  INTEGER  NOT NULL,
+    owner       TEXT     NOT NULL DEFAULT '',
+    tags        TEXT     NOT NULL DEFAULT '[]',
+    enabled     BOOLEAN  NOT NULL DEFAULT TRUE,
+    created_at  DATETIME NOT NULL,
+    updated_at  DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_subscriptions_project ON subscriptions (project_id, pipeline_id);
+CREATE INDEX IF NOT EXISTS idx_subscriptions_channel ON subscriptions (channel_id);
@@ -0,0 +1,16 @@
+-- 通知渠道, secret 为加密后的敏感字段
+CREATE TABLE IF NOT EXISTS channels
+(
+    id            INTEGER PRIMARY KEY AUTOINCREMENT,
+    name          TEXT     NOT NULL,
+    project_id    TEXT     NOT NULL,
+    kind          TEXT     NOT NULL,
+    config        TEXT     NOT NULL DEFAULT '{}',
+    secret        TEXT,
+    secret_fields TEXT     NOT NULL DEFAULT '[]',
+    created_by    TEXT     NOT NULL DEFAULT '',
+    created_at    DATETIME NOT NULL,
+    updated_at    DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_channels_project ON channels (project_id);
@@ -0,0 +1,22 @@
+-- 流水线缓存
+CREATE TABLE IF NOT EXISTS pipelines
+(
+    id                      INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id              TEXT     NOT NULL,
+    pipeline_id             TEXT     NOT NULL,
+    name                    TEXT     NOT NULL,
+    description             TEXT     NOT NULL DEFAULT '',
+    creator                 TEXT     NOT NULL DEFAULT '',
+    latest_build_num        INTEGER,
+    latest_build_status     TEXT,
+    latest_build_user       TEXT,
+    latest_build_start_time DATETIME,
+    latest_build_end_time   DATETIME,
+    running_build_count     INTEGER  NOT NULL DEFAULT 0,
+    created_at              DATETIME NOT NULL,
+    updated_at              DATETIME NOT NULL,
+    UNIQUE (project_id, pipeline_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_pipelines_pipeline_id ON pipelines (pipeline_id);
+CREATE INDEX IF NOT EXISTS idx_build_history_build_id ON build_history (build_id);
@@ -0,0 +1,35 @@
+-- 通知发件箱
+CREATE TABLE IF NOT EXISTS notifications
+(
+    id          INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id  TEXT     NOT NULL,
+    title       TEXT     NOT NULL,
+    content     TEXT     NOT NULL,
+    severity    TEXT     NOT NULL DEFAULT 'info',
+    source      TEXT     NOT NULL,
+    template_id TEXT,
+    created_by  TEXT     NOT NULL DEFAULT '',
+    created_at  DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_notifications_project ON notifications (project_id, created_at);
+
+-- 每个渠道一条投递记录, 由投递任务重试直到成功或达到最大次数
+CREATE TABLE IF NOT EXISTS deliveries
+(
+    id              INTEGER PRIMARY KEY AUTOINCREMENT,
+    notification_id INTEGER  NOT NULL REFERENCES notifications (id) ON DELETE CASCADE,
+    channel_id      INTEGER  NOT NULL,
+    status          TEXT     NOT NULL DEFAULT 'pending',
+    attempts        INTEGER  NOT NULL DEFAULT 0,
+    next_attempt_at DATETIME NOT NULL,
+    last_error      TEXT,
+    response_status INTEGER,
+    response_body   TEXT,
+    delivered_at    DATETIME,
+    created_at      DATETIME NOT NULL,
+    updated_at      DATETIME NOT NULL,
+    UNIQUE (notification_id, channel_id)
+);
+
+CREATE INDEX IF NOT EXISTS idx_deliveries_due ON deliveries (status, next_attempt_at);
@@ -0,0 +1,34 @@
+-- 通知关联的构建上下文, 手动通知为空
+ALTER TABLE notifications ADD COLUMN pipeline_id TEXT;
+ALTER TABLE notifications ADD COLUMN build_id TEXT;
+ALTER TABLE notifications ADD COLUMN event TEXT;
+
+-- 命中的订阅规则, 直接指定渠道时为空
+ALTER TABLE deliveries ADD COLUMN subscription_id INTEGER;
+
+-- 投递尝试审计记录, 冗余保存上下文, 不随通知或渠道变更
+CREATE TABLE IF NOT EXISTS delivery_attempts
+(
+    id               INTEGER PRIMARY KEY AUTOINCREMENT,
+    delivery_id      INTEGER  NOT NULL,
+    notification_id  INTEGER  NOT NULL,
+    project_id       TEXT     NOT NULL,
+    pipeline_id      TEXT,
+    build_id         TEXT,
+    source           TEXT     NOT NULL,
+    event            TEXT,
+    subscription_id  INTEGER,
+    channel_id       INTEGER  NOT NULL,
+    attempt          INTEGER  NOT NULL,
+    status           TEXT     NOT NULL,
+    payload_hash     TEXT     NOT NULL,
+    response_status  INTEGER,
+    response_excerpt TEXT,
+    error            TEXT,
+    latency_ms       INTEGER  NOT NULL,
+    attempted_at     DATETIME NOT NULL
+);
+
+CREATE INDEX IF NOT EXISTS idx_delivery_attempts_time ON delivery_attempts (attempted_at);
+CREATE INDEX IF NOT EXISTS idx_delivery_attempts_project ON delivery_attempts (project_id, pipeline_id, build_id);
+CREATE INDEX IF NOT EXISTS idx_delivery_attempts_channel ON delivery_attempts (channel_id);
@@ -0,0 +1,8 @@
+-- 多个 DevOps 实例: 流水线和构建记录来源实例, 已有数据归属默认实例
+ALTER TABLE pipelines ADD COLUMN source TEXT NOT NULL DEFAULT 'default';
+ALTER TABLE build_history ADD COLUMN source TEXT NOT NULL DEFAULT 'default';
+
+-- 订阅限定的实例, 为空时不限定
+ALTER TABLE subscriptions ADD COLUMN source TEXT;
+
+CREATE INDEX IF NOT EXISTS idx_pipelines_source ON pipelines (source, project_id);
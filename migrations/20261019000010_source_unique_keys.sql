@@ -0,0 +1,95 @@
+-- 不同 DevOps 实例的项目和流水线 id 可能相同, 唯一键加入来源实例
+CREATE TABLE pipelines_new
+(
+    id                      INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id              TEXT     NOT NULL,
+    pipeline_id             TEXT     NOT NULL,
+    name                    TEXT     NOT NULL,
+    description             TEXT     NOT NULL DEFAULT '',
+    creator                 TEXT     NOT NULL DEFAULT '',
+    latest_build_num        INTEGER,
+    latest_build_status     TEXT,
+    latest_build_user       TEXT,
+    latest_build_start_time DATETIME,
+    latest_build_end_time   DATETIME,
+    running_build_count     INTEGER  NOT NULL DEFAULT 0,
+    created_at              DATETIME NOT NULL,
+    updated_at              DATETIME NOT NULL,
+    source                  TEXT     NOT NULL DEFAULT 'default',
+    UNIQUE (source, project_id, pipeline_id)
+);
+
+INSERT INTO pipelines_new (id, project_id, pipeline_id, name, description, creator, latest_build_num,
+                           latest_build_status, latest_build_user, latest_build_start_time,
+                           latest_build_end_time, running_build_count, created_at, updated_at, source)
+SELECT id, project_id, pipeline_id, name, description, creator, latest_build_num,
+       latest_build_status, latest_build_user, latest_build_start_time,
+       latest_build_end_time, running_build_count, created_at, updated_at, source
+FROM pipelines;
+
+DROP TABLE pipelines;
+ALTER TABLE pipelines_new RENAME TO pipelines;
+
+CREATE INDEX IF NOT EXISTS idx_pipelines_pipeline_id ON pipelines (pipeline_id);
+CREATE INDEX IF NOT EXISTS idx_pipelines_source ON pipelines (source, project_id);
+
+CREATE TABLE build_history_new
+(
+    id           INTEGER PRIMARY KEY AUTOINCREMENT,
+    project_id   TEXT     NOT NULL,
+    pipeline_id  TEXT     NOT NULL,
+    build_id     TEXT     NOT NULL,
+    build_num    INTEGER  NOT NULL,
+    status       TEXT     NOT NULL,
+    trigger      TEXT     NOT NULL DEFAULT '',
+    start_user   TEXT     NOT NULL DEFAULT '',
+    start_time   DATETIME NOT NULL,
+    end_time     DATETIME,
+    duration     INTEGER,
+    stages       TEXT     NOT NULL DEFAULT '[]',
+    aggregated   BOOLEAN  NOT NULL DEFAULT FALSE,
+    created_at   DATETIME NOT NULL,
+    updated_at   DATETIME NOT NULL,
+    source       TEXT     NOT NULL DEFAULT 'default',
+    UNIQUE (source, project_id, pipeline_id, build_id)
+);
+
+INSERT INTO build_history_new (id, project_id, pipeline_id, build_id, build_num, status, trigger,
+                               start_user, start_time, end_time, duration, stages, aggregated,
+                               created_at, updated_at, source)
+SELECT id, project_id, pipeline_id, build_id, build_num, status, trigger,
+       start_user, start_time, end_time, duration, stages, aggregated,
+       created_at, updated_at, source
+FROM build_history;
+
+DROP TABLE build_history;
+ALTER TABLE build_history_new RENAME TO build_history;
+
+CREATE INDEX IF NOT EXISTS idx_build_history_pipeline ON build_history (source, project_id, pipeline_id, build_num);
+CREATE INDEX IF NOT EXISTS idx_build_history_start_time ON build_history (start_time);
+CREATE INDEX IF NOT EXISTS idx_build_history_build_id ON build_history (build_id);
+
+-- 已有聚合归属默认实例
+CREATE TABLE build_daily_stats_new
+(
+    source         TEXT    NOT NULL DEFAULT 'default',
+    project_id     TEXT    NOT NULL,
+    pipeline_id    TEXT    NOT NULL,
+    day            DATE    NOT NULL,
+    total_count    INTEGER NOT NULL DEFAULT 0,
+    succeed_count  INTEGER NOT NULL DEFAULT 0,
+    failed_count   INTEGER NOT NULL DEFAULT 0,
+    canceled_count INTEGER NOT NULL DEFAULT 0,
+    total_duration INTEGER NOT NULL DEFAULT 0,
+    max_duration   INTEGER NOT NULL DEFAULT 0,
+    PRIMARY KEY (source, project_id, pipeline_id, day)
+);
+
+INSERT INTO build_daily_stats_new (project_id, pipeline_id, day, total_count, succeed_count,
+                                   failed_count, canceled_count, total_duration, max_duration)
+SELECT project_id, pipeline_id, day, total_count, succeed_count,
+       failed_count, canceled_count, total_duration, max_duration
+FROM build_daily_stats;
+
+DROP TABLE build_daily_stats;
+ALTER TABLE build_daily_stats_new RENAME TO build_daily_stats;
@@ -0,0 +1,10 @@
+-- 已计入按天聚合的结果, 同一构建重跑后先扣除旧结果再计入新结果
+ALTER TABLE build_history ADD COLUMN counted_day DATE;
+ALTER TABLE build_history ADD COLUMN counted_status TEXT;
+ALTER TABLE build_history ADD COLUMN counted_duration INTEGER;
+
+UPDATE build_history
+SET counted_day      = date(start_time),
+    counted_status   = status,
+    counted_duration = duration
+WHERE aggregated = TRUE;
@@ -0,0 +1,21 @@
+-- 项目成员按 DevOps 实例区分, 原 source 列(手工维护或同步)改名为 origin, 已有成员归属默认实例
+CREATE TABLE project_members_new
+(
+    source     TEXT     NOT NULL DEFAULT 'default',
+    project_id TEXT     NOT NULL,
+    subject    TEXT     NOT NULL,
+    role       TEXT     NOT NULL,
+    origin     TEXT     NOT NULL DEFAULT 'manual',
+    created_at DATETIME NOT NULL,
+    updated_at DATETIME NOT NULL,
+    PRIMARY KEY (source, project_id, subject)
+);
+
+INSERT INTO project_members_new (project_id, subject, role, origin, created_at, updated_at)
+SELECT project_id, subject, role, source, created_at, updated_at
+FROM project_members;
+
+DROP TABLE project_members;
+ALTER TABLE project_members_new RENAME TO project_members;
+
+CREATE INDEX IF NOT EXISTS idx_project_members_subject ON project_members (subject);
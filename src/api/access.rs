@@ -0,0 +1,122 @@
+use crate::api::auth::AuthUser;
+use crate::context::{self, REQUEST_ID_HEADER};
+use crate::telemetry;
+use axum::extract::{ConnectInfo, MatchedPath, Request};
+use axum::http::HeaderValue;
+use axum::middleware::Next;
+use axum::response::Response;
+use log::info;
+use opentelemetry::context::FutureExt;
+use opentelemetry::trace::{SpanKind, TraceContextExt};
+use opentelemetry::{Context, KeyValue};
+use std::net::SocketAddr;
+use std::time::Instant;
+
+/// 接受的请求 ID 最大长度
+const MAX_REQUEST_ID_LEN: usize = 128;
+
+/**
+ * 使用调用方传入的 X-Request-Id, 缺失或不合法时生成新的
+ */
+fn request_id_of(request: &Request) -> String {
+    request
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| {
+            !id.is_empty()
+                && id.len() <= MAX_REQUEST_ID_LEN
+                && id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+        })
+        .map(str::to_string)
+        .unwrap_or_else(|| uuid::Uuid::new_v4().to_string())
+}
+
+/**
+ * 请求 ID、server span 及访问日志, 日志以结构化字段输出到 access 目标;
+ * 调用方传入 traceparent 时 span 挂在调用方的链路下
+ */
+pub async fn trace(mut request: Request, next: Next) -> Response {
+    let started = Instant::now();
+    let request_id = request_id_of(&request);
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        request.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    let method = request.method().to_string();
+    let path = request.uri().path().to_string();
+    let client_ip = request
+        .extensions()
+        .get::<ConnectInfo<SocketAddr>>()
+        .map(|ConnectInfo(addr)| addr.ip().to_string());
+    let mut attributes = vec![
+        KeyValue::new("http.request.method", method.clone()),
+        KeyValue::new("url.path", path.clone()),
+        KeyValue::new("request_id", request_id.clone()),
+    ];
+    if let Some(client_ip) = &client_ip {
+        attributes.push(KeyValue::new("client.address", client_ip.clone()));
+    }
+    // 路由匹配后由 route 改为 "{method} {route}"
+    let cx = telemetry::start_span_with_parent(
+        &telemetry::extract(request.headers()),
+        method.clone(),
+        SpanKind::Server,
+        attributes,
+    );
+
+    let mut response = context::with_request_id(
+        request_id.clone(),
+        next.run(request).with_context(cx.clone()),
+    )
+    .await;
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+    let user = response
+        .extensions()
+        .get::<AuthUser>()
+        .map(|user| user.subject.clone());
+    let status = response.status().as_u16();
+    let span = cx.span();
+    span.set_attribute(KeyValue::new("http.response.status_code", status as i64));
+    if let Some(user) = &user {
+        span.set_attribute(KeyValue::new("enduser.id", user.clone()));
+    }
+    if response.status().is_server_error() {
+        telemetry::record_error(&cx, response.status());
+    }
+    span.end();
+    let trace_id = span
+        .span_context()
+        .is_valid()
+        .then(|| span.span_context().trace_id().to_string());
+    info!(
+        target: "access",
+        request_id = request_id.as_str(),
+        method = method.as_str(),
+        path = path.as_str(),
+        status = status,
+        latency_ms = started.elapsed().as_millis() as u64,
+        user = user.as_deref(),
+        client_ip = client_ip.as_deref(),
+        trace_id = trace_id.as_deref();
+        "{} {} {}", method, path, status
+    );
+    response
+}
+
+/**
+ * 路由匹配后以路由模板命名当前 server span, 避免按实际路径产生大量 span 名称
+ */
+pub async fn route(request: Request, next: Next) -> Response {
+    if let Some(route) = request.extensions().get::<MatchedPath>() {
+        let cx = Context::current();
+        let span = cx.span();
+        span.update_name(format!("{} {}", request.method(), route.as_str()));
+        span.set_attribute(KeyValue::new("http.route", route.as_str().to_string()));
+    }
+    next.run(request).await
+}
@@ -0,0 +1,368 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, SCOPES};
+use crate::api::error::{ApiError, FieldError};
+use crate::api::interface::{ApiBody, ApiResult, ErrorBody};
+use crate::application::health::HealthReport;
+use crate::logging::{self, LogFilter};
+use crate::repository::entity::ApiKeyEntity;
+use axum::extract::rejection::{JsonRejection, PathRejection};
+use axum::extract::{Path, State};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use log::info;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/**
+ * 创建 API Key 请求
+ */
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct CreateApiKeyRequest {
+    #[schema(example = "ci-release")]
+    pub name: String,
+    #[schema(example = json!(["subscriptions:read", "notifications:send"]))]
+    pub scopes: Vec<String>,
+    pub expires_at: Option<DateTime<Utc>>,
+}
+
+/**
+ * API Key 信息, 不包含密钥
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeyView {
+    pub id: i64,
+    pub name: String,
+    #[schema(example = "vn_1a2b3c4d")]
+    pub prefix: String,
+    pub scopes: Vec<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub usage_count: i64,
+}
+
+impl From<ApiKeyEntity> for ApiKeyView {
+    fn from(entity: ApiKeyEntity) -> Self {
+        Self {
+            id: entity.id.unwrap_or_default(),
+            name: entity.name,
+            prefix: entity.prefix,
+            scopes: entity.scopes.0,
+            created_by: entity.created_by,
+            created_at: entity.created_at,
+            expires_at: entity.expires_at,
+            revoked_at: entity.revoked_at,
+            rotated_at: entity.rotated_at,
+            last_used_at: entity.last_used_at,
+            usage_count: entity.usage_count,
+        }
+    }
+}
+
+/**
+ * 新生成的 API Key, 明文只返回这一次
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiKeySecret {
+    pub api_key: ApiKeyView,
+    pub key: String,
+}
+
+/**
+ * 管理接口只对管理员开放, 未开启认证时无法确认调用方, 一律拒绝
+ */
+fn require_admin(user: Option<&AuthUser>) -> Result<(), ApiError> {
+    match user {
+        Some(user) if user.is_admin() => Ok(()),
+        Some(_) => Err(ApiError::Forbidden("admin permission required".to_string())),
+        None => Err(ApiError::Forbidden(
+            "admin endpoints require api.auth.enabled".to_string(),
+        )),
+    }
+}
+
+/**
+ * 创建 API Key
+ */
+#[utoipa::path(
+    post,
+    tag = "admin",
+    description = "创建 API Key",
+    path = "/api/admin/api-keys",
+    request_body = CreateApiKeyRequest,
+    responses(
+        (status = 200, description = "API key created", body = ApiBody<ApiKeySecret>),
+        (status = BAD_REQUEST, description = "Malformed request body", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Admin permission required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid fields", body = ErrorBody)
+    )
+)]
+pub async fn create_api_key(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    payload: Result<Json<CreateApiKeyRequest>, JsonRejection>,
+) -> ApiResult<ApiKeySecret> {
+    let Json(request) = payload?;
+    let user = user.map(|Extension(user)| user);
+    require_admin(user.as_ref())?;
+    let mut errors = vec![];
+    if request.name.trim().is_empty() {
+        errors.push(FieldError::new("name", "must not be empty"));
+    }
+    for (index, scope) in request.scopes.iter().enumerate() {
+        if !SCOPES.contains(&scope.as_str()) {
+            errors.push(FieldError::new(
+                format!("scopes[{}]", index),
+                format!("unknown scope {}", scope),
+            ));
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors));
+    }
+    let created_by = user.map(|user| user.subject).unwrap_or_default();
+    let (entity, key) = state
+        .services
+        .api_keys
+        .create(request.name, request.scopes, request.expires_at, created_by)
+        .await?;
+    Ok(ApiBody::success(Some(ApiKeySecret {
+        api_key: entity.into(),
+        key,
+    })))
+}
+
+/**
+ * API Key 列表
+ */
+#[utoipa::path(
+    get,
+    tag = "admin",
+    description = "API Key 列表",
+    path = "/api/admin/api-keys",
+    responses(
+        (status = 200, description = "API keys", body = ApiBody<Vec<ApiKeyView>>),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Admin permission required", body = ErrorBody)
+    )
+)]
+pub async fn list_api_keys(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+) -> ApiResult<Vec<ApiKeyView>> {
+    require_admin(user.as_deref())?;
+    let keys = state.services.api_keys.list().await?;
+    Ok(ApiBody::success(Some(
+        keys.into_iter().map(ApiKeyView::from).collect(),
+    )))
+}
+
+/**
+ * 吊销 API Key
+ */
+#[utoipa::path(
+    delete,
+    tag = "admin",
+    description = "吊销 API Key",
+    path = "/api/admin/api-keys/{id}",
+    params(("id" = i64, Path, description = "API key id")),
+    responses(
+        (status = 200, description = "API key revoked", body = ApiBody<ApiKeyView>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Admin permission required", body = ErrorBody),
+        (status = NOT_FOUND, description = "API key not found or already revoked", body = ErrorBody)
+    )
+)]
+pub async fn revoke_api_key(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<ApiKeyView> {
+    let Path(id) = path?;
+    require_admin(user.as_deref())?;
+    match state.services.api_keys.revoke(id).await? {
+        Some(entity) => Ok(ApiBody::success(Some(entity.into()))),
+        None => Err(ApiError::NotFound(format!(
+            "api key {} not found or already revoked",
+            id
+        ))),
+    }
+}
+
+/**
+ * 轮换 API Key
+ */
+#[utoipa::path(
+    post,
+    tag = "admin",
+    description = "轮换 API Key, 旧密钥立即失效",
+    path = "/api/admin/api-keys/{id}/rotate",
+    params(("id" = i64, Path, description = "API key id")),
+    responses(
+        (status = 200, description = "API key rotated", body = ApiBody<ApiKeySecret>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Admin permission required", body = ErrorBody),
+        (status = NOT_FOUND, description = "API key not found or revoked", body = ErrorBody)
+    )
+)]
+pub async fn rotate_api_key(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<ApiKeySecret> {
+    let Path(id) = path?;
+    require_admin(user.as_deref())?;
+    match state.services.api_keys.rotate(id).await? {
+        Some((entity, key)) => Ok(ApiBody::success(Some(ApiKeySecret {
+            api_key: entity.into(),
+            key,
+        }))),
+        None => Err(ApiError::NotFound(format!(
+            "api key {} not found or revoked",
+            id
+        ))),
+    }
+}
+
+/**
+ * 当前生效的日志级别
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LogLevelsView {
+    #[schema(example = "info")]
+    pub level: String,
+    /// 按模块覆盖的级别
+    #[schema(example = json!({"vision_notification::api": "debug", "sqlx": "warn"}))]
+    pub modules: BTreeMap<String, String>,
+}
+
+impl From<LogFilter> for LogLevelsView {
+    fn from(filter: LogFilter) -> Self {
+        Self {
+            level: filter.level().as_str().to_lowercase(),
+            modules: filter
+                .modules()
+                .iter()
+                .map(|(module, level)| (module.clone(), level.as_str().to_lowercase()))
+                .collect(),
+        }
+    }
+}
+
+/**
+ * 调整日志级别请求, 未出现的模块保持不变
+ */
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct UpdateLogLevelsRequest {
+    /// 全局级别
+    #[schema(example = "info")]
+    pub level: Option<String>,
+    /// 模块级别, null 表示移除覆盖
+    #[serde(default)]
+    #[schema(example = json!({"vision_notification::application::poller": "debug", "sqlx": null}))]
+    pub modules: BTreeMap<String, Option<String>>,
+}
+
+/**
+ * 就绪检查明细
+ */
+#[utoipa::path(
+    get,
+    tag = "admin",
+    description = "与 /readyz 相同的检查, 附带错误信息、耗时和检查明细",
+    path = "/api/admin/health",
+    responses(
+        (status = 200, description = "Health report", body = ApiBody<HealthReport>),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Admin permission required", body = ErrorBody)
+    )
+)]
+pub async fn get_health(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+) -> ApiResult<HealthReport> {
+    require_admin(user.as_deref())?;
+    Ok(ApiBody::success(Some(state.services.health.check().await)))
+}
+
+/**
+ * 日志级别
+ */
+#[utoipa::path(
+    get,
+    tag = "admin",
+    description = "当前生效的日志级别",
+    path = "/api/admin/log-levels",
+    responses(
+        (status = 200, description = "Log levels", body = ApiBody<LogLevelsView>),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Admin permission required", body = ErrorBody)
+    )
+)]
+pub async fn get_log_levels(user: Option<Extension<AuthUser>>) -> ApiResult<LogLevelsView> {
+    require_admin(user.as_deref())?;
+    Ok(ApiBody::success(Some(logging::filter().into())))
+}
+
+/**
+ * 调整日志级别
+ */
+#[utoipa::path(
+    put,
+    tag = "admin",
+    description = "运行时调整全局或模块的日志级别, 不写回配置, 重新加载 log 配置后以配置为准",
+    path = "/api/admin/log-levels",
+    request_body = UpdateLogLevelsRequest,
+    responses(
+        (status = 200, description = "Log levels updated", body = ApiBody<LogLevelsView>),
+        (status = BAD_REQUEST, description = "Malformed request body", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Admin permission required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid fields", body = ErrorBody)
+    )
+)]
+pub async fn update_log_levels(
+    user: Option<Extension<AuthUser>>,
+    payload: Result<Json<UpdateLogLevelsRequest>, JsonRejection>,
+) -> ApiResult<LogLevelsView> {
+    let Json(request) = payload?;
+    let user = user.map(|Extension(user)| user);
+    require_admin(user.as_ref())?;
+    let mut filter = logging::filter();
+    let mut errors = vec![];
+    if let Some(level) = &request.level {
+        match logging::parse_level(level) {
+            Ok(level) => filter.set_level(level),
+            Err(err) => errors.push(FieldError::new("level", err.to_string())),
+        }
+    }
+    for (module, level) in request.modules.iter() {
+        let field = format!("modules.{}", module);
+        if module.trim().is_empty() {
+            errors.push(FieldError::new(field, "module must not be empty"));
+            continue;
+        }
+        match level.as_deref().map(logging::parse_level).transpose() {
+            Ok(level) => filter.set_module(module.trim(), level),
+            Err(err) => errors.push(FieldError::new(field, err.to_string())),
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors));
+    }
+    info!(
+        "log levels changed to {} by {}",
+        filter,
+        user.map(|user| user.subject).unwrap_or_default()
+    );
+    logging::set_filter(filter.clone());
+    Ok(ApiBody::success(Some(filter.into())))
+}
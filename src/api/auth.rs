@@ -0,0 +1,541 @@
+use crate::api::ApiState;
+use crate::api::error::{ApiError, FieldError};
+use crate::conf::Secret;
+use crate::repository::entity::ProjectRole;
+use anyhow::{Context, anyhow};
+use axum::extract::{Request, State};
+use axum::http::header;
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use jsonwebtoken::jwk::JwkSet;
+use jsonwebtoken::{Algorithm, DecodingKey, Header, Validation, decode, decode_header};
+use log::{debug, warn};
+use serde::Deserialize;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
+
+/// JWKS 远程刷新的最小间隔
+const JWKS_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+/// 拉取远程 JWKS 的超时
+const JWKS_FETCH_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// 携带 API Key 的请求头
+pub const API_KEY_HEADER: &str = "X-API-Key";
+
+/// API Key 可授予的权限范围
+pub const SCOPES: &[&str] = &[
+    "admin",
+    "subscriptions:read",
+    "subscriptions:write",
+    "channels:read",
+    "channels:write",
+    "pipelines:read",
+    "notifications:send",
+    "deliveries:read",
+    "events:read",
+];
+
+#[derive(Deserialize, Debug, Clone, PartialEq, Default)]
+pub struct AuthArgs {
+    /// 是否开启认证
+    #[serde(default)]
+    pub enabled: bool,
+    /// HS256 密钥, 支持 file:/path 或 env:NAME 引用
+    #[serde(default)]
+    pub secret: Option<Secret>,
+    /// RS256 公钥集合, 本地文件路径或 http(s) 地址
+    #[serde(default)]
+    pub jwks: Option<String>,
+    #[serde(default)]
+    pub issuer: Option<String>,
+    #[serde(default)]
+    pub audience: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Claims {
+    sub: String,
+    #[serde(default)]
+    roles: Vec<String>,
+}
+
+/**
+ * 已认证的调用方, 认证通过后放入请求扩展
+ */
+#[derive(Debug, Clone)]
+pub struct AuthUser {
+    pub subject: String,
+    pub roles: Vec<String>,
+    /// API Key 的权限范围, 用户令牌为 None 不受限
+    pub scopes: Option<Vec<String>>,
+}
+
+impl AuthUser {
+    pub fn is_admin(&self) -> bool {
+        self.roles.iter().any(|role| role == "admin")
+            || self
+                .scopes
+                .as_ref()
+                .is_some_and(|scopes| scopes.iter().any(|scope| scope == "admin"))
+    }
+
+    pub fn has_scope(&self, scope: &str) -> bool {
+        match &self.scopes {
+            None => true,
+            Some(scopes) => scopes.iter().any(|s| s == scope || s == "admin"),
+        }
+    }
+}
+
+struct CachedJwks {
+    keys: JwkSet,
+    refreshed_at: Option<Instant>,
+}
+
+/**
+ * JWT 校验
+ */
+pub struct JwtVerifier {
+    args: AuthArgs,
+    client: reqwest::Client,
+    jwks: RwLock<CachedJwks>,
+}
+
+impl JwtVerifier {
+    pub fn new(args: AuthArgs) -> Result<Self, anyhow::Error> {
+        if args.secret.is_none() && args.jwks.is_none() {
+            return Err(anyhow!(
+                "auth is enabled but neither secret nor jwks is configured"
+            ));
+        }
+        let keys = match args.jwks.as_deref() {
+            Some(source) if !Self::is_remote(source) => {
+                let content = std::fs::read_to_string(source)
+                    .with_context(|| format!("read jwks file {} failed", source))?;
+                serde_json::from_str::<JwkSet>(&content)
+                    .with_context(|| format!("parse jwks file {} failed", source))?
+            }
+            _ => JwkSet { keys: vec![] },
+        };
+        Ok(Self {
+            args,
+            client: reqwest::Client::builder()
+                .timeout(JWKS_FETCH_TIMEOUT)
+                .build()
+                .context("create jwks client failed")?,
+            jwks: RwLock::new(CachedJwks {
+                keys,
+                refreshed_at: None,
+            }),
+        })
+    }
+
+    fn is_remote(source: &str) -> bool {
+        source.starts_with("http://") || source.starts_with("https://")
+    }
+
+    pub async fn verify(&self, token: &str) -> Result<AuthUser, anyhow::Error> {
+        let header = decode_header(token).context("invalid token header")?;
+        let key = match header.alg {
+            Algorithm::HS256 => {
+                let secret = self
+                    .args
+                    .secret
+                    .as_ref()
+                    .ok_or_else(|| anyhow!("HS256 tokens are not accepted"))?;
+                DecodingKey::from_secret(secret.expose().as_bytes())
+            }
+            Algorithm::RS256 => self.rsa_key(&header).await?,
+            alg => return Err(anyhow!("unsupported token algorithm {:?}", alg)),
+        };
+
+        let mut validation = Validation::new(header.alg);
+        // 配置了签发方或受众时令牌必须携带对应声明, 缺失不能视为通过
+        let mut required = vec!["exp"];
+        if let Some(issuer) = &self.args.issuer {
+            validation.set_issuer(&[issuer]);
+            required.push("iss");
+        }
+        match &self.args.audience {
+            Some(audience) => {
+                validation.set_audience(&[audience]);
+                required.push("aud");
+            }
+            None => validation.validate_aud = false,
+        }
+        validation.set_required_spec_claims(&required);
+        let data = decode::<Claims>(token, &key, &validation).context("invalid token")?;
+        Ok(AuthUser {
+            subject: data.claims.sub,
+            roles: data.claims.roles,
+            scopes: None,
+        })
+    }
+
+    async fn rsa_key(&self, header: &Header) -> Result<DecodingKey, anyhow::Error> {
+        let kid = header
+            .kid
+            .as_deref()
+            .ok_or_else(|| anyhow!("token header has no kid"))?;
+        if let Some(key) = self.find_key(kid).await? {
+            return Ok(key);
+        }
+        // 远程 JWKS 可能已轮换, 未命中时刷新一次
+        if self.refresh_jwks().await?
+            && let Some(key) = self.find_key(kid).await?
+        {
+            return Ok(key);
+        }
+        Err(anyhow!("no jwk found for kid {}", kid))
+    }
+
+    async fn find_key(&self, kid: &str) -> Result<Option<DecodingKey>, anyhow::Error> {
+        let jwks = self.jwks.read().await;
+        match jwks.keys.find(kid) {
+            Some(jwk) => Ok(Some(DecodingKey::from_jwk(jwk)?)),
+            None => Ok(None),
+        }
+    }
+
+    /**
+     * 拉取远程 JWKS, 拉取期间不持有锁, 其他请求仍可使用已缓存的公钥
+     */
+    async fn refresh_jwks(&self) -> Result<bool, anyhow::Error> {
+        let Some(url) = self.args.jwks.as_deref().filter(|s| Self::is_remote(s)) else {
+            return Ok(false);
+        };
+        {
+            // 先占用刷新时间, 并发的未命中不会重复拉取
+            let mut jwks = self.jwks.write().await;
+            if jwks
+                .refreshed_at
+                .is_some_and(|at| at.elapsed() < JWKS_REFRESH_INTERVAL)
+            {
+                return Ok(false);
+            }
+            jwks.refreshed_at = Some(Instant::now());
+        }
+        debug!("refreshing jwks from {}", url);
+        let keys = self
+            .client
+            .get(url)
+            .send()
+            .await
+            .context("fetch jwks failed")?
+            .error_for_status()
+            .context("fetch jwks failed")?
+            .json::<JwkSet>()
+            .await
+            .context("parse jwks failed")?;
+        self.jwks.write().await.keys = keys;
+        Ok(true)
+    }
+}
+
+/**
+ * 认证中间件
+ */
+pub async fn authenticate(
+    State(state): State<Arc<ApiState>>,
+    mut request: Request,
+    next: Next,
+) -> Response {
+    if !state.auth_enabled {
+        return next.run(request).await;
+    }
+
+    let result = if let Some(key) = header_value(&request, API_KEY_HEADER) {
+        authenticate_api_key(&state, key).await
+    } else if let Some(token) = header_value(&request, header::AUTHORIZATION.as_str())
+        .and_then(|v| v.strip_prefix("Bearer "))
+    {
+        match state.verifier.as_ref() {
+            Some(verifier) => verifier
+                .verify(token)
+                .await
+                .map_err(|err| ApiError::Unauthorized(format!("{:#}", err))),
+            None => Err(ApiError::Unauthorized(
+                "bearer tokens are not accepted".to_string(),
+            )),
+        }
+    } else {
+        Err(ApiError::Unauthorized(
+            "missing bearer token or api key".to_string(),
+        ))
+    };
+
+    match result {
+        Ok(user) => {
+            request.extensions_mut().insert(user.clone());
+            let mut response = next.run(request).await;
+            // 供访问日志记录调用方
+            response.extensions_mut().insert(user);
+            response
+        }
+        Err(err) => {
+            warn!("reject request {}: {}", request.uri(), err);
+            err.into_response()
+        }
+    }
+}
+
+fn header_value<'a>(request: &'a Request, name: &str) -> Option<&'a str> {
+    request
+        .headers()
+        .get(name)
+        .and_then(|value| value.to_str().ok())
+}
+
+async fn authenticate_api_key(state: &ApiState, key: &str) -> Result<AuthUser, ApiError> {
+    let entity = state
+        .services
+        .api_keys
+        .authenticate(key)
+        .await?
+        .ok_or_else(|| ApiError::Unauthorized("invalid api key".to_string()))?;
+    Ok(AuthUser {
+        subject: format!("api-key:{}", entity.id.unwrap_or_default()),
+        roles: vec![],
+        scopes: Some(entity.scopes.0),
+    })
+}
+
+/**
+ * 校验 API Key 拥有指定权限范围, 用户令牌不受限
+ */
+pub fn require_scope(user: Option<&AuthUser>, scope: &str) -> Result<(), ApiError> {
+    match user {
+        Some(user) if !user.has_scope(scope) => {
+            Err(ApiError::Forbidden(format!("scope {} required", scope)))
+        }
+        _ => Ok(()),
+    }
+}
+
+/**
+ * 项目所属的 DevOps 实例, 未指定时取配置了该项目的实例
+ */
+pub async fn project_source(
+    state: &ApiState,
+    source: Option<&str>,
+    project_id: &str,
+) -> Result<String, ApiError> {
+    let pipelines = &state.services.pipelines;
+    pipelines
+        .source_of(source, project_id)
+        .await?
+        .ok_or_else(|| {
+            ApiError::Validation(vec![FieldError::new(
+                "source",
+                match source {
+                    Some(source) => format!("unknown devops source {}", source),
+                    None => format!(
+                        "project {} is not configured in any devops source, specify one of {:?}",
+                        project_id,
+                        pipelines.sources()
+                    ),
+                },
+            )])
+        })
+}
+
+/**
+ * 校验调用方在实例的项目中至少拥有指定角色, 全局管理员不受限, 未开启认证时放行
+ */
+pub async fn authorize(
+    state: &ApiState,
+    user: Option<&AuthUser>,
+    source: Option<&str>,
+    project_id: &str,
+    required: ProjectRole,
+) -> Result<(), ApiError> {
+    let Some(user) = user else {
+        return Ok(());
+    };
+    if user.is_admin() {
+        return Ok(());
+    }
+    let source = project_source(state, source, project_id).await?;
+    let role = state
+        .services
+        .access
+        .role_of(&source, project_id, &user.subject)
+        .await?;
+    match role {
+        Some(role) if role >= required => Ok(()),
+        _ => Err(ApiError::Forbidden(format!(
+            "{} role on project {} required",
+            required, project_id
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{EncodingKey, encode};
+    use serde::Serialize;
+
+    const SECRET: &str = "s3cret";
+
+    #[derive(Serialize)]
+    struct TestClaims<'a> {
+        sub: &'a str,
+        roles: Vec<&'a str>,
+        exp: i64,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        iss: Option<&'a str>,
+        #[serde(skip_serializing_if = "Option::is_none")]
+        aud: Option<&'a str>,
+    }
+
+    fn claims(sub: &str) -> TestClaims<'_> {
+        TestClaims {
+            sub,
+            roles: vec!["admin"],
+            exp: chrono::Utc::now().timestamp() + 3600,
+            iss: None,
+            aud: None,
+        }
+    }
+
+    fn token(claims: &TestClaims, secret: &str) -> String {
+        encode(
+            &Header::new(Algorithm::HS256),
+            claims,
+            &EncodingKey::from_secret(secret.as_bytes()),
+        )
+        .unwrap()
+    }
+
+    fn verifier(issuer: Option<&str>, audience: Option<&str>) -> JwtVerifier {
+        JwtVerifier::new(AuthArgs {
+            enabled: true,
+            secret: Some(Secret::resolve(SECRET).unwrap()),
+            jwks: None,
+            issuer: issuer.map(str::to_string),
+            audience: audience.map(str::to_string),
+        })
+        .unwrap()
+    }
+
+    fn api_key(scopes: &[&str]) -> AuthUser {
+        AuthUser {
+            subject: "api-key:1".to_string(),
+            roles: vec![],
+            scopes: Some(scopes.iter().map(|scope| scope.to_string()).collect()),
+        }
+    }
+
+    #[test]
+    fn new_requires_secret_or_jwks() {
+        assert!(
+            JwtVerifier::new(AuthArgs {
+                enabled: true,
+                ..Default::default()
+            })
+            .is_err()
+        );
+    }
+
+    #[tokio::test]
+    async fn verify_accepts_valid_token() {
+        let user = verifier(None, None)
+            .verify(&token(&claims("alice"), SECRET))
+            .await
+            .unwrap();
+        assert_eq!(user.subject, "alice");
+        assert_eq!(user.roles, vec!["admin"]);
+        assert!(user.scopes.is_none());
+        assert!(user.is_admin());
+    }
+
+    #[tokio::test]
+    async fn verify_rejects_wrong_secret() {
+        let result = verifier(None, None)
+            .verify(&token(&claims("alice"), "other"))
+            .await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_rejects_expired_token() {
+        let mut claims = claims("alice");
+        claims.exp = chrono::Utc::now().timestamp() - 3600;
+        let result = verifier(None, None).verify(&token(&claims, SECRET)).await;
+        assert!(result.is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_rejects_malformed_token() {
+        assert!(verifier(None, None).verify("not-a-token").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn verify_checks_issuer_and_audience() {
+        let verifier = verifier(Some("vision"), Some("notification"));
+        let mut claims = claims("alice");
+        assert!(verifier.verify(&token(&claims, SECRET)).await.is_err());
+
+        claims.iss = Some("vision");
+        claims.aud = Some("other");
+        assert!(verifier.verify(&token(&claims, SECRET)).await.is_err());
+
+        claims.aud = Some("notification");
+        assert!(verifier.verify(&token(&claims, SECRET)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn verify_rejects_hs256_without_secret() {
+        let dir = std::env::temp_dir().join(format!("jwks-{}.json", std::process::id()));
+        std::fs::write(&dir, r#"{"keys":[]}"#).unwrap();
+        let verifier = JwtVerifier::new(AuthArgs {
+            enabled: true,
+            jwks: Some(dir.to_string_lossy().to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        std::fs::remove_file(&dir).unwrap();
+        let result = verifier.verify(&token(&claims("alice"), SECRET)).await;
+        assert!(result.is_err());
+    }
+
+    #[test]
+    fn user_token_has_every_scope() {
+        let user = AuthUser {
+            subject: "bob".to_string(),
+            roles: vec![],
+            scopes: None,
+        };
+        assert!(!user.is_admin());
+        for scope in SCOPES {
+            assert!(require_scope(Some(&user), scope).is_ok());
+        }
+    }
+
+    #[test]
+    fn api_key_is_limited_to_its_scopes() {
+        let user = api_key(&["channels:read"]);
+        assert!(!user.is_admin());
+        assert!(require_scope(Some(&user), "channels:read").is_ok());
+        assert!(matches!(
+            require_scope(Some(&user), "channels:write"),
+            Err(ApiError::Forbidden(_))
+        ));
+    }
+
+    #[test]
+    fn admin_scope_grants_every_scope() {
+        let user = api_key(&["admin"]);
+        assert!(user.is_admin());
+        for scope in SCOPES {
+            assert!(require_scope(Some(&user), scope).is_ok());
+        }
+    }
+
+    #[test]
+    fn anonymous_caller_passes_scope_check() {
+        assert!(require_scope(None, "admin").is_ok());
+    }
+}
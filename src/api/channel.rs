@@ -0,0 +1,518 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, authorize, require_scope};
+use crate::api::error::{ApiError, FieldError};
+use crate::api::interface::{ApiBody, ApiResult, ErrorBody};
+use crate::application::notification::excerpt;
+use crate::channel::{ChannelMessage, destination, optional_secrets, required_secrets};
+use crate::repository::entity::{ChannelEntity, ChannelKind, ProjectRole};
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{Path, Query, State};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use log::warn;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sqlx::types::Json as SqlJson;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+/// 敏感字段在响应中的占位
+const MASK: &str = "******";
+
+/**
+ * 创建/更新渠道请求
+ */
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct ChannelRequest {
+    #[schema(example = "release group")]
+    pub name: String,
+    #[schema(example = "demo")]
+    pub project_id: String,
+    pub kind: ChannelKind,
+    /// 非敏感配置, 如 email 的 smtp_host/from/to
+    #[serde(default = "default_config")]
+    #[schema(value_type = Object)]
+    pub config: Value,
+    /// 敏感字段, 只写; 更新时未提供的字段保持不变, 值为空字符串时删除
+    #[serde(default)]
+    #[schema(example = json!({"webhook_key": "693a91f6-7xxx-4bc4-97a0-0ec2sifa5aaa"}))]
+    pub secrets: BTreeMap<String, String>,
+}
+
+fn default_config() -> Value {
+    Value::Object(Default::default())
+}
+
+impl ChannelRequest {
+    /**
+     * 校验请求, secrets 为合并后的敏感字段
+     */
+    fn validate(
+        &self,
+        state: &ApiState,
+        secrets: &BTreeMap<String, String>,
+    ) -> Result<(), ApiError> {
+        let mut errors = vec![];
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "must not be empty"));
+        }
+        if self.project_id.trim().is_empty() {
+            errors.push(FieldError::new("project_id", "must not be empty"));
+        }
+        if let Err(err) = state
+            .services
+            .channels
+            .validate_config(self.kind, &self.config)
+        {
+            errors.push(FieldError::new("config", err));
+        }
+        let required = required_secrets(self.kind);
+        let optional = optional_secrets(self.kind);
+        for field in self.secrets.keys() {
+            if !required.contains(&field.as_str()) && !optional.contains(&field.as_str()) {
+                errors.push(FieldError::new(
+                    format!("secrets.{}", field),
+                    format!("unknown secret for {} channel", self.kind),
+                ));
+            }
+        }
+        for field in required {
+            if !secrets.contains_key(*field) {
+                errors.push(FieldError::new(format!("secrets.{}", field), "is required"));
+            }
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(errors))
+        }
+    }
+
+    /**
+     * 合并已保存的敏感字段
+     */
+    fn merge_secrets(&self, mut secrets: BTreeMap<String, String>) -> BTreeMap<String, String> {
+        for (field, value) in self.secrets.iter() {
+            if value.is_empty() {
+                secrets.remove(field);
+            } else {
+                secrets.insert(field.clone(), value.clone());
+            }
+        }
+        secrets
+    }
+}
+
+/**
+ * 渠道信息, 敏感字段已脱敏
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChannelView {
+    pub id: i64,
+    pub name: String,
+    pub project_id: String,
+    pub kind: ChannelKind,
+    #[schema(value_type = Object)]
+    pub config: Value,
+    #[schema(example = json!({"webhook_key": "******"}))]
+    pub secrets: BTreeMap<String, String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<ChannelEntity> for ChannelView {
+    fn from(entity: ChannelEntity) -> Self {
+        Self {
+            id: entity.id.unwrap_or_default(),
+            name: entity.name,
+            project_id: entity.project_id,
+            kind: entity.kind,
+            config: entity.config.0,
+            secrets: entity
+                .secret_fields
+                .0
+                .into_iter()
+                .map(|field| (field, MASK.to_string()))
+                .collect(),
+            created_by: entity.created_by,
+            created_at: entity.created_at,
+            updated_at: entity.updated_at,
+        }
+    }
+}
+
+/**
+ * 渠道查询条件
+ */
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ChannelQuery {
+    pub project_id: Option<String>,
+}
+
+/**
+ * 测试消息结果, 包含渠道的原始响应
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ChannelTestResult {
+    pub success: bool,
+    /// HTTP 状态码或 SMTP 应答码, 请求未送达时为空
+    pub status: Option<u16>,
+    /// 渠道响应内容, 与投递审计截取同样的长度
+    pub body: Option<String>,
+    /// 请求未送达时的错误信息
+    pub error: Option<String>,
+}
+
+async fn find_channel(state: &ApiState, id: i64) -> Result<ChannelEntity, ApiError> {
+    state
+        .services
+        .channels
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("channel {} not found", id)))
+}
+
+fn require_encryption(
+    state: &ApiState,
+    secrets: &BTreeMap<String, String>,
+) -> Result<(), ApiError> {
+    if !secrets.is_empty() && !state.services.channels.encryption_enabled() {
+        return Err(ApiError::Unavailable(
+            "channel encryption key is not configured".to_string(),
+        ));
+    }
+    Ok(())
+}
+
+/**
+ * 创建渠道
+ */
+#[utoipa::path(
+    post,
+    tag = "channels",
+    description = "创建通知渠道, 敏感字段加密保存且不会在响应中返回",
+    path = "/api/channels",
+    request_body = ChannelRequest,
+    responses(
+        (status = 200, description = "Channel created", body = ApiBody<ChannelView>),
+        (status = BAD_REQUEST, description = "Malformed request body", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid fields", body = ErrorBody),
+        (status = SERVICE_UNAVAILABLE, description = "Encryption key not configured", body = ErrorBody)
+    )
+)]
+pub async fn create_channel(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    payload: Result<Json<ChannelRequest>, JsonRejection>,
+) -> ApiResult<ChannelView> {
+    let Json(request) = payload?;
+    require_scope(user.as_deref(), "channels:write")?;
+    let secrets = request.merge_secrets(BTreeMap::new());
+    request.validate(&state, &secrets)?;
+    authorize(
+        &state,
+        user.as_deref(),
+        None,
+        &request.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    require_encryption(&state, &secrets)?;
+
+    let now = Utc::now();
+    let entity = ChannelEntity {
+        id: None,
+        name: request.name,
+        project_id: request.project_id,
+        kind: request.kind,
+        config: SqlJson(request.config),
+        secret: None,
+        secret_fields: SqlJson(vec![]),
+        created_by: user.map(|Extension(user)| user.subject).unwrap_or_default(),
+        created_at: now,
+        updated_at: now,
+    };
+    let entity = state.services.channels.create(entity, &secrets).await?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
+
+/**
+ * 渠道详情
+ */
+#[utoipa::path(
+    get,
+    tag = "channels",
+    description = "渠道详情",
+    path = "/api/channels/{id}",
+    params(("id" = i64, Path, description = "Channel id")),
+    responses(
+        (status = 200, description = "Channel", body = ApiBody<ChannelView>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Channel not found", body = ErrorBody)
+    )
+)]
+pub async fn get_channel(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<ChannelView> {
+    let Path(id) = path?;
+    require_scope(user.as_deref(), "channels:read")?;
+    let entity = find_channel(&state, id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        None,
+        &entity.project_id,
+        ProjectRole::Viewer,
+    )
+    .await?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
+
+/**
+ * 渠道列表
+ */
+#[utoipa::path(
+    get,
+    tag = "channels",
+    description = "渠道列表, 非管理员只返回所在项目的渠道",
+    path = "/api/channels",
+    params(ChannelQuery),
+    responses(
+        (status = 200, description = "Channels", body = ApiBody<Vec<ChannelView>>),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody)
+    )
+)]
+pub async fn list_channels(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    query: Result<Query<ChannelQuery>, QueryRejection>,
+) -> ApiResult<Vec<ChannelView>> {
+    let Query(query) = query?;
+    require_scope(user.as_deref(), "channels:read")?;
+    let project_ids = match (query.project_id, user.as_deref()) {
+        (Some(project_id), user) => {
+            authorize(&state, user, None, &project_id, ProjectRole::Viewer).await?;
+            Some(vec![project_id])
+        }
+        (None, Some(user)) if !user.is_admin() => Some(
+            state
+                .services
+                .access
+                .projects_of(None, &user.subject)
+                .await?,
+        ),
+        _ => None,
+    };
+    let entities = state.services.channels.list(project_ids.as_deref()).await?;
+    Ok(ApiBody::success(Some(
+        entities.into_iter().map(ChannelView::from).collect(),
+    )))
+}
+
+/**
+ * 更新渠道
+ */
+#[utoipa::path(
+    put,
+    tag = "channels",
+    description = "更新渠道, 未提供的敏感字段保持不变",
+    path = "/api/channels/{id}",
+    params(("id" = i64, Path, description = "Channel id")),
+    request_body = ChannelRequest,
+    responses(
+        (status = 200, description = "Channel updated", body = ApiBody<ChannelView>),
+        (status = BAD_REQUEST, description = "Malformed id or request body", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Channel not found", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid fields", body = ErrorBody),
+        (status = SERVICE_UNAVAILABLE, description = "Encryption key not configured", body = ErrorBody)
+    )
+)]
+pub async fn update_channel(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+    payload: Result<Json<ChannelRequest>, JsonRejection>,
+) -> ApiResult<ChannelView> {
+    let Path(id) = path?;
+    let Json(request) = payload?;
+    require_scope(user.as_deref(), "channels:write")?;
+    let existing = find_channel(&state, id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        None,
+        &existing.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    // 渠道类型变化时原有敏感字段不再适用
+    let saved = if request.kind == existing.kind {
+        state.services.channels.secrets(&existing)?
+    } else {
+        BTreeMap::new()
+    };
+    // 目的地变化时必须重新提供敏感字段, 避免把已保存的密钥发往新的地址
+    if request.kind == existing.kind
+        && destination(request.kind, &request.config)
+            != destination(existing.kind, &existing.config.0)
+    {
+        let missing = saved
+            .keys()
+            .filter(|field| {
+                request
+                    .secrets
+                    .get(*field)
+                    .is_none_or(|value| value.is_empty())
+            })
+            .map(|field| {
+                FieldError::new(
+                    format!("secrets.{}", field),
+                    "must be provided again when the destination changes",
+                )
+            })
+            .collect::<Vec<_>>();
+        if !missing.is_empty() {
+            return Err(ApiError::Validation(missing));
+        }
+    }
+    let secrets = request.merge_secrets(saved);
+    request.validate(&state, &secrets)?;
+    if request.project_id != existing.project_id {
+        authorize(
+            &state,
+            user.as_deref(),
+            None,
+            &request.project_id,
+            ProjectRole::Editor,
+        )
+        .await?;
+    }
+    require_encryption(&state, &secrets)?;
+
+    let entity = ChannelEntity {
+        name: request.name,
+        project_id: request.project_id,
+        kind: request.kind,
+        config: SqlJson(request.config),
+        updated_at: Utc::now(),
+        ..existing
+    };
+    let entity = state.services.channels.update(entity, &secrets).await?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
+
+/**
+ * 删除渠道
+ */
+#[utoipa::path(
+    delete,
+    tag = "channels",
+    description = "删除渠道, 仍被订阅引用时拒绝删除",
+    path = "/api/channels/{id}",
+    params(("id" = i64, Path, description = "Channel id")),
+    responses(
+        (status = 200, description = "Channel deleted", body = ApiBody<ChannelView>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Channel not found", body = ErrorBody),
+        (status = CONFLICT, description = "Channel is used by subscriptions", body = ErrorBody)
+    )
+)]
+pub async fn delete_channel(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<ChannelView> {
+    let Path(id) = path?;
+    require_scope(user.as_deref(), "channels:write")?;
+    let existing = find_channel(&state, id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        None,
+        &existing.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    let subscriptions = state.services.channels.subscriptions(id).await?;
+    if subscriptions > 0 {
+        return Err(ApiError::Conflict(format!(
+            "channel {} is used by {} subscriptions",
+            id, subscriptions
+        )));
+    }
+    let entity = state.services.channels.delete(id).await?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
+
+/**
+ * 发送测试消息
+ */
+#[utoipa::path(
+    post,
+    tag = "channels",
+    description = "通过渠道发送一条测试消息, 返回渠道的原始响应",
+    path = "/api/channels/{id}/test",
+    params(("id" = i64, Path, description = "Channel id")),
+    responses(
+        (status = 200, description = "Test message result", body = ApiBody<ChannelTestResult>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Channel not found", body = ErrorBody)
+    )
+)]
+pub async fn test_channel(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<ChannelTestResult> {
+    let Path(id) = path?;
+    require_scope(user.as_deref(), "channels:write")?;
+    let channel = find_channel(&state, id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        None,
+        &channel.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    let message = ChannelMessage {
+        title: "Test notification".to_string(),
+        content: format!("This is a test message from channel **{}**.", channel.name),
+    };
+    // 发送失败同样返回 200, 便于调用方查看原因; webhook 地址受 allowed_base_urls 限制
+    let result = match state.services.channels.send(&channel, &message).await {
+        Ok(response) => ChannelTestResult {
+            success: response.success,
+            status: Some(response.status),
+            body: Some(excerpt(&response.body)),
+            error: None,
+        },
+        Err(err) => {
+            warn!("test message of channel {} failed. {:#}", id, err);
+            ChannelTestResult {
+                success: false,
+                status: None,
+                body: None,
+                error: Some(format!("{:#}", err)),
+            }
+        }
+    };
+    Ok(ApiBody::success(Some(result)))
+}
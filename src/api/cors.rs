@@ -0,0 +1,117 @@
+use anyhow::anyhow;
+use axum::http::{HeaderName, HeaderValue, Method};
+use serde::Deserialize;
+use std::time::Duration;
+use tower_http::cors::{AllowOrigin, CorsLayer};
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct CorsArgs {
+    /// 允许的来源, 为空时不开启跨域, "*" 表示任意来源
+    #[serde(default)]
+    pub allowed_origins: Vec<String>,
+    #[serde(default = "default_allowed_methods")]
+    pub allowed_methods: Vec<String>,
+    #[serde(default = "default_allowed_headers")]
+    pub allowed_headers: Vec<String>,
+    /// 允许浏览器读取的响应头
+    #[serde(default = "default_exposed_headers")]
+    pub exposed_headers: Vec<String>,
+    /// 是否允许携带 Cookie, 不能与任意来源同时使用
+    #[serde(default)]
+    pub allow_credentials: bool,
+    /// 预检结果缓存时间(秒)
+    #[serde(default = "default_max_age")]
+    pub max_age: u64,
+}
+
+fn default_allowed_methods() -> Vec<String> {
+    ["GET", "POST", "PUT", "DELETE"].map(String::from).to_vec()
+}
+
+fn default_allowed_headers() -> Vec<String> {
+    [
+        "authorization",
+        "content-type",
+        "x-api-key",
+        "x-request-id",
+        "last-event-id",
+    ]
+    .map(String::from)
+    .to_vec()
+}
+
+fn default_exposed_headers() -> Vec<String> {
+    ["x-request-id", "retry-after"].map(String::from).to_vec()
+}
+
+fn default_max_age() -> u64 {
+    600
+}
+
+impl Default for CorsArgs {
+    fn default() -> Self {
+        Self {
+            allowed_origins: vec![],
+            allowed_methods: default_allowed_methods(),
+            allowed_headers: default_allowed_headers(),
+            exposed_headers: default_exposed_headers(),
+            allow_credentials: false,
+            max_age: default_max_age(),
+        }
+    }
+}
+
+impl CorsArgs {
+    /**
+     * 未配置来源时返回 None
+     */
+    pub fn layer(&self) -> Result<Option<CorsLayer>, anyhow::Error> {
+        if self.allowed_origins.is_empty() {
+            return Ok(None);
+        }
+        let origin = if self.allowed_origins.iter().any(|origin| origin == "*") {
+            if self.allow_credentials {
+                return Err(anyhow!(
+                    "cors allow_credentials can not be used with wildcard origin"
+                ));
+            }
+            AllowOrigin::any()
+        } else {
+            let origins = self
+                .allowed_origins
+                .iter()
+                .map(|origin| {
+                    HeaderValue::from_str(origin.trim_end_matches('/'))
+                        .map_err(|_| anyhow!("invalid cors origin {}", origin))
+                })
+                .collect::<Result<Vec<_>, _>>()?;
+            AllowOrigin::list(origins)
+        };
+        let methods = self
+            .allowed_methods
+            .iter()
+            .map(|method| {
+                Method::from_bytes(method.to_uppercase().as_bytes())
+                    .map_err(|_| anyhow!("invalid cors method {}", method))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+        let headers = |names: &[String]| {
+            names
+                .iter()
+                .map(|name| {
+                    HeaderName::from_bytes(name.as_bytes())
+                        .map_err(|_| anyhow!("invalid cors header {}", name))
+                })
+                .collect::<Result<Vec<_>, _>>()
+        };
+        Ok(Some(
+            CorsLayer::new()
+                .allow_origin(origin)
+                .allow_methods(methods)
+                .allow_headers(headers(&self.allowed_headers)?)
+                .expose_headers(headers(&self.exposed_headers)?)
+                .allow_credentials(self.allow_credentials)
+                .max_age(Duration::from_secs(self.max_age)),
+        ))
+    }
+}
@@ -0,0 +1,308 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, authorize, require_scope};
+use crate::api::error::{ApiError, FieldError};
+use crate::api::interface::{ApiBody, ApiResult, ErrorBody, Page};
+use crate::repository::entity::{
+    DeliveryAttemptEntity, DeliveryAttemptFilter, DeliveryStatus, ProjectRole,
+};
+use axum::Extension;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Query, State};
+use axum::http::header;
+use axum::response::{IntoResponse, Response};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+/// 默认每页条数
+const DEFAULT_PAGE_SIZE: u32 = 20;
+/// 每页最大条数
+const MAX_PAGE_SIZE: u32 = 200;
+/// 单次导出的最大条数
+const MAX_EXPORT_ROWS: u32 = 10000;
+
+/**
+ * 投递尝试记录
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeliveryAttemptView {
+    pub id: i64,
+    pub delivery_id: i64,
+    pub notification_id: i64,
+    pub project_id: String,
+    pub pipeline_id: Option<String>,
+    pub build_id: Option<String>,
+    #[schema(example = "manual")]
+    pub source: String,
+    pub event: Option<String>,
+    /// 命中的订阅
+    pub subscription_id: Option<i64>,
+    pub channel_id: i64,
+    pub attempt: i32,
+    pub status: DeliveryStatus,
+    /// 渲染后消息的 sha256
+    pub payload_hash: String,
+    pub response_status: Option<i32>,
+    pub response_excerpt: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+    pub attempted_at: DateTime<Utc>,
+}
+
+impl From<DeliveryAttemptEntity> for DeliveryAttemptView {
+    fn from(entity: DeliveryAttemptEntity) -> Self {
+        Self {
+            id: entity.id.unwrap_or_default(),
+            delivery_id: entity.delivery_id,
+            notification_id: entity.notification_id,
+            project_id: entity.project_id,
+            pipeline_id: entity.pipeline_id,
+            build_id: entity.build_id,
+            source: entity.source,
+            event: entity.event,
+            subscription_id: entity.subscription_id,
+            channel_id: entity.channel_id,
+            attempt: entity.attempt,
+            status: entity.status,
+            payload_hash: entity.payload_hash,
+            response_status: entity.response_status,
+            response_excerpt: entity.response_excerpt,
+            error: entity.error,
+            latency_ms: entity.latency_ms,
+            attempted_at: entity.attempted_at,
+        }
+    }
+}
+
+/**
+ * 投递记录查询条件
+ */
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct DeliveryQuery {
+    pub project_id: Option<String>,
+    pub pipeline_id: Option<String>,
+    pub build_id: Option<String>,
+    pub channel_id: Option<i64>,
+    pub status: Option<DeliveryStatus>,
+    /// 尝试时间下限(包含)
+    pub from: Option<DateTime<Utc>>,
+    /// 尝试时间上限(不包含)
+    pub to: Option<DateTime<Utc>>,
+    /// 页码, 从 1 开始
+    pub page: Option<u32>,
+    /// 每页条数, 默认 20, 最大 200
+    pub page_size: Option<u32>,
+}
+
+impl DeliveryQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut errors = vec![];
+        if self.page == Some(0) {
+            errors.push(FieldError::new("page", "must be at least 1"));
+        }
+        if self
+            .page_size
+            .is_some_and(|page_size| page_size == 0 || page_size > MAX_PAGE_SIZE)
+        {
+            errors.push(FieldError::new(
+                "page_size",
+                format!("must be between 1 and {}", MAX_PAGE_SIZE),
+            ));
+        }
+        if let (Some(from), Some(to)) = (self.from, self.to)
+            && from >= to
+        {
+            errors.push(FieldError::new("to", "must be after from"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(errors))
+        }
+    }
+
+    /**
+     * 转换为查询条件, 非管理员只能查看所在项目
+     */
+    async fn to_filter(
+        &self,
+        state: &ApiState,
+        user: Option<&AuthUser>,
+    ) -> Result<DeliveryAttemptFilter, ApiError> {
+        let mut filter = DeliveryAttemptFilter {
+            project_id: self.project_id.clone(),
+            project_ids: None,
+            pipeline_id: self.pipeline_id.clone(),
+            build_id: self.build_id.clone(),
+            channel_id: self.channel_id,
+            status: self.status,
+            from: self.from,
+            to: self.to,
+        };
+        match (&filter.project_id, user) {
+            (Some(project_id), user) => {
+                authorize(state, user, None, project_id, ProjectRole::Viewer).await?;
+            }
+            (None, Some(user)) if !user.is_admin() => {
+                filter.project_ids = Some(
+                    state
+                        .services
+                        .access
+                        .projects_of(None, &user.subject)
+                        .await?,
+                );
+            }
+            _ => {}
+        }
+        Ok(filter)
+    }
+}
+
+/**
+ * 投递记录
+ */
+#[utoipa::path(
+    get,
+    tag = "deliveries",
+    description = "投递尝试审计记录, 按时间倒序分页, 非管理员只返回所在项目的记录",
+    path = "/api/deliveries",
+    params(DeliveryQuery),
+    responses(
+        (status = 200, description = "Delivery attempts", body = ApiBody<Page<DeliveryAttemptView>>),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid query", body = ErrorBody)
+    )
+)]
+pub async fn list_deliveries(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    query: Result<Query<DeliveryQuery>, QueryRejection>,
+) -> ApiResult<Page<DeliveryAttemptView>> {
+    let Query(query) = query?;
+    require_scope(user.as_deref(), "deliveries:read")?;
+    query.validate()?;
+    let filter = query.to_filter(&state, user.as_deref()).await?;
+    let page = query.page.unwrap_or(1);
+    let page_size = query.page_size.unwrap_or(DEFAULT_PAGE_SIZE);
+    let offset = (page - 1).saturating_mul(page_size);
+    let (attempts, total) = state
+        .services
+        .notifications
+        .attempts(&filter, offset, page_size)
+        .await?;
+    Ok(ApiBody::success(Some(Page {
+        items: attempts
+            .into_iter()
+            .map(DeliveryAttemptView::from)
+            .collect(),
+        total,
+        page,
+        page_size,
+    })))
+}
+
+/**
+ * 导出投递记录
+ */
+#[utoipa::path(
+    get,
+    tag = "deliveries",
+    description = "按查询条件导出投递尝试记录为 CSV, 忽略分页参数, 最多 10000 条",
+    path = "/api/deliveries/export",
+    params(DeliveryQuery),
+    responses(
+        (status = 200, description = "CSV file", content_type = "text/csv", body = String),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid query", body = ErrorBody)
+    )
+)]
+pub async fn export_deliveries(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    query: Result<Query<DeliveryQuery>, QueryRejection>,
+) -> Result<Response, ApiError> {
+    let Query(query) = query?;
+    require_scope(user.as_deref(), "deliveries:read")?;
+    query.validate()?;
+    let filter = query.to_filter(&state, user.as_deref()).await?;
+    let (attempts, _) = state
+        .services
+        .notifications
+        .attempts(&filter, 0, MAX_EXPORT_ROWS)
+        .await?;
+
+    let mut csv = String::from(
+        "id,attempted_at,project_id,pipeline_id,build_id,source,event,subscription_id,channel_id,\
+         notification_id,delivery_id,attempt,status,latency_ms,response_status,payload_hash,\
+         error,response_excerpt\n",
+    );
+    for attempt in attempts {
+        let status = match attempt.status {
+            DeliveryStatus::Pending => "pending",
+            DeliveryStatus::Sending => "sending",
+            DeliveryStatus::Succeed => "succeed",
+            DeliveryStatus::Failed => "failed",
+        };
+        let row = [
+            attempt.id.unwrap_or_default().to_string(),
+            attempt.attempted_at.to_rfc3339(),
+            attempt.project_id,
+            attempt.pipeline_id.unwrap_or_default(),
+            attempt.build_id.unwrap_or_default(),
+            attempt.source,
+            attempt.event.unwrap_or_default(),
+            optional(attempt.subscription_id),
+            attempt.channel_id.to_string(),
+            attempt.notification_id.to_string(),
+            attempt.delivery_id.to_string(),
+            attempt.attempt.to_string(),
+            status.to_string(),
+            attempt.latency_ms.to_string(),
+            optional(attempt.response_status),
+            attempt.payload_hash,
+            attempt.error.unwrap_or_default(),
+            attempt.response_excerpt.unwrap_or_default(),
+        ];
+        let row: Vec<String> = row.iter().map(|field| csv_field(field)).collect();
+        csv.push_str(&row.join(","));
+        csv.push('\n');
+    }
+    let filename = format!(
+        "attachment; filename=\"deliveries-{}.csv\"",
+        Utc::now().format("%Y%m%d%H%M%S")
+    );
+    Ok((
+        [
+            (header::CONTENT_TYPE, "text/csv; charset=utf-8".to_string()),
+            (header::CONTENT_DISPOSITION, filename),
+        ],
+        csv,
+    )
+        .into_response())
+}
+
+fn optional<T: ToString>(value: Option<T>) -> String {
+    value.map(|value| value.to_string()).unwrap_or_default()
+}
+
+/**
+ * CSV 字段转义, 以公式字符开头的值加前缀, 避免在表格软件中被执行
+ */
+fn csv_field(value: &str) -> String {
+    let value = if value.starts_with(['=', '+', '-', '@']) {
+        format!("'{}", value)
+    } else {
+        value.to_string()
+    };
+    if value.contains([',', '"', '\n', '\r']) {
+        format!("\"{}\"", value.replace('"', "\"\""))
+    } else {
+        value
+    }
+}
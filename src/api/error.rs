@@ -0,0 +1,160 @@
+use crate::api::interface::ApiBody;
+use crate::context;
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::http::{HeaderValue, StatusCode, header};
+use axum::response::{IntoResponse, Response};
+use log::error;
+use serde::Serialize;
+use thiserror::Error;
+use utoipa::ToSchema;
+
+/**
+ * 错误码, 取值为 HTTP 状态码 * 100 + 序号, 对外保持稳定
+ *
+ * | code  | HTTP |
+ * |-------|------|
+ * | 40000 | 400  |
+ * | 40100 | 401  |
+ * | 40300 | 403  |
+ * | 40400 | 404  |
+ * | 40900 | 409  |
+ * | 42200 | 422  |
+ * | 42900 | 429  |
+ * | 50000 | 500  |
+ * | 50300 | 503  |
+ */
+pub mod code {
+    pub const SUCCESS: i32 = 0;
+    pub const BAD_REQUEST: i32 = 40000;
+    pub const UNAUTHORIZED: i32 = 40100;
+    pub const FORBIDDEN: i32 = 40300;
+    pub const NOT_FOUND: i32 = 40400;
+    pub const CONFLICT: i32 = 40900;
+    pub const VALIDATION: i32 = 42200;
+    pub const TOO_MANY_REQUESTS: i32 = 42900;
+    pub const INTERNAL: i32 = 50000;
+    pub const UNAVAILABLE: i32 = 50300;
+}
+
+/**
+ * 错误码对应的 HTTP 状态码
+ */
+pub fn status_of(code: i32) -> StatusCode {
+    if code == code::SUCCESS {
+        return StatusCode::OK;
+    }
+    u16::try_from(code / 100)
+        .ok()
+        .and_then(|status| StatusCode::from_u16(status).ok())
+        .filter(|status| status.is_client_error() || status.is_server_error())
+        .unwrap_or(StatusCode::INTERNAL_SERVER_ERROR)
+}
+
+/**
+ * 字段校验错误
+ */
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct FieldError {
+    #[schema(example = "name")]
+    pub field: String,
+    #[schema(example = "must not be empty")]
+    pub message: String,
+}
+
+impl FieldError {
+    pub fn new(field: impl ToString, message: impl ToString) -> Self {
+        Self {
+            field: field.to_string(),
+            message: message.to_string(),
+        }
+    }
+}
+
+/**
+ * 接口错误
+ */
+#[allow(unused)]
+#[derive(Debug, Error)]
+pub enum ApiError {
+    #[error("{0}")]
+    BadRequest(String),
+    #[error("{0}")]
+    Unauthorized(String),
+    #[error("{0}")]
+    Forbidden(String),
+    #[error("{0}")]
+    NotFound(String),
+    #[error("{0}")]
+    Conflict(String),
+    #[error("validation failed")]
+    Validation(Vec<FieldError>),
+    #[error("too many requests, retry after {retry_after} seconds")]
+    TooManyRequests { retry_after: u64 },
+    #[error("{0}")]
+    Unavailable(String),
+    #[error("internal server error")]
+    Internal(#[from] anyhow::Error),
+}
+
+impl ApiError {
+    pub fn code(&self) -> i32 {
+        match self {
+            ApiError::BadRequest(_) => code::BAD_REQUEST,
+            ApiError::Unauthorized(_) => code::UNAUTHORIZED,
+            ApiError::Forbidden(_) => code::FORBIDDEN,
+            ApiError::NotFound(_) => code::NOT_FOUND,
+            ApiError::Conflict(_) => code::CONFLICT,
+            ApiError::Validation(_) => code::VALIDATION,
+            ApiError::TooManyRequests { .. } => code::TOO_MANY_REQUESTS,
+            ApiError::Unavailable(_) => code::UNAVAILABLE,
+            ApiError::Internal(_) => code::INTERNAL,
+        }
+    }
+}
+
+impl From<JsonRejection> for ApiError {
+    fn from(rejection: JsonRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<QueryRejection> for ApiError {
+    fn from(rejection: QueryRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl From<PathRejection> for ApiError {
+    fn from(rejection: PathRejection) -> Self {
+        ApiError::BadRequest(rejection.body_text())
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        let message = self.to_string();
+        let (data, retry_after) = match &self {
+            ApiError::Validation(errors) => (Some(errors.clone()), None),
+            ApiError::TooManyRequests { retry_after } => (None, Some(*retry_after)),
+            ApiError::Internal(err) => {
+                // 细节只记录在日志中
+                error!(
+                    "request {} internal error: {:?}",
+                    context::request_id().unwrap_or_default(),
+                    err
+                );
+                (None, None)
+            }
+            _ => (None, None),
+        };
+        let mut response = ApiBody::new(self.code(), message, data).into_response();
+        let headers = response.headers_mut();
+        if let ApiError::Unauthorized(_) = self {
+            headers.insert(header::WWW_AUTHENTICATE, HeaderValue::from_static("Bearer"));
+        }
+        if let Some(retry_after) = retry_after {
+            headers.insert(header::RETRY_AFTER, HeaderValue::from(retry_after));
+        }
+        response
+    }
+}
@@ -0,0 +1,188 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, authorize, require_scope};
+use crate::api::error::ApiError;
+use crate::api::interface::ErrorBody;
+use crate::application::event::{Event, EventBus};
+use crate::repository::entity::ProjectRole;
+use axum::Extension;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Query, State};
+use axum::http::HeaderMap;
+use axum::response::sse::{Event as SseEvent, KeepAlive, Sse};
+use log::{debug, error};
+use serde::Deserialize;
+use std::collections::HashSet;
+use std::convert::Infallible;
+use std::sync::Arc;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+use tokio::sync::mpsc;
+use tokio_stream::Stream;
+use tokio_stream::StreamExt;
+use tokio_stream::wrappers::ReceiverStream;
+use utoipa::IntoParams;
+
+/// 断线续传的请求头
+const LAST_EVENT_ID: &str = "last-event-id";
+/// 单个连接待发送的事件数
+const STREAM_BUFFER: usize = 64;
+
+/**
+ * 事件流订阅条件
+ */
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct EventQuery {
+    pub project_id: Option<String>,
+    pub pipeline_id: Option<String>,
+    /// 续传起点, 无法设置 Last-Event-ID 请求头时使用
+    pub last_event_id: Option<u64>,
+}
+
+struct EventFilter {
+    project_id: Option<String>,
+    pipeline_id: Option<String>,
+    /// 非管理员可见的项目
+    projects: Option<HashSet<String>>,
+}
+
+impl EventFilter {
+    fn matches(&self, event: &Event) -> bool {
+        self.project_id
+            .as_ref()
+            .is_none_or(|project_id| *project_id == event.project_id)
+            && self
+                .pipeline_id
+                .as_ref()
+                .is_none_or(|pipeline_id| event.pipeline_id.as_ref() == Some(pipeline_id))
+            && self
+                .projects
+                .as_ref()
+                .is_none_or(|projects| projects.contains(&event.project_id))
+    }
+}
+
+/**
+ * 实时事件流
+ */
+#[utoipa::path(
+    get,
+    tag = "events",
+    description = "以 Server-Sent Events 推送构建、通知及投递事件. 事件的 id 可通过 Last-Event-ID 请求头续传, \
+                   服务只保留最近的事件, 超出部分不会补发; 空闲时定期发送 heartbeat 注释",
+    path = "/api/events/stream",
+    params(
+        EventQuery,
+        ("Last-Event-ID" = Option<u64>, Header, description = "上次收到的事件 id")
+    ),
+    responses(
+        (status = 200, description = "Event stream", content_type = "text/event-stream", body = String),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody)
+    )
+)]
+pub async fn stream_events(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    headers: HeaderMap,
+    query: Result<Query<EventQuery>, QueryRejection>,
+) -> Result<Sse<impl Stream<Item = Result<SseEvent, Infallible>>>, ApiError> {
+    let Query(query) = query?;
+    let user = user.as_deref();
+    require_scope(user, "events:read")?;
+    let last_id = match headers.get(LAST_EVENT_ID) {
+        Some(value) => Some(
+            value
+                .to_str()
+                .ok()
+                .and_then(|value| value.trim().parse::<u64>().ok())
+                .ok_or_else(|| ApiError::BadRequest("invalid Last-Event-ID header".to_string()))?,
+        ),
+        None => query.last_event_id,
+    };
+    let projects = match (&query.project_id, user) {
+        (Some(project_id), user) => {
+            authorize(&state, user, None, project_id, ProjectRole::Viewer).await?;
+            None
+        }
+        (None, Some(user)) if !user.is_admin() => Some(
+            state
+                .services
+                .access
+                .projects_of(None, &user.subject)
+                .await?
+                .into_iter()
+                .collect(),
+        ),
+        _ => None,
+    };
+    let filter = EventFilter {
+        project_id: query.project_id,
+        pipeline_id: query.pipeline_id,
+        projects,
+    };
+
+    let (sender, receiver) = mpsc::channel(STREAM_BUFFER);
+    tokio::spawn(forward(
+        state.services.events.clone(),
+        filter,
+        last_id,
+        sender,
+        state.shutdown.clone(),
+    ));
+    let stream = ReceiverStream::new(receiver).map(Ok);
+    Ok(Sse::new(stream).keep_alive(KeepAlive::new().interval(state.heartbeat).text("heartbeat")))
+}
+
+/**
+ * 将总线上的事件转发给连接, 连接断开或服务停止时结束
+ */
+async fn forward(
+    events: EventBus,
+    filter: EventFilter,
+    mut last_id: Option<u64>,
+    sender: mpsc::Sender<SseEvent>,
+    shutdown: tokio_util::sync::CancellationToken,
+) {
+    let (mut missed, mut receiver) = events.subscribe(last_id);
+    loop {
+        for event in missed.drain(..) {
+            if !send(&sender, &filter, &event).await {
+                return;
+            }
+            last_id = Some(event.id);
+        }
+        select! {
+            _ = shutdown.cancelled() => return,
+            _ = sender.closed() => return,
+            received = receiver.recv() => match received {
+                Ok(event) => missed.push(event),
+                // 消费过慢, 从缓冲区补发后重新订阅
+                Err(RecvError::Lagged(skipped)) => {
+                    debug!("event stream lagged {} events, resuming from buffer", skipped);
+                    (missed, receiver) = events.subscribe(last_id);
+                }
+                Err(RecvError::Closed) => return,
+            },
+        }
+    }
+}
+
+async fn send(sender: &mpsc::Sender<SseEvent>, filter: &EventFilter, event: &Event) -> bool {
+    if !filter.matches(event) {
+        return true;
+    }
+    let message = match SseEvent::default()
+        .id(event.id.to_string())
+        .event(&event.kind)
+        .json_data(event)
+    {
+        Ok(message) => message,
+        Err(err) => {
+            error!("serialize event {} failed. {:?}", event.id, err);
+            return true;
+        }
+    };
+    sender.send(message).await.is_ok()
+}
@@ -0,0 +1,82 @@
+use crate::api::ApiState;
+use crate::application::health::{HealthReport, HealthStatus};
+use axum::Json;
+use axum::extract::State;
+use axum::http::StatusCode;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct LivenessView {
+    #[schema(example = "ok")]
+    pub status: String,
+}
+
+/**
+ * 公开的就绪状态, 只包含各项检查的状态; 错误信息等明细见 /api/admin/health
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ReadinessView {
+    pub status: HealthStatus,
+    /// 正在停止, 不再接收新流量
+    pub draining: bool,
+    pub checks: BTreeMap<String, HealthStatus>,
+}
+
+impl From<HealthReport> for ReadinessView {
+    fn from(report: HealthReport) -> Self {
+        Self {
+            status: report.status,
+            draining: report.draining,
+            checks: report
+                .checks
+                .into_iter()
+                .map(|(name, check)| (name, check.status))
+                .collect(),
+        }
+    }
+}
+
+/**
+ * 存活检查
+ */
+#[utoipa::path(
+    get,
+    tag = "health",
+    description = "进程存活即返回 200, 不检查依赖",
+    path = "/healthz",
+    responses(
+        (status = 200, description = "Process is alive", body = LivenessView)
+    )
+)]
+pub async fn liveness() -> Json<LivenessView> {
+    Json(LivenessView {
+        status: "ok".to_string(),
+    })
+}
+
+/**
+ * 就绪检查
+ */
+#[utoipa::path(
+    get,
+    tag = "health",
+    description = "检查数据库、迁移、DevOps 连通性(结果缓存)及通知渠道. \
+                   数据库或迁移异常、服务正在停止时返回 503, DevOps 或渠道异常只标记为 degraded. \
+                   未认证即可访问, 只返回状态",
+    path = "/readyz",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = ReadinessView),
+        (status = SERVICE_UNAVAILABLE, description = "Not ready", body = ReadinessView)
+    )
+)]
+pub async fn readiness(State(state): State<Arc<ApiState>>) -> (StatusCode, Json<ReadinessView>) {
+    let report = state.services.health.check().await;
+    let status = match report.status {
+        HealthStatus::Unavailable => StatusCode::SERVICE_UNAVAILABLE,
+        _ => StatusCode::OK,
+    };
+    (status, Json(report.into()))
+}
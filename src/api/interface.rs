@@ -1,9 +1,11 @@
-use axum::http::{Method, StatusCode, Uri};
+use crate::api::error::{ApiError, FieldError, status_of};
+use crate::context;
+use axum::http::{Method, Uri};
 use axum::response::{IntoResponse, Response};
 use axum::{BoxError, Json};
 use getset::Getters;
 use serde::{Deserialize, Serialize};
-use utoipa::openapi::security::{HttpAuthScheme, HttpBuilder};
+use utoipa::openapi::security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder};
 use utoipa::{Modify, OpenApi, ToSchema};
 
 /**
@@ -18,6 +20,9 @@ pub struct ApiBody<T> {
     #[schema(example = "success")]
     message: String,
     data: Option<T>,
+    /// 请求 ID, 与响应头 X-Request-Id 相同
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    request_id: Option<String>,
 }
 
 impl<T> ApiBody<T> {
@@ -26,23 +31,21 @@ impl<T> ApiBody<T> {
             code,
             message,
             data,
+            request_id: context::request_id(),
         }
     }
 
     pub fn success(data: Option<T>) -> Self {
-        ApiBody {
-            code: 0,
-            message: "success".to_string(),
+        Self::new(
+            crate::api::error::code::SUCCESS,
+            "success".to_string(),
             data,
-        }
+        )
     }
 
+    #[allow(unused)]
     pub fn failure(code: i32, message: String) -> Self {
-        ApiBody {
-            code,
-            message,
-            data: None,
-        }
+        Self::new(code, message, None)
     }
 }
 
@@ -51,24 +54,39 @@ impl<T> ApiBody<T> {
  */
 impl<T: Serialize> IntoResponse for ApiBody<T> {
     fn into_response(self) -> Response {
-        // 可根据 code 映射为 HTTP 状态码
-        let status = if self.code == 0 {
-            StatusCode::OK
-        } else {
-            StatusCode::INTERNAL_SERVER_ERROR
-        };
-        (status, Json(self)).into_response()
+        // 根据 code 映射为 HTTP 状态码
+        (status_of(self.code), Json(self)).into_response()
     }
 }
 
+/**
+ * 分页结果
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page<T> {
+    pub items: Vec<T>,
+    pub total: i64,
+    #[schema(example = 1)]
+    pub page: u32,
+    #[schema(example = 20)]
+    pub page_size: u32,
+}
+
+/**
+ * 接口返回
+ */
+pub type ApiResult<T> = Result<ApiBody<T>, ApiError>;
+
+/**
+ * 错误响应体, 校验失败时 data 为字段错误列表
+ */
+pub type ErrorBody = ApiBody<Vec<FieldError>>;
+
 /**
  * 404 处理
  */
-pub async fn handler_404(method: Method, uri: Uri) -> (StatusCode, ApiBody<String>) {
-    (
-        StatusCode::NOT_FOUND,
-        ApiBody::failure(-1, format!("{} {} Not Found", method, uri)),
-    )
+pub async fn handler_404(method: Method, uri: Uri) -> ApiError {
+    ApiError::NotFound(format!("{} {} Not Found", method, uri))
 }
 
 /**
@@ -80,15 +98,12 @@ pub async fn handle_error(
     uri: Uri,
     // the last argument must be the error itself
     err: BoxError,
-) -> (StatusCode, ApiBody<String>) {
-    (
-        StatusCode::INTERNAL_SERVER_ERROR,
-        ApiBody::new(
-            -1,
-            format!("{} {} failed", method, uri),
-            Some(err.to_string()),
-        ),
-    )
+) -> ApiError {
+    if err.is::<tower::timeout::error::Elapsed>() {
+        ApiError::Unavailable(format!("{} {} timed out", method, uri))
+    } else {
+        ApiError::Internal(anyhow::anyhow!("{} {} failed: {}", method, uri, err))
+    }
 }
 
 // 定义OpenAPI文档
@@ -99,9 +114,44 @@ pub async fn handle_error(
         version = "v1.0.0"
     ),
     modifiers(&SecurityAddon),
+    security(("bearer_auth" = []), ("api_key" = [])),
+    components(schemas(ErrorBody, FieldError)),
     paths(
         index,
-        get_users
+        super::admin::create_api_key,
+        super::admin::list_api_keys,
+        super::admin::revoke_api_key,
+        super::admin::rotate_api_key,
+        super::admin::get_log_levels,
+        super::admin::update_log_levels,
+        super::admin::get_health,
+        super::member::list_members,
+        super::member::set_member,
+        super::member::remove_member,
+        super::pipeline::list_pipelines,
+        super::pipeline::list_builds,
+        super::pipeline::get_build,
+        super::subscription::create_subscription,
+        super::subscription::get_subscription,
+        super::subscription::list_subscriptions,
+        super::subscription::update_subscription,
+        super::subscription::enable_subscription,
+        super::subscription::disable_subscription,
+        super::subscription::delete_subscription,
+        super::channel::create_channel,
+        super::channel::get_channel,
+        super::channel::list_channels,
+        super::channel::update_channel,
+        super::channel::delete_channel,
+        super::channel::test_channel,
+        super::notification::send_notification,
+        super::notification::get_notification,
+        super::delivery::list_deliveries,
+        super::delivery::export_deliveries,
+        super::event::stream_events,
+        super::websocket::connect,
+        super::health::liveness,
+        super::health::readiness,
     ),
 )]
 pub struct ApiDoc;
@@ -123,6 +173,10 @@ impl Modify for SecurityAddon {
                     .build(),
             ),
         );
+        components.add_security_scheme(
+            "api_key",
+            SecurityScheme::ApiKey(ApiKey::Header(ApiKeyValue::new("X-API-Key"))),
+        );
     }
 }
 
@@ -136,26 +190,9 @@ impl Modify for SecurityAddon {
     path = "/api",
     responses(
         (status = 200, description = "Pet found successfully", body = ApiBody<String>),
-        (status = NOT_FOUND, description = "Pet was not found")
+        (status = NOT_FOUND, description = "Pet was not found", body = ErrorBody)
     )
 )]
 pub async fn index() -> ApiBody<String> {
     ApiBody::success(Some("Hello World".to_string()))
 }
-
-/**
- * 接口
- */
-#[utoipa::path(
-    get,
-    tag = "users",
-    description = "用户管理接口",
-    path = "/api/users",
-    responses(
-        (status = 200, description = "Users found successfully"),
-        (status = NOT_FOUND, description = "Pet was not found")
-    )
-)]
-pub async fn get_users() -> ApiBody<String> {
-    ApiBody::success(Some("Hello World".to_string()))
-}
@@ -0,0 +1,201 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, authorize, project_source};
+use crate::api::error::ApiError;
+use crate::api::interface::{ApiBody, ApiResult, ErrorBody};
+use crate::repository::entity::{ProjectMemberEntity, ProjectRole};
+use axum::extract::rejection::{JsonRejection, QueryRejection};
+use axum::extract::{Path, Query, State};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+/**
+ * 项目成员
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct MemberView {
+    /// DevOps 实例
+    #[schema(example = "default")]
+    pub source: String,
+    pub project_id: String,
+    #[schema(example = "alice")]
+    pub subject: String,
+    pub role: ProjectRole,
+    /// manual 或 devops
+    #[schema(example = "manual")]
+    pub origin: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<ProjectMemberEntity> for MemberView {
+    fn from(entity: ProjectMemberEntity) -> Self {
+        Self {
+            source: entity.source,
+            project_id: entity.project_id,
+            subject: entity.subject,
+            role: entity.role,
+            origin: entity.origin,
+            updated_at: entity.updated_at,
+        }
+    }
+}
+
+/**
+ * 成员所在的 DevOps 实例
+ */
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct MemberQuery {
+    /// DevOps 实例, 默认取配置了该项目的实例
+    pub source: Option<String>,
+}
+
+/**
+ * 设置成员角色请求
+ */
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SetMemberRequest {
+    pub role: ProjectRole,
+}
+
+/**
+ * 项目成员列表
+ */
+#[utoipa::path(
+    get,
+    tag = "members",
+    description = "项目成员列表",
+    path = "/api/projects/{project_id}/members",
+    params(("project_id" = String, Path, description = "DevOps project id"), MemberQuery),
+    responses(
+        (status = 200, description = "Project members", body = ApiBody<Vec<MemberView>>),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Unknown DevOps source", body = ErrorBody)
+    )
+)]
+pub async fn list_members(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    Path(project_id): Path<String>,
+    query: Result<Query<MemberQuery>, QueryRejection>,
+) -> ApiResult<Vec<MemberView>> {
+    let Query(query) = query?;
+    let source = project_source(&state, query.source.as_deref(), &project_id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        Some(&source),
+        &project_id,
+        ProjectRole::Viewer,
+    )
+    .await?;
+    let members = state.services.access.members(&source, &project_id).await?;
+    Ok(ApiBody::success(Some(
+        members.into_iter().map(MemberView::from).collect(),
+    )))
+}
+
+/**
+ * 设置成员角色
+ */
+#[utoipa::path(
+    put,
+    tag = "members",
+    description = "设置项目成员角色",
+    path = "/api/projects/{project_id}/members/{subject}",
+    params(
+        ("project_id" = String, Path, description = "DevOps project id"),
+        ("subject" = String, Path, description = "User id or api-key:{id}"),
+        MemberQuery
+    ),
+    request_body = SetMemberRequest,
+    responses(
+        (status = 200, description = "Member saved", body = ApiBody<MemberView>),
+        (status = BAD_REQUEST, description = "Malformed request body", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Project admin role required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Unknown DevOps source", body = ErrorBody)
+    )
+)]
+pub async fn set_member(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    Path((project_id, subject)): Path<(String, String)>,
+    query: Result<Query<MemberQuery>, QueryRejection>,
+    payload: Result<Json<SetMemberRequest>, JsonRejection>,
+) -> ApiResult<MemberView> {
+    let Query(query) = query?;
+    let Json(request) = payload?;
+    let source = project_source(&state, query.source.as_deref(), &project_id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        Some(&source),
+        &project_id,
+        ProjectRole::Admin,
+    )
+    .await?;
+    let member = state
+        .services
+        .access
+        .set_role(&source, &project_id, &subject, request.role)
+        .await?;
+    Ok(ApiBody::success(Some(member.into())))
+}
+
+/**
+ * 移除成员
+ */
+#[utoipa::path(
+    delete,
+    tag = "members",
+    description = "移除项目成员",
+    path = "/api/projects/{project_id}/members/{subject}",
+    params(
+        ("project_id" = String, Path, description = "DevOps project id"),
+        ("subject" = String, Path, description = "User id or api-key:{id}"),
+        MemberQuery
+    ),
+    responses(
+        (status = 200, description = "Member removed", body = ApiBody<String>),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Project admin role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Member not found", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Unknown DevOps source", body = ErrorBody)
+    )
+)]
+pub async fn remove_member(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    Path((project_id, subject)): Path<(String, String)>,
+    query: Result<Query<MemberQuery>, QueryRejection>,
+) -> ApiResult<String> {
+    let Query(query) = query?;
+    let source = project_source(&state, query.source.as_deref(), &project_id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        Some(&source),
+        &project_id,
+        ProjectRole::Admin,
+    )
+    .await?;
+    let removed = state
+        .services
+        .access
+        .remove(&source, &project_id, &subject)
+        .await?;
+    if removed {
+        Ok(ApiBody::success(None))
+    } else {
+        Err(ApiError::NotFound(format!(
+            "member {} not found in project {}",
+            subject, project_id
+        )))
+    }
+}
@@ -1,8 +1,38 @@
+mod access;
+mod admin;
+mod auth;
+mod channel;
+mod cors;
+mod delivery;
+mod error;
+mod event;
+mod health;
 mod interface;
+mod member;
+mod notification;
+mod pipeline;
+mod rate_limit;
+mod subscription;
+mod websocket;
 
+use crate::api::auth::{AuthArgs, JwtVerifier};
+use crate::api::cors::CorsArgs;
 use crate::api::interface::ApiDoc;
+use crate::api::rate_limit::{RateLimitArgs, RateLimiter};
+use crate::application::access::AccessService;
+use crate::application::api_key::ApiKeyService;
+use crate::application::channel::ChannelService;
+use crate::application::event::EventBus;
+use crate::application::health::HealthService;
+use crate::application::history::BuildHistoryService;
+use crate::application::notification::NotificationService;
+use crate::application::pipeline::PipelineService;
+use crate::application::subscription::SubscriptionService;
+use crate::application::supervisor::ManagedService;
+use crate::conf::{ConfigIssues, EventOptions};
 use axum::error_handling::HandleErrorLayer;
-use axum::routing::get;
+use axum::middleware;
+use axum::routing::{delete, get, post, put};
 use axum::{Router, serve};
 use axum_prometheus::GenericMetricLayer;
 use axum_prometheus::Handle;
@@ -11,76 +41,284 @@ use axum_prometheus::metrics_exporter_prometheus::PrometheusHandle;
 use log::info;
 use serde::Deserialize;
 use std::fmt::Debug;
+use std::net::SocketAddr;
 use std::sync::Arc;
 use std::time::Duration;
 use tokio::net::TcpListener;
 use tokio::select;
+use tokio::sync::Semaphore;
+use tokio::task::JoinHandle;
 use tokio_util::sync::CancellationToken;
 use tower::ServiceBuilder;
+use tower_http::cors::CorsLayer;
 use tower_http::timeout::TimeoutLayer;
-use tower_http::trace::TraceLayer;
 use utoipa::OpenApi;
 use utoipa_swagger_ui::SwaggerUi;
 
+/**
+ * 生成的 OpenAPI 文档, 与 Swagger UI 展示的一致
+ */
+pub fn openapi() -> utoipa::openapi::OpenApi {
+    ApiDoc::openapi()
+}
+
+/**
+ * 接口依赖的应用服务
+ */
 #[derive(Clone)]
-pub struct ApiState {}
+pub struct ApiServices {
+    pub api_keys: ApiKeyService,
+    pub access: AccessService,
+    pub subscriptions: SubscriptionService,
+    pub channels: ChannelService,
+    pub pipelines: PipelineService,
+    pub history: BuildHistoryService,
+    pub notifications: NotificationService,
+    pub events: EventBus,
+    pub health: HealthService,
+}
 
-#[derive(Deserialize, Debug, Clone)]
+#[derive(Clone)]
+pub struct ApiState {
+    auth_enabled: bool,
+    verifier: Option<Arc<JwtVerifier>>,
+    services: ApiServices,
+    /// 事件流心跳间隔
+    heartbeat: Duration,
+    /// 服务停止时结束长连接
+    shutdown: CancellationToken,
+    /// WebSocket 连接数限制
+    connections: Arc<Semaphore>,
+    /// WebSocket 未确认事件上限
+    ack_window: usize,
+    rate_limiter: Arc<RateLimiter>,
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
 pub struct ApiServiceArgs {
     #[serde(alias = "address")]
     pub address: String,
     pub port: u16,
     pub timeout: u64,
+    #[serde(default)]
+    pub auth: AuthArgs,
+    #[serde(default)]
+    pub rate_limit: RateLimitArgs,
+    #[serde(default)]
+    pub cors: CorsArgs,
+}
+
+impl ApiServiceArgs {
+    pub fn validate(&self, issues: &mut ConfigIssues) {
+        issues.check(
+            !self.address.trim().is_empty(),
+            "api.address",
+            "is empty",
+            "use 0.0.0.0 to listen on all interfaces",
+        );
+        issues.positive(self.port, "api.port");
+        issues.positive(self.timeout, "api.timeout");
+
+        let auth = &self.auth;
+        if auth.enabled {
+            issues.check(
+                auth.secret.is_some() || auth.jwks.is_some(),
+                "api.auth",
+                "auth is enabled but neither secret nor jwks is configured",
+                "set api.auth.secret for HS256 or api.auth.jwks for RS256 tokens",
+            );
+        }
+        if let Some(jwks) = auth.jwks.as_deref()
+            && !jwks.starts_with("http://")
+            && !jwks.starts_with("https://")
+        {
+            issues.check(
+                std::path::Path::new(jwks).is_file(),
+                "api.auth.jwks",
+                format!("file {} does not exist", jwks),
+                "point it to a local jwks json file or an http(s) url",
+            );
+        }
+
+        if let Err(err) = self.cors.layer() {
+            issues.push(
+                "api.cors",
+                format!("{:#}", err),
+                "list explicit origins such as https://console.example.com",
+            );
+        }
+
+        let rate_limit = &self.rate_limit;
+        issues.positive(rate_limit.requests, "api.rate_limit.requests");
+        issues.positive(rate_limit.period, "api.rate_limit.period");
+        for (index, rule) in rate_limit.rules.iter().enumerate() {
+            let key = format!("api.rate_limit.rules[{}]", index);
+            issues.check(
+                !rule.name.trim().is_empty(),
+                format!("{}.name", key),
+                "is empty",
+                "name the route group, it is used as a metric label",
+            );
+            issues.check(
+                rule.path_prefix.starts_with('/'),
+                format!("{}.path_prefix", key),
+                "must start with /",
+                "use the full request path, e.g. /api/notifications",
+            );
+            issues.positive(rule.requests, format!("{}.requests", key));
+            issues.positive(rule.period, format!("{}.period", key));
+        }
+    }
 }
 
 pub struct ApiService {
     args: ApiServiceArgs,
+    events: EventOptions,
     cancel_token: CancellationToken,
+    verifier: Option<Arc<JwtVerifier>>,
+    cors: Option<CorsLayer>,
+    rate_limiter: Arc<RateLimiter>,
+    services: ApiServices,
 }
 
 impl ApiService {
-    pub fn new(token: CancellationToken, args: ApiServiceArgs) -> Result<Self, anyhow::Error> {
+    pub fn new(
+        token: CancellationToken,
+        args: ApiServiceArgs,
+        events: EventOptions,
+        services: ApiServices,
+    ) -> Result<Self, anyhow::Error> {
+        let auth = &args.auth;
+        let verifier = if auth.enabled && (auth.secret.is_some() || auth.jwks.is_some()) {
+            Some(Arc::new(JwtVerifier::new(auth.clone())?))
+        } else {
+            None
+        };
+        let cors = args.cors.layer()?;
+        let rate_limiter = Arc::new(RateLimiter::new(args.rate_limit.clone()));
         Ok(Self {
             cancel_token: token,
             args,
+            events,
+            verifier,
+            cors,
+            rate_limiter,
+            services,
         })
     }
 
+    /**
+     * 运行时更新限流规则
+     */
+    pub fn set_rate_limit(&self, args: RateLimitArgs) {
+        self.rate_limiter.update(args);
+    }
+
     fn routes() -> Router<Arc<ApiState>> {
         Router::new()
             .route("/", get(interface::index))
-            .route("/users", get(interface::get_users))
+            .route(
+                "/admin/api-keys",
+                get(admin::list_api_keys).post(admin::create_api_key),
+            )
+            .route("/admin/api-keys/{id}", delete(admin::revoke_api_key))
+            .route("/admin/api-keys/{id}/rotate", post(admin::rotate_api_key))
+            .route(
+                "/admin/log-levels",
+                get(admin::get_log_levels).put(admin::update_log_levels),
+            )
+            .route("/admin/health", get(admin::get_health))
+            .route("/projects/{project_id}/members", get(member::list_members))
+            .route(
+                "/projects/{project_id}/pipelines",
+                get(pipeline::list_pipelines),
+            )
+            .route(
+                "/pipelines/{pipeline_id}/builds",
+                get(pipeline::list_builds),
+            )
+            .route("/builds/{build_id}", get(pipeline::get_build))
+            .route(
+                "/projects/{project_id}/members/{subject}",
+                put(member::set_member).delete(member::remove_member),
+            )
+            .route(
+                "/subscriptions",
+                get(subscription::list_subscriptions).post(subscription::create_subscription),
+            )
+            .route(
+                "/subscriptions/{id}",
+                get(subscription::get_subscription)
+                    .put(subscription::update_subscription)
+                    .delete(subscription::delete_subscription),
+            )
+            .route(
+                "/subscriptions/{id}/enable",
+                post(subscription::enable_subscription),
+            )
+            .route(
+                "/subscriptions/{id}/disable",
+                post(subscription::disable_subscription),
+            )
+            .route(
+                "/channels",
+                get(channel::list_channels).post(channel::create_channel),
+            )
+            .route(
+                "/channels/{id}",
+                get(channel::get_channel)
+                    .put(channel::update_channel)
+                    .delete(channel::delete_channel),
+            )
+            .route("/channels/{id}/test", post(channel::test_channel))
+            .route("/notifications", post(notification::send_notification))
+            .route("/notifications/{id}", get(notification::get_notification))
+            .route("/deliveries", get(delivery::list_deliveries))
+            .route("/deliveries/export", get(delivery::export_deliveries))
     }
 
-    pub fn start(&self) -> Result<(), anyhow::Error> {
-        info!("starting api service");
-        let token = self.cancel_token.clone();
-        let args = self.args.clone();
-        let addr = format!("{}:{}", args.address, args.port);
-        info!("listening on {}", addr);
-        let listener = std::net::TcpListener::bind(addr)?;
-        listener.set_nonblocking(true)?;
-        let app_state = ApiState {};
-        let state = Arc::new(app_state);
-        tokio::spawn(Self::start_app(token, listener, args, state));
-        Ok(())
+    /**
+     * 长连接接口, 不受请求超时限制
+     */
+    fn stream_routes() -> Router<Arc<ApiState>> {
+        Router::new()
+            .route("/events/stream", get(event::stream_events))
+            .route("/events/ws", get(websocket::connect))
+    }
+
+    /**
+     * 先认证再限流, 限流可以按 API Key 或用户计数; 最外层记录匹配到的路由
+     */
+    fn protect(router: Router<Arc<ApiState>>, state: &Arc<ApiState>) -> Router<Arc<ApiState>> {
+        router
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                rate_limit::limit,
+            ))
+            .route_layer(middleware::from_fn_with_state(
+                state.clone(),
+                auth::authenticate,
+            ))
+            .route_layer(middleware::from_fn(access::route))
     }
 
     async fn start_app(
         token: CancellationToken,
         listener: std::net::TcpListener,
         args: ApiServiceArgs,
+        cors: Option<CorsLayer>,
         state: Arc<ApiState>,
     ) -> Result<(), anyhow::Error> {
         let (prometheus_layer, metric_handle) = Self::build_metrics();
+        websocket::describe_metrics(state.connections.available_permits());
         let api = ApiDoc::openapi();
         // Create a regular axum app.
         let app = Router::<Arc<ApiState>>::new()
-            .nest("/api", Self::routes())
+            .nest("/api", Self::protect(Self::routes(), &state))
             .route("/metrics", get(|| async move { metric_handle.render() }))
+            .route("/healthz", get(health::liveness))
+            .route("/readyz", get(health::readiness))
             .fallback(interface::handler_404)
-            // request trace
-            .layer(TraceLayer::new_for_http())
             // request timeout
             .layer(TimeoutLayer::new(Duration::from_secs(args.timeout)))
             // prometheus metric
@@ -90,20 +328,30 @@ impl ApiService {
                     .layer(HandleErrorLayer::new(interface::handle_error))
                     .timeout(Duration::from_secs(args.timeout)),
             )
+            .merge(Router::new().nest("/api", Self::protect(Self::stream_routes(), &state)))
             .with_state(state)
             .merge(SwaggerUi::new("/swagger-ui").url("/api-docs/openapi.json", api));
+        let app = match cors {
+            Some(cors) => app.layer(cors),
+            None => app,
+        };
+        // request id and access log
+        let app = app.layer(middleware::from_fn(access::trace));
 
         let tcp_listener = TcpListener::from_std(listener)?;
         // Run the server with graceful shutdown
-        let _ = serve(tcp_listener, app)
-            .with_graceful_shutdown(async move {
-                select! {
-                    _ = token.cancelled() => {
-                        info!("received shutdown api service signal");
-                    },
-                }
-            })
-            .await;
+        serve(
+            tcp_listener,
+            app.into_make_service_with_connect_info::<SocketAddr>(),
+        )
+        .with_graceful_shutdown(async move {
+            select! {
+                _ = token.cancelled() => {
+                    info!("received shutdown api service signal");
+                },
+            }
+        })
+        .await?;
         Ok(())
     }
 
@@ -112,12 +360,46 @@ impl ApiService {
         PrometheusHandle,
     ) {
         PrometheusMetricLayerBuilder::new()
-            .with_ignore_patterns(&["/metrics"])
+            .with_ignore_patterns(&["/metrics", "/healthz", "/readyz"])
             .with_default_metrics()
             .build_pair()
     }
+}
+
+impl ManagedService for ApiService {
+    fn name(&self) -> String {
+        "api".to_string()
+    }
+
+    fn start(&self) -> Result<JoinHandle<Result<(), anyhow::Error>>, anyhow::Error> {
+        info!("starting api service");
+        let token = self.cancel_token.clone();
+        let args = self.args.clone();
+        let addr = format!("{}:{}", args.address, args.port);
+        info!("listening on {}", addr);
+        let listener = std::net::TcpListener::bind(addr)?;
+        listener.set_nonblocking(true)?;
+        let app_state = ApiState {
+            auth_enabled: args.auth.enabled,
+            verifier: self.verifier.clone(),
+            services: self.services.clone(),
+            heartbeat: Duration::from_secs(*self.events.heartbeat()),
+            shutdown: token.clone(),
+            connections: Arc::new(Semaphore::new(*self.events.max_connections())),
+            ack_window: (*self.events.ack_window()).max(1),
+            rate_limiter: self.rate_limiter.clone(),
+        };
+        let state = Arc::new(app_state);
+        Ok(tokio::spawn(Self::start_app(
+            token,
+            listener,
+            args,
+            self.cors.clone(),
+            state,
+        )))
+    }
 
-    pub fn stop(&self) -> Result<(), anyhow::Error> {
+    fn stop(&self) -> Result<(), anyhow::Error> {
         info!("Stopping ApiService");
         self.cancel_token.cancel();
         Ok(())
@@ -0,0 +1,329 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, authorize, require_scope};
+use crate::api::error::{ApiError, FieldError};
+use crate::api::interface::{ApiBody, ApiResult, ErrorBody};
+use crate::repository::entity::{
+    DeliveryEntity, DeliveryStatus, DeliveryTarget, NotificationEntity, ProjectRole, Severity,
+};
+use axum::extract::rejection::{JsonRejection, PathRejection};
+use axum::extract::{Path, State};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::ToSchema;
+
+/// 标题最大长度
+const MAX_TITLE_LEN: usize = 256;
+
+/**
+ * 发送通知请求, channel_ids 与 tags 至少提供一个
+ */
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct NotificationRequest {
+    #[schema(example = "demo")]
+    pub project_id: String,
+    /// 项目所属的 DevOps 实例, 默认取配置了该项目的实例
+    pub source: Option<String>,
+    #[schema(example = "v2.3.0 released")]
+    pub title: String,
+    /// markdown 内容
+    pub content: String,
+    #[serde(default)]
+    pub severity: Severity,
+    /// 目标渠道, 须属于同一项目
+    #[serde(default)]
+    pub channel_ids: Vec<i64>,
+    /// 按订阅标签匹配渠道
+    #[serde(default)]
+    #[schema(example = json!(["release"]))]
+    pub tags: Vec<String>,
+    /// 配置文件中的消息模板
+    pub template_id: Option<String>,
+}
+
+impl NotificationRequest {
+    fn validate(&self, state: &ApiState) -> Result<(), ApiError> {
+        let mut errors = vec![];
+        if self.project_id.trim().is_empty() {
+            errors.push(FieldError::new("project_id", "must not be empty"));
+        }
+        if self.title.trim().is_empty() {
+            errors.push(FieldError::new("title", "must not be empty"));
+        } else if self.title.chars().count() > MAX_TITLE_LEN {
+            errors.push(FieldError::new(
+                "title",
+                format!("must be at most {} characters", MAX_TITLE_LEN),
+            ));
+        }
+        if self.content.trim().is_empty() {
+            errors.push(FieldError::new("content", "must not be empty"));
+        }
+        if self.channel_ids.is_empty() && self.tags.is_empty() {
+            errors.push(FieldError::new(
+                "channel_ids",
+                "channel_ids or tags is required",
+            ));
+        }
+        if let Some(source) = &self.source
+            && !state.services.pipelines.sources().contains(source)
+        {
+            errors.push(FieldError::new(
+                "source",
+                format!("unknown devops source {}", source),
+            ));
+        }
+        if let Some(template_id) = &self.template_id
+            && !state.services.notifications.has_template(template_id)
+        {
+            errors.push(FieldError::new(
+                "template_id",
+                format!("template {} not found", template_id),
+            ));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(errors))
+        }
+    }
+}
+
+/**
+ * 单个渠道的投递状态
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct DeliveryView {
+    pub id: i64,
+    pub channel_id: i64,
+    /// 命中的订阅, 直接指定渠道时为空
+    pub subscription_id: Option<i64>,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    /// 下次尝试时间, 仅 pending 状态有意义
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<DeliveryEntity> for DeliveryView {
+    fn from(entity: DeliveryEntity) -> Self {
+        Self {
+            id: entity.id.unwrap_or_default(),
+            channel_id: entity.channel_id,
+            subscription_id: entity.subscription_id,
+            status: entity.status,
+            attempts: entity.attempts,
+            next_attempt_at: entity.next_attempt_at,
+            last_error: entity.last_error,
+            response_status: entity.response_status,
+            response_body: entity.response_body,
+            delivered_at: entity.delivered_at,
+            updated_at: entity.updated_at,
+        }
+    }
+}
+
+/**
+ * 通知及其投递状态
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct NotificationView {
+    pub id: i64,
+    pub project_id: String,
+    pub title: String,
+    pub content: String,
+    pub severity: Severity,
+    #[schema(example = "manual")]
+    pub source: String,
+    pub template_id: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub deliveries: Vec<DeliveryView>,
+}
+
+impl NotificationView {
+    fn new(notification: NotificationEntity, deliveries: Vec<DeliveryEntity>) -> Self {
+        Self {
+            id: notification.id.unwrap_or_default(),
+            project_id: notification.project_id,
+            title: notification.title,
+            content: notification.content,
+            severity: notification.severity,
+            source: notification.source,
+            template_id: notification.template_id,
+            created_by: notification.created_by,
+            created_at: notification.created_at,
+            deliveries: deliveries.into_iter().map(DeliveryView::from).collect(),
+        }
+    }
+}
+
+/**
+ * 校验渠道属于项目, 合并按标签匹配的渠道
+ */
+async fn resolve_targets(
+    state: &ApiState,
+    request: &NotificationRequest,
+) -> Result<Vec<DeliveryTarget>, ApiError> {
+    let mut errors = vec![];
+    let mut targets = vec![];
+    for (index, channel_id) in request.channel_ids.iter().enumerate() {
+        match state.services.channels.get(*channel_id).await? {
+            Some(channel) if channel.project_id == request.project_id => {
+                targets.push(DeliveryTarget {
+                    channel_id: *channel_id,
+                    subscription_id: None,
+                })
+            }
+            Some(_) => errors.push(FieldError::new(
+                format!("channel_ids[{}]", index),
+                "channel belongs to another project",
+            )),
+            None => errors.push(FieldError::new(
+                format!("channel_ids[{}]", index),
+                format!("channel {} not found", channel_id),
+            )),
+        }
+    }
+    if !request.tags.is_empty() {
+        let source = state
+            .services
+            .pipelines
+            .source_of(request.source.as_deref(), &request.project_id)
+            .await?;
+        let tagged = state
+            .services
+            .subscriptions
+            .targets_by_tags(source.as_deref(), &request.project_id, &request.tags)
+            .await?;
+        if tagged.is_empty() {
+            errors.push(FieldError::new(
+                "tags",
+                "no enabled subscription matches the tags",
+            ));
+        }
+        // 直接指定的渠道优先, 同一渠道只投递一次
+        for target in tagged {
+            if !targets
+                .iter()
+                .any(|existing| existing.channel_id == target.channel_id)
+            {
+                targets.push(target);
+            }
+        }
+    }
+    if !errors.is_empty() {
+        return Err(ApiError::Validation(errors));
+    }
+    targets.sort_unstable();
+    targets.dedup_by_key(|target| target.channel_id);
+    Ok(targets)
+}
+
+/**
+ * 发送通知
+ */
+#[utoipa::path(
+    post,
+    tag = "notifications",
+    description = "发送通知, 写入发件箱后异步投递, 通过返回的 id 查询各渠道投递状态",
+    path = "/api/notifications",
+    request_body = NotificationRequest,
+    responses(
+        (status = 200, description = "Notification queued", body = ApiBody<NotificationView>),
+        (status = BAD_REQUEST, description = "Malformed request body", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid fields", body = ErrorBody)
+    )
+)]
+pub async fn send_notification(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    payload: Result<Json<NotificationRequest>, JsonRejection>,
+) -> ApiResult<NotificationView> {
+    let Json(request) = payload?;
+    require_scope(user.as_deref(), "notifications:send")?;
+    request.validate(&state)?;
+    authorize(
+        &state,
+        user.as_deref(),
+        request.source.as_deref(),
+        &request.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    let targets = resolve_targets(&state, &request).await?;
+
+    let notification = NotificationEntity {
+        id: None,
+        project_id: request.project_id,
+        title: request.title,
+        content: request.content,
+        severity: request.severity,
+        source: "manual".to_string(),
+        template_id: request.template_id,
+        created_by: user.map(|Extension(user)| user.subject).unwrap_or_default(),
+        created_at: Utc::now(),
+        pipeline_id: None,
+        build_id: None,
+        event: None,
+    };
+    let (notification, deliveries) = state
+        .services
+        .notifications
+        .enqueue(notification, &targets)
+        .await?;
+    Ok(ApiBody::success(Some(NotificationView::new(
+        notification,
+        deliveries,
+    ))))
+}
+
+/**
+ * 通知投递状态
+ */
+#[utoipa::path(
+    get,
+    tag = "notifications",
+    description = "通知详情及各渠道的投递状态",
+    path = "/api/notifications/{id}",
+    params(("id" = i64, Path, description = "Notification id")),
+    responses(
+        (status = 200, description = "Notification", body = ApiBody<NotificationView>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Notification not found", body = ErrorBody)
+    )
+)]
+pub async fn get_notification(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<NotificationView> {
+    let Path(id) = path?;
+    require_scope(user.as_deref(), "notifications:send")?;
+    let (notification, deliveries) = state
+        .services
+        .notifications
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("notification {} not found", id)))?;
+    authorize(
+        &state,
+        user.as_deref(),
+        None,
+        &notification.project_id,
+        ProjectRole::Viewer,
+    )
+    .await?;
+    Ok(ApiBody::success(Some(NotificationView::new(
+        notification,
+        deliveries,
+    ))))
+}
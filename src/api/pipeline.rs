@@ -0,0 +1,394 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, authorize, project_source, require_scope};
+use crate::api::error::{ApiError, FieldError};
+use crate::api::interface::{ApiBody, ApiResult, ErrorBody};
+use crate::repository::entity::{
+    BuildEntity, BuildFilter, BuildStage, PipelineEntity, ProjectRole,
+};
+use axum::Extension;
+use axum::extract::rejection::QueryRejection;
+use axum::extract::{Path, Query, State};
+use chrono::{DateTime, Utc};
+use log::error;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+/// 构建列表默认条数
+const DEFAULT_LIMIT: u32 = 50;
+/// 构建列表最大条数
+const MAX_LIMIT: u32 = 500;
+
+/**
+ * 流水线信息
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct PipelineView {
+    /// 来源 DevOps 实例
+    #[schema(example = "prod")]
+    pub source: String,
+    pub project_id: String,
+    pub pipeline_id: String,
+    pub name: String,
+    pub description: String,
+    pub creator: String,
+    pub latest_build_num: Option<i32>,
+    pub latest_build_status: Option<String>,
+    pub latest_build_user: Option<String>,
+    pub latest_build_start_time: Option<DateTime<Utc>>,
+    pub latest_build_end_time: Option<DateTime<Utc>>,
+    pub running_build_count: i32,
+    /// 缓存更新时间
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<PipelineEntity> for PipelineView {
+    fn from(entity: PipelineEntity) -> Self {
+        Self {
+            source: entity.source,
+            project_id: entity.project_id,
+            pipeline_id: entity.pipeline_id,
+            name: entity.name,
+            description: entity.description,
+            creator: entity.creator,
+            latest_build_num: entity.latest_build_num,
+            latest_build_status: entity.latest_build_status,
+            latest_build_user: entity.latest_build_user,
+            latest_build_start_time: entity.latest_build_start_time,
+            latest_build_end_time: entity.latest_build_end_time,
+            running_build_count: entity.running_build_count,
+            updated_at: entity.updated_at,
+        }
+    }
+}
+
+/**
+ * 构建信息
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct BuildView {
+    #[schema(example = "b-2c4f0a9e1d")]
+    pub build_id: String,
+    /// 来源 DevOps 实例
+    #[schema(example = "prod")]
+    pub source: String,
+    pub project_id: String,
+    pub pipeline_id: String,
+    pub build_num: i32,
+    #[schema(example = "SUCCEED")]
+    pub status: String,
+    pub trigger: String,
+    pub start_user: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// 耗时(秒)
+    pub duration: Option<i64>,
+    pub stages: Vec<BuildStage>,
+    /// 缓存更新时间
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<BuildEntity> for BuildView {
+    fn from(entity: BuildEntity) -> Self {
+        Self {
+            build_id: entity.build_id,
+            source: entity.source,
+            project_id: entity.project_id,
+            pipeline_id: entity.pipeline_id,
+            build_num: entity.build_num,
+            status: entity.status,
+            trigger: entity.trigger,
+            start_user: entity.start_user,
+            start_time: entity.start_time,
+            end_time: entity.end_time,
+            duration: entity.duration,
+            stages: entity.stages.0,
+            updated_at: entity.updated_at,
+        }
+    }
+}
+
+/**
+ * 是否从 DevOps 实时刷新
+ */
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct RefreshQuery {
+    /// 为 true 时先从 DevOps 拉取最新数据再返回
+    #[serde(default)]
+    pub refresh: bool,
+    /// DevOps 实例, 构建 id 存在于多个实例时必须指定
+    pub source: Option<String>,
+}
+
+/**
+ * 流水线查询条件
+ */
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct PipelineQuery {
+    /// 为 true 时先从 DevOps 拉取最新数据再返回
+    #[serde(default)]
+    pub refresh: bool,
+    /// 刷新时使用的 DevOps 实例, 默认取项目所属的实例
+    pub source: Option<String>,
+}
+
+/**
+ * 构建查询条件
+ */
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct BuildQuery {
+    /// 构建状态, 如 SUCCEED/FAILED/RUNNING
+    pub status: Option<String>,
+    /// 触发人
+    pub start_user: Option<String>,
+    /// 开始时间下限(包含)
+    pub start_from: Option<DateTime<Utc>>,
+    /// 开始时间上限(不包含)
+    pub start_to: Option<DateTime<Utc>>,
+    /// 返回条数, 默认 50, 最大 500
+    pub limit: Option<u32>,
+    /// 为 true 时先从 DevOps 拉取最新数据再返回
+    #[serde(default)]
+    pub refresh: bool,
+    /// DevOps 实例, 流水线 id 存在于多个实例时必须指定
+    pub source: Option<String>,
+}
+
+impl BuildQuery {
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut errors = vec![];
+        if self
+            .limit
+            .is_some_and(|limit| limit == 0 || limit > MAX_LIMIT)
+        {
+            errors.push(FieldError::new(
+                "limit",
+                format!("must be between 1 and {}", MAX_LIMIT),
+            ));
+        }
+        if let (Some(start_from), Some(start_to)) = (self.start_from, self.start_to)
+            && start_from >= start_to
+        {
+            errors.push(FieldError::new("start_to", "must be after start_from"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(errors))
+        }
+    }
+}
+
+/**
+ * 实时刷新失败视为上游不可用
+ */
+fn refresh_failed(err: anyhow::Error) -> ApiError {
+    error!("refresh from devops failed. {:?}", err);
+    ApiError::Unavailable(format!("refresh from devops failed: {}", err))
+}
+
+/**
+ * 同一 id 匹配多个实例时要求调用方指定实例
+ */
+fn single<T>(
+    mut found: Vec<T>,
+    kind: &str,
+    id: &str,
+    source: impl Fn(&T) -> &str,
+) -> Result<T, ApiError> {
+    match found.len() {
+        0 => Err(ApiError::NotFound(format!("{} {} not found", kind, id))),
+        1 => Ok(found.remove(0)),
+        _ => Err(ApiError::Validation(vec![FieldError::new(
+            "source",
+            format!(
+                "{} {} exists in multiple devops sources, specify one of {:?}",
+                kind,
+                id,
+                found.iter().map(source).collect::<Vec<_>>()
+            ),
+        )])),
+    }
+}
+
+async fn find_pipeline(
+    state: &ApiState,
+    source: Option<&str>,
+    pipeline_id: &str,
+) -> Result<PipelineEntity, ApiError> {
+    let found = state.services.pipelines.get(source, pipeline_id).await?;
+    single(found, "pipeline", pipeline_id, |pipeline| &pipeline.source)
+}
+
+async fn find_build(
+    state: &ApiState,
+    source: Option<&str>,
+    build_id: &str,
+) -> Result<BuildEntity, ApiError> {
+    let found = state.services.history.find_build(source, build_id).await?;
+    single(found, "build", build_id, |build| &build.source)
+}
+
+/**
+ * 项目流水线列表
+ */
+#[utoipa::path(
+    get,
+    tag = "pipelines",
+    description = "项目下的流水线, 默认读取本地缓存",
+    path = "/api/projects/{project_id}/pipelines",
+    params(("project_id" = String, Path, description = "Project id"), PipelineQuery),
+    responses(
+        (status = 200, description = "Pipelines", body = ApiBody<Vec<PipelineView>>),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Unknown DevOps source", body = ErrorBody),
+        (status = SERVICE_UNAVAILABLE, description = "Refresh from DevOps failed", body = ErrorBody)
+    )
+)]
+pub async fn list_pipelines(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    Path(project_id): Path<String>,
+    query: Result<Query<PipelineQuery>, QueryRejection>,
+) -> ApiResult<Vec<PipelineView>> {
+    let Query(query) = query?;
+    require_scope(user.as_deref(), "pipelines:read")?;
+    authorize(
+        &state,
+        user.as_deref(),
+        query.source.as_deref(),
+        &project_id,
+        ProjectRole::Viewer,
+    )
+    .await?;
+    let pipelines = &state.services.pipelines;
+    let entities = if query.refresh {
+        let source = project_source(&state, query.source.as_deref(), &project_id).await?;
+        pipelines
+            .refresh_pipelines(&source, &project_id)
+            .await
+            .map_err(refresh_failed)?
+    } else {
+        pipelines.list(&project_id).await?
+    };
+    Ok(ApiBody::success(Some(
+        entities.into_iter().map(PipelineView::from).collect(),
+    )))
+}
+
+/**
+ * 流水线构建列表
+ */
+#[utoipa::path(
+    get,
+    tag = "pipelines",
+    description = "流水线的构建, 按构建号倒序, 默认读取本地缓存",
+    path = "/api/pipelines/{pipeline_id}/builds",
+    params(("pipeline_id" = String, Path, description = "Pipeline id"), BuildQuery),
+    responses(
+        (status = 200, description = "Builds", body = ApiBody<Vec<BuildView>>),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Pipeline not found", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid query or pipeline id exists in multiple sources", body = ErrorBody),
+        (status = SERVICE_UNAVAILABLE, description = "Refresh from DevOps failed", body = ErrorBody)
+    )
+)]
+pub async fn list_builds(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    Path(pipeline_id): Path<String>,
+    query: Result<Query<BuildQuery>, QueryRejection>,
+) -> ApiResult<Vec<BuildView>> {
+    let Query(query) = query?;
+    require_scope(user.as_deref(), "pipelines:read")?;
+    query.validate()?;
+    let pipeline = find_pipeline(&state, query.source.as_deref(), &pipeline_id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        Some(&pipeline.source),
+        &pipeline.project_id,
+        ProjectRole::Viewer,
+    )
+    .await?;
+    if query.refresh {
+        state
+            .services
+            .pipelines
+            .refresh_builds(
+                &pipeline.source,
+                &pipeline.project_id,
+                &pipeline.pipeline_id,
+            )
+            .await
+            .map_err(refresh_failed)?;
+    }
+    let filter = BuildFilter {
+        source: Some(pipeline.source),
+        pipeline_id: Some(pipeline.pipeline_id),
+        status: query.status,
+        start_user: query.start_user,
+        start_from: query.start_from,
+        start_to: query.start_to,
+        limit: query.limit.unwrap_or(DEFAULT_LIMIT),
+    };
+    let entities = state.services.history.find_builds(&filter).await?;
+    Ok(ApiBody::success(Some(
+        entities.into_iter().map(BuildView::from).collect(),
+    )))
+}
+
+/**
+ * 构建详情
+ */
+#[utoipa::path(
+    get,
+    tag = "pipelines",
+    description = "构建详情, 默认读取本地缓存",
+    path = "/api/builds/{build_id}",
+    params(("build_id" = String, Path, description = "Build id"), RefreshQuery),
+    responses(
+        (status = 200, description = "Build", body = ApiBody<BuildView>),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Build not found", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Build id exists in multiple sources", body = ErrorBody),
+        (status = SERVICE_UNAVAILABLE, description = "Refresh from DevOps failed", body = ErrorBody)
+    )
+)]
+pub async fn get_build(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    Path(build_id): Path<String>,
+    query: Result<Query<RefreshQuery>, QueryRejection>,
+) -> ApiResult<BuildView> {
+    let Query(query) = query?;
+    require_scope(user.as_deref(), "pipelines:read")?;
+    let mut entity = find_build(&state, query.source.as_deref(), &build_id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        Some(&entity.source),
+        &entity.project_id,
+        ProjectRole::Viewer,
+    )
+    .await?;
+    if query.refresh {
+        state
+            .services
+            .pipelines
+            .refresh_builds(&entity.source, &entity.project_id, &entity.pipeline_id)
+            .await
+            .map_err(refresh_failed)?;
+        entity = find_build(&state, Some(&entity.source), &build_id).await?;
+    }
+    Ok(ApiBody::success(Some(entity.into())))
+}
@@ -0,0 +1,226 @@
+use crate::api::ApiState;
+use crate::api::auth::AuthUser;
+use crate::api::error::ApiError;
+use axum::extract::{ConnectInfo, OriginalUri, Request, State};
+use axum::middleware::Next;
+use axum::response::{IntoResponse, Response};
+use axum_prometheus::metrics::counter;
+use log::info;
+use serde::Deserialize;
+use std::collections::HashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex, RwLock, RwLockReadGuard};
+use std::time::{Duration, Instant};
+
+/// 被限流的请求数
+const RATE_LIMITED: &str = "api_rate_limited_requests_total";
+/// 记录的客户端数超过该值时清理已回满的令牌桶
+const MAX_TRACKED_BUCKETS: usize = 10000;
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct RateLimitArgs {
+    #[serde(default)]
+    pub enabled: bool,
+    /// 未匹配规则的请求, 每个客户端在 period 秒内允许的请求数
+    #[serde(default = "default_requests")]
+    pub requests: u32,
+    #[serde(default = "default_period")]
+    pub period: u64,
+    /// 是否信任 X-Forwarded-For, 仅在反向代理之后开启
+    #[serde(default)]
+    pub trust_forwarded: bool,
+    /// 按路由分组的限制, 按顺序取第一个匹配的规则
+    #[serde(default)]
+    pub rules: Vec<RateLimitRule>,
+}
+
+fn default_requests() -> u32 {
+    600
+}
+
+fn default_period() -> u64 {
+    60
+}
+
+impl Default for RateLimitArgs {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            requests: default_requests(),
+            period: default_period(),
+            trust_forwarded: false,
+            rules: vec![],
+        }
+    }
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub struct RateLimitRule {
+    /// 分组名称, 用作指标标签
+    pub name: String,
+    /// 请求路径前缀, 如 /api/notifications
+    pub path_prefix: String,
+    /// 限定的请求方法, 为空时匹配所有方法
+    #[serde(default)]
+    pub methods: Vec<String>,
+    pub requests: u32,
+    pub period: u64,
+}
+
+impl RateLimitRule {
+    fn matches(&self, method: &str, path: &str) -> bool {
+        path.starts_with(&self.path_prefix)
+            && (self.methods.is_empty()
+                || self
+                    .methods
+                    .iter()
+                    .any(|allowed| allowed.eq_ignore_ascii_case(method)))
+    }
+}
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+/**
+ * 按客户端和路由分组的令牌桶限流
+ */
+pub struct RateLimiter {
+    args: RwLock<RateLimitArgs>,
+    buckets: Mutex<HashMap<(String, String), Bucket>>,
+}
+
+impl RateLimiter {
+    pub fn new(args: RateLimitArgs) -> Self {
+        Self {
+            args: RwLock::new(args),
+            buckets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    fn args(&self) -> RwLockReadGuard<'_, RateLimitArgs> {
+        self.args.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    pub fn enabled(&self) -> bool {
+        self.args().enabled
+    }
+
+    /**
+     * 替换限流规则并清空已有的令牌桶
+     */
+    pub fn update(&self, args: RateLimitArgs) {
+        info!("rate limit changed to {:?}", args);
+        *self.args.write().unwrap_or_else(|err| err.into_inner()) = args;
+        self.buckets
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clear();
+    }
+
+    /**
+     * 返回请求所属的分组, 及其单位时间内的请求数和周期
+     */
+    fn group(&self, method: &str, path: &str) -> (String, u32, u64) {
+        let args = self.args();
+        args.rules
+            .iter()
+            .find(|rule| rule.matches(method, path))
+            .map(|rule| (rule.name.clone(), rule.requests, rule.period))
+            .unwrap_or(("default".to_string(), args.requests, args.period))
+    }
+
+    /**
+     * 消耗一个令牌, 不足时返回需要等待的秒数
+     */
+    fn acquire(&self, group: &str, client: &str, requests: u32, period: u64) -> Result<(), u64> {
+        let capacity = requests.max(1) as f64;
+        let rate = capacity / period.max(1) as f64;
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().unwrap_or_else(|err| err.into_inner());
+        if buckets.len() > MAX_TRACKED_BUCKETS {
+            buckets.retain(|_, bucket| {
+                bucket.tokens + now.duration_since(bucket.updated_at).as_secs_f64() * rate
+                    < capacity
+            });
+        }
+        let bucket = buckets
+            .entry((group.to_string(), client.to_string()))
+            .or_insert(Bucket {
+                tokens: capacity,
+                updated_at: now,
+            });
+        let elapsed = now.duration_since(bucket.updated_at).as_secs_f64();
+        bucket.tokens = (bucket.tokens + elapsed * rate).min(capacity);
+        bucket.updated_at = now;
+        if bucket.tokens >= 1.0 {
+            bucket.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = Duration::from_secs_f64((1.0 - bucket.tokens) / rate);
+            Err(wait.as_secs_f64().ceil().max(1.0) as u64)
+        }
+    }
+
+    /**
+     * 限流的客户端标识: API Key 或用户, 未认证时为来源 IP
+     */
+    fn client_of(&self, request: &Request) -> (&'static str, String) {
+        if let Some(user) = request.extensions().get::<AuthUser>() {
+            let kind = if user.scopes.is_some() {
+                "api_key"
+            } else {
+                "user"
+            };
+            return (kind, user.subject.clone());
+        }
+        let forwarded = self
+            .args()
+            .trust_forwarded
+            .then(|| {
+                request
+                    .headers()
+                    .get("x-forwarded-for")
+                    .and_then(|value| value.to_str().ok())
+                    .and_then(|value| value.split(',').next())
+                    .and_then(|value| value.trim().parse::<IpAddr>().ok())
+            })
+            .flatten();
+        let ip = forwarded.or_else(|| {
+            request
+                .extensions()
+                .get::<ConnectInfo<SocketAddr>>()
+                .map(|ConnectInfo(addr)| addr.ip())
+        });
+        (
+            "ip",
+            ip.map(|ip| ip.to_string())
+                .unwrap_or_else(|| "unknown".to_string()),
+        )
+    }
+}
+
+/**
+ * 限流中间件, 在认证之后执行以便按 API Key 或用户计数
+ */
+pub async fn limit(State(state): State<Arc<ApiState>>, request: Request, next: Next) -> Response {
+    let limiter = &state.rate_limiter;
+    if !limiter.enabled() {
+        return next.run(request).await;
+    }
+    let path = request
+        .extensions()
+        .get::<OriginalUri>()
+        .map(|uri| uri.path().to_string())
+        .unwrap_or_else(|| request.uri().path().to_string());
+    let (group, requests, period) = limiter.group(request.method().as_str(), &path);
+    let (kind, client) = limiter.client_of(&request);
+    match limiter.acquire(&group, &client, requests, period) {
+        Ok(()) => next.run(request).await,
+        Err(retry_after) => {
+            counter!(RATE_LIMITED, "group" => group, "client" => kind).increment(1);
+            ApiError::TooManyRequests { retry_after }.into_response()
+        }
+    }
+}
@@ -0,0 +1,521 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, authorize, require_scope};
+use crate::api::error::{ApiError, FieldError};
+use crate::api::interface::{ApiBody, ApiResult, ErrorBody};
+use crate::application::subscription::EVENTS;
+use crate::repository::entity::{ProjectRole, SubscriptionEntity, SubscriptionFilter};
+use axum::extract::rejection::{JsonRejection, PathRejection, QueryRejection};
+use axum::extract::{Path, Query, State};
+use axum::{Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::types::Json as SqlJson;
+use std::sync::Arc;
+use utoipa::{IntoParams, ToSchema};
+
+/**
+ * 创建/更新订阅请求
+ */
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct SubscriptionRequest {
+    #[schema(example = "release failures")]
+    pub name: String,
+    #[schema(example = "demo")]
+    pub project_id: String,
+    /// 为空时订阅项目下所有流水线
+    pub pipeline_id: Option<String>,
+    /// 订阅的构建事件: started/succeed/failed/canceled
+    #[schema(example = json!(["failed"]))]
+    pub events: Vec<String>,
+    pub channel_id: i64,
+    /// 默认为创建人
+    pub owner: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default = "default_enabled")]
+    pub enabled: bool,
+    /// 限定的 DevOps 实例, 为空时不限定
+    #[schema(example = "prod")]
+    pub source: Option<String>,
+}
+
+fn default_enabled() -> bool {
+    true
+}
+
+impl SubscriptionRequest {
+    fn validate(&self) -> Result<(), ApiError> {
+        let mut errors = vec![];
+        if self.name.trim().is_empty() {
+            errors.push(FieldError::new("name", "must not be empty"));
+        }
+        if self.project_id.trim().is_empty() {
+            errors.push(FieldError::new("project_id", "must not be empty"));
+        }
+        if self
+            .pipeline_id
+            .as_ref()
+            .is_some_and(|pipeline_id| pipeline_id.trim().is_empty())
+        {
+            errors.push(FieldError::new("pipeline_id", "must not be blank"));
+        }
+        if self.events.is_empty() {
+            errors.push(FieldError::new("events", "must not be empty"));
+        }
+        for (index, event) in self.events.iter().enumerate() {
+            if !EVENTS.contains(&event.as_str()) {
+                errors.push(FieldError::new(
+                    format!("events[{}]", index),
+                    format!("unknown event {}, expected one of {:?}", event, EVENTS),
+                ));
+            }
+        }
+        if self.channel_id <= 0 {
+            errors.push(FieldError::new("channel_id", "must be a positive id"));
+        }
+        if errors.is_empty() {
+            Ok(())
+        } else {
+            Err(ApiError::Validation(errors))
+        }
+    }
+}
+
+/**
+ * 订阅信息
+ */
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SubscriptionView {
+    pub id: i64,
+    pub name: String,
+    pub project_id: String,
+    pub pipeline_id: Option<String>,
+    pub events: Vec<String>,
+    pub channel_id: i64,
+    pub owner: String,
+    pub tags: Vec<String>,
+    pub enabled: bool,
+    pub source: Option<String>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+impl From<SubscriptionEntity> for SubscriptionView {
+    fn from(entity: SubscriptionEntity) -> Self {
+        Self {
+            id: entity.id.unwrap_or_default(),
+            name: entity.name,
+            project_id: entity.project_id,
+            pipeline_id: entity.pipeline_id,
+            events: entity.events.0,
+            channel_id: entity.channel_id,
+            owner: entity.owner,
+            tags: entity.tags.0,
+            enabled: entity.enabled,
+            source: entity.source,
+            created_at: entity.created_at,
+            updated_at: entity.updated_at,
+        }
+    }
+}
+
+/**
+ * 订阅查询条件
+ */
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct SubscriptionQuery {
+    pub project_id: Option<String>,
+    pub pipeline_id: Option<String>,
+    pub channel_id: Option<i64>,
+    pub owner: Option<String>,
+    pub enabled: Option<bool>,
+    /// DevOps 实例
+    pub source: Option<String>,
+}
+
+/**
+ * 校验限定的 DevOps 实例已配置
+ */
+fn check_source(state: &ApiState, request: &SubscriptionRequest) -> Result<(), ApiError> {
+    let Some(source) = &request.source else {
+        return Ok(());
+    };
+    let sources = state.services.pipelines.sources();
+    if sources.contains(source) {
+        Ok(())
+    } else {
+        Err(ApiError::Validation(vec![FieldError::new(
+            "source",
+            format!(
+                "unknown devops source {}, expected one of {:?}",
+                source, sources
+            ),
+        )]))
+    }
+}
+
+/**
+ * 校验渠道存在且属于同一项目
+ */
+async fn check_channel(state: &ApiState, request: &SubscriptionRequest) -> Result<(), ApiError> {
+    let channel = state.services.channels.get(request.channel_id).await?;
+    match channel {
+        Some(channel) if channel.project_id == request.project_id => Ok(()),
+        Some(_) => Err(ApiError::Validation(vec![FieldError::new(
+            "channel_id",
+            "channel belongs to another project",
+        )])),
+        None => Err(ApiError::Validation(vec![FieldError::new(
+            "channel_id",
+            format!("channel {} not found", request.channel_id),
+        )])),
+    }
+}
+
+async fn find_subscription(state: &ApiState, id: i64) -> Result<SubscriptionEntity, ApiError> {
+    state
+        .services
+        .subscriptions
+        .get(id)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("subscription {} not found", id)))
+}
+
+/**
+ * 创建订阅
+ */
+#[utoipa::path(
+    post,
+    tag = "subscriptions",
+    description = "创建订阅",
+    path = "/api/subscriptions",
+    request_body = SubscriptionRequest,
+    responses(
+        (status = 200, description = "Subscription created", body = ApiBody<SubscriptionView>),
+        (status = BAD_REQUEST, description = "Malformed request body", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid fields", body = ErrorBody)
+    )
+)]
+pub async fn create_subscription(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    payload: Result<Json<SubscriptionRequest>, JsonRejection>,
+) -> ApiResult<SubscriptionView> {
+    let Json(request) = payload?;
+    require_scope(user.as_deref(), "subscriptions:write")?;
+    request.validate()?;
+    authorize(
+        &state,
+        user.as_deref(),
+        request.source.as_deref(),
+        &request.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    check_source(&state, &request)?;
+    check_channel(&state, &request).await?;
+
+    let now = Utc::now();
+    let owner = request
+        .owner
+        .or_else(|| user.map(|Extension(user)| user.subject))
+        .unwrap_or_default();
+    let entity = SubscriptionEntity {
+        id: None,
+        name: request.name,
+        project_id: request.project_id,
+        pipeline_id: request.pipeline_id,
+        events: SqlJson(request.events),
+        channel_id: request.channel_id,
+        owner,
+        tags: SqlJson(request.tags),
+        enabled: request.enabled,
+        created_at: now,
+        updated_at: now,
+        source: request.source,
+    };
+    let entity = state.services.subscriptions.create(entity).await?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
+
+/**
+ * 订阅详情
+ */
+#[utoipa::path(
+    get,
+    tag = "subscriptions",
+    description = "订阅详情",
+    path = "/api/subscriptions/{id}",
+    params(("id" = i64, Path, description = "Subscription id")),
+    responses(
+        (status = 200, description = "Subscription", body = ApiBody<SubscriptionView>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Subscription not found", body = ErrorBody)
+    )
+)]
+pub async fn get_subscription(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<SubscriptionView> {
+    let Path(id) = path?;
+    require_scope(user.as_deref(), "subscriptions:read")?;
+    let entity = find_subscription(&state, id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        entity.source.as_deref(),
+        &entity.project_id,
+        ProjectRole::Viewer,
+    )
+    .await?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
+
+/**
+ * 订阅列表
+ */
+#[utoipa::path(
+    get,
+    tag = "subscriptions",
+    description = "订阅列表, 非管理员只返回所在项目的订阅",
+    path = "/api/subscriptions",
+    params(SubscriptionQuery),
+    responses(
+        (status = 200, description = "Subscriptions", body = ApiBody<Vec<SubscriptionView>>),
+        (status = BAD_REQUEST, description = "Malformed query", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Viewer role required", body = ErrorBody)
+    )
+)]
+pub async fn list_subscriptions(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    query: Result<Query<SubscriptionQuery>, QueryRejection>,
+) -> ApiResult<Vec<SubscriptionView>> {
+    let Query(query) = query?;
+    require_scope(user.as_deref(), "subscriptions:read")?;
+    let mut filter = SubscriptionFilter {
+        project_id: query.project_id,
+        project_ids: None,
+        pipeline_id: query.pipeline_id,
+        channel_id: query.channel_id,
+        owner: query.owner,
+        enabled: query.enabled,
+        source: query.source,
+    };
+    match (&filter.project_id, user.as_deref()) {
+        (Some(project_id), user) => {
+            authorize(
+                &state,
+                user,
+                filter.source.as_deref(),
+                project_id,
+                ProjectRole::Viewer,
+            )
+            .await?;
+        }
+        (None, Some(user)) if !user.is_admin() => {
+            filter.project_ids = Some(
+                state
+                    .services
+                    .access
+                    .projects_of(filter.source.as_deref(), &user.subject)
+                    .await?,
+            );
+        }
+        _ => {}
+    }
+    let entities = state.services.subscriptions.list(&filter).await?;
+    Ok(ApiBody::success(Some(
+        entities.into_iter().map(SubscriptionView::from).collect(),
+    )))
+}
+
+/**
+ * 更新订阅
+ */
+#[utoipa::path(
+    put,
+    tag = "subscriptions",
+    description = "更新订阅",
+    path = "/api/subscriptions/{id}",
+    params(("id" = i64, Path, description = "Subscription id")),
+    request_body = SubscriptionRequest,
+    responses(
+        (status = 200, description = "Subscription updated", body = ApiBody<SubscriptionView>),
+        (status = BAD_REQUEST, description = "Malformed id or request body", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Subscription not found", body = ErrorBody),
+        (status = UNPROCESSABLE_ENTITY, description = "Invalid fields", body = ErrorBody)
+    )
+)]
+pub async fn update_subscription(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+    payload: Result<Json<SubscriptionRequest>, JsonRejection>,
+) -> ApiResult<SubscriptionView> {
+    let Path(id) = path?;
+    let Json(request) = payload?;
+    require_scope(user.as_deref(), "subscriptions:write")?;
+    let existing = find_subscription(&state, id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        existing.source.as_deref(),
+        &existing.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    request.validate()?;
+    if request.project_id != existing.project_id {
+        authorize(
+            &state,
+            user.as_deref(),
+            request.source.as_deref(),
+            &request.project_id,
+            ProjectRole::Editor,
+        )
+        .await?;
+    }
+    check_source(&state, &request)?;
+    check_channel(&state, &request).await?;
+
+    let entity = SubscriptionEntity {
+        id: existing.id,
+        name: request.name,
+        project_id: request.project_id,
+        pipeline_id: request.pipeline_id,
+        events: SqlJson(request.events),
+        channel_id: request.channel_id,
+        owner: request.owner.unwrap_or(existing.owner),
+        tags: SqlJson(request.tags),
+        enabled: request.enabled,
+        created_at: existing.created_at,
+        updated_at: Utc::now(),
+        source: request.source,
+    };
+    let entity = state.services.subscriptions.update(entity).await?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
+
+async fn set_enabled(
+    state: &ApiState,
+    user: Option<&AuthUser>,
+    id: i64,
+    enabled: bool,
+) -> ApiResult<SubscriptionView> {
+    require_scope(user, "subscriptions:write")?;
+    let existing = find_subscription(state, id).await?;
+    authorize(
+        state,
+        user,
+        existing.source.as_deref(),
+        &existing.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    let entity = state
+        .services
+        .subscriptions
+        .set_enabled(id, enabled)
+        .await?
+        .ok_or_else(|| ApiError::NotFound(format!("subscription {} not found", id)))?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
+
+/**
+ * 启用订阅
+ */
+#[utoipa::path(
+    post,
+    tag = "subscriptions",
+    description = "启用订阅",
+    path = "/api/subscriptions/{id}/enable",
+    params(("id" = i64, Path, description = "Subscription id")),
+    responses(
+        (status = 200, description = "Subscription enabled", body = ApiBody<SubscriptionView>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Subscription not found", body = ErrorBody)
+    )
+)]
+pub async fn enable_subscription(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<SubscriptionView> {
+    let Path(id) = path?;
+    set_enabled(&state, user.as_deref(), id, true).await
+}
+
+/**
+ * 停用订阅
+ */
+#[utoipa::path(
+    post,
+    tag = "subscriptions",
+    description = "停用订阅",
+    path = "/api/subscriptions/{id}/disable",
+    params(("id" = i64, Path, description = "Subscription id")),
+    responses(
+        (status = 200, description = "Subscription disabled", body = ApiBody<SubscriptionView>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Subscription not found", body = ErrorBody)
+    )
+)]
+pub async fn disable_subscription(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<SubscriptionView> {
+    let Path(id) = path?;
+    set_enabled(&state, user.as_deref(), id, false).await
+}
+
+/**
+ * 删除订阅
+ */
+#[utoipa::path(
+    delete,
+    tag = "subscriptions",
+    description = "删除订阅",
+    path = "/api/subscriptions/{id}",
+    params(("id" = i64, Path, description = "Subscription id")),
+    responses(
+        (status = 200, description = "Subscription deleted", body = ApiBody<SubscriptionView>),
+        (status = BAD_REQUEST, description = "Malformed id", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Editor role required", body = ErrorBody),
+        (status = NOT_FOUND, description = "Subscription not found", body = ErrorBody)
+    )
+)]
+pub async fn delete_subscription(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    path: Result<Path<i64>, PathRejection>,
+) -> ApiResult<SubscriptionView> {
+    let Path(id) = path?;
+    require_scope(user.as_deref(), "subscriptions:write")?;
+    let existing = find_subscription(&state, id).await?;
+    authorize(
+        &state,
+        user.as_deref(),
+        existing.source.as_deref(),
+        &existing.project_id,
+        ProjectRole::Editor,
+    )
+    .await?;
+    let entity = state.services.subscriptions.delete(id).await?;
+    Ok(ApiBody::success(Some(entity.into())))
+}
@@ -0,0 +1,311 @@
+use crate::api::ApiState;
+use crate::api::auth::{AuthUser, authorize, require_scope};
+use crate::api::error::ApiError;
+use crate::api::interface::ErrorBody;
+use crate::application::event::Event;
+use crate::repository::entity::ProjectRole;
+use axum::Extension;
+use axum::extract::State;
+use axum::extract::ws::{CloseFrame, Message, WebSocket, WebSocketUpgrade, close_code};
+use axum::response::Response;
+use axum_prometheus::metrics::{counter, gauge};
+use log::{debug, warn};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashSet, VecDeque};
+use std::sync::Arc;
+use tokio::select;
+use tokio::sync::broadcast::error::RecvError;
+
+/// 当前连接数
+const CONNECTIONS: &str = "websocket_connections";
+/// 最大连接数
+const CONNECTIONS_MAX: &str = "websocket_connections_max";
+/// 超出上限被拒绝的连接数
+const CONNECTIONS_REJECTED: &str = "websocket_connections_rejected_total";
+
+pub fn describe_metrics(max_connections: usize) {
+    gauge!(CONNECTIONS).set(0.0);
+    gauge!(CONNECTIONS_MAX).set(max_connections as f64);
+    counter!(CONNECTIONS_REJECTED).absolute(0);
+}
+
+/**
+ * 客户端消息
+ */
+#[derive(Debug, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ClientMessage {
+    /// 订阅项目或项目下的单条流水线
+    Subscribe {
+        project_id: String,
+        pipeline_id: Option<String>,
+    },
+    Unsubscribe {
+        project_id: String,
+        pipeline_id: Option<String>,
+    },
+    /// 确认编号不大于 id 的所有事件
+    Ack {
+        id: u64,
+    },
+    Ping,
+}
+
+/**
+ * 服务端消息
+ */
+#[derive(Debug, Serialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum ServerMessage<'a> {
+    Subscribed {
+        project_id: String,
+        pipeline_id: Option<String>,
+    },
+    Unsubscribed {
+        project_id: String,
+        pipeline_id: Option<String>,
+    },
+    Event {
+        event: &'a Event,
+    },
+    /// 积压超出缓冲区, 部分事件已丢弃
+    Lagged {
+        skipped: u64,
+    },
+    Pong,
+    Error {
+        message: String,
+    },
+}
+
+#[derive(Debug, PartialEq, Eq, Hash)]
+struct Topic {
+    project_id: String,
+    pipeline_id: Option<String>,
+}
+
+impl Topic {
+    fn matches(&self, event: &Event) -> bool {
+        self.project_id == event.project_id
+            && self
+                .pipeline_id
+                .as_ref()
+                .is_none_or(|pipeline_id| event.pipeline_id.as_ref() == Some(pipeline_id))
+    }
+}
+
+/**
+ * WebSocket 事件订阅
+ */
+#[utoipa::path(
+    get,
+    tag = "events",
+    description = "WebSocket 事件订阅, 消息均为带 type 字段的 JSON 文本帧. \
+                   客户端发送 subscribe/unsubscribe {project_id, pipeline_id?} 调整订阅, ack {id} 确认事件, ping 探活; \
+                   服务端推送 event {event}, 并回复 subscribed/unsubscribed/pong/error. \
+                   未确认事件达到上限后暂停推送, 积压超出缓冲区时发送 lagged {skipped}",
+    path = "/api/events/ws",
+    responses(
+        (status = 101, description = "Switching protocols"),
+        (status = BAD_REQUEST, description = "Not a websocket request", body = ErrorBody),
+        (status = UNAUTHORIZED, description = "Missing or invalid credentials", body = ErrorBody),
+        (status = FORBIDDEN, description = "Missing events:read scope", body = ErrorBody),
+        (status = SERVICE_UNAVAILABLE, description = "Too many connections", body = ErrorBody)
+    )
+)]
+pub async fn connect(
+    State(state): State<Arc<ApiState>>,
+    user: Option<Extension<AuthUser>>,
+    upgrade: WebSocketUpgrade,
+) -> Result<Response, ApiError> {
+    let user = user.map(|Extension(user)| user);
+    require_scope(user.as_ref(), "events:read")?;
+    let Ok(permit) = state.connections.clone().try_acquire_owned() else {
+        counter!(CONNECTIONS_REJECTED).increment(1);
+        return Err(ApiError::Unavailable(
+            "too many websocket connections".to_string(),
+        ));
+    };
+    Ok(upgrade.on_upgrade(move |socket| async move {
+        gauge!(CONNECTIONS).increment(1.0);
+        Connection::new(state, user).run(socket).await;
+        gauge!(CONNECTIONS).decrement(1.0);
+        drop(permit);
+    }))
+}
+
+struct Connection {
+    state: Arc<ApiState>,
+    user: Option<AuthUser>,
+    topics: HashSet<Topic>,
+    /// 已推送未确认的事件
+    in_flight: VecDeque<u64>,
+}
+
+impl Connection {
+    fn new(state: Arc<ApiState>, user: Option<AuthUser>) -> Self {
+        Self {
+            state,
+            user,
+            topics: HashSet::new(),
+            in_flight: VecDeque::new(),
+        }
+    }
+
+    async fn run(mut self, mut socket: WebSocket) {
+        let events = self.state.services.events.clone();
+        let shutdown = self.state.shutdown.clone();
+        let (pending, mut receiver) = events.subscribe(None);
+        let mut pending = VecDeque::from(pending);
+        // 已从总线取出的最后一个事件, 用于积压时从缓冲区续传
+        let mut last_id = None;
+        let mut heartbeat = tokio::time::interval(self.state.heartbeat);
+        loop {
+            while self.in_flight.len() < self.state.ack_window
+                && let Some(event) = pending.pop_front()
+            {
+                if self.topics.iter().any(|topic| topic.matches(&event)) {
+                    if !send(&mut socket, &ServerMessage::Event { event: &event }).await {
+                        return;
+                    }
+                    self.in_flight.push_back(event.id);
+                }
+            }
+            let ready = pending.is_empty() && self.in_flight.len() < self.state.ack_window;
+            select! {
+                _ = shutdown.cancelled() => {
+                    let _ = socket
+                        .send(Message::Close(Some(CloseFrame {
+                            code: close_code::AWAY,
+                            reason: "server shutting down".into(),
+                        })))
+                        .await;
+                    return;
+                },
+                _ = heartbeat.tick() => {
+                    if socket.send(Message::Ping(Default::default())).await.is_err() {
+                        return;
+                    }
+                },
+                message = socket.recv() => match message {
+                    Some(Ok(Message::Text(text))) => {
+                        let reply = self.handle(text.as_str()).await;
+                        if let Some(reply) = reply
+                            && !send(&mut socket, &reply).await
+                        {
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Binary(_))) => {
+                        let reply = ServerMessage::Error {
+                            message: "binary frames are not supported".to_string(),
+                        };
+                        if !send(&mut socket, &reply).await {
+                            return;
+                        }
+                    }
+                    Some(Ok(Message::Close(_))) | None => return,
+                    Some(Ok(_)) => {}
+                    Some(Err(err)) => {
+                        debug!("websocket receive failed. {:?}", err);
+                        return;
+                    }
+                },
+                received = receiver.recv(), if ready => match received {
+                    Ok(event) => {
+                        last_id = Some(event.id);
+                        pending.push_back(event);
+                    }
+                    Err(RecvError::Lagged(_)) => {
+                        let (missed, fresh) = events.subscribe(last_id);
+                        receiver = fresh;
+                        // 事件编号连续, 缓冲区中缺失的部分已被丢弃
+                        let skipped = match (last_id, missed.first()) {
+                            (Some(last_id), Some(first)) => first.id.saturating_sub(last_id + 1),
+                            _ => 0,
+                        };
+                        if skipped > 0 && !send(&mut socket, &ServerMessage::Lagged { skipped }).await {
+                            return;
+                        }
+                        if let Some(last) = missed.last() {
+                            last_id = Some(last.id);
+                        }
+                        pending.extend(missed);
+                    }
+                    Err(RecvError::Closed) => return,
+                },
+            }
+        }
+    }
+
+    async fn handle(&mut self, text: &str) -> Option<ServerMessage<'static>> {
+        let message = match serde_json::from_str::<ClientMessage>(text) {
+            Ok(message) => message,
+            Err(err) => {
+                return Some(ServerMessage::Error {
+                    message: format!("invalid message: {}", err),
+                });
+            }
+        };
+        match message {
+            ClientMessage::Subscribe {
+                project_id,
+                pipeline_id,
+            } => {
+                if let Err(err) = authorize(
+                    &self.state,
+                    self.user.as_ref(),
+                    None,
+                    &project_id,
+                    ProjectRole::Viewer,
+                )
+                .await
+                {
+                    warn!("reject websocket subscription to {}: {}", project_id, err);
+                    return Some(ServerMessage::Error {
+                        message: err.to_string(),
+                    });
+                }
+                self.topics.insert(Topic {
+                    project_id: project_id.clone(),
+                    pipeline_id: pipeline_id.clone(),
+                });
+                Some(ServerMessage::Subscribed {
+                    project_id,
+                    pipeline_id,
+                })
+            }
+            ClientMessage::Unsubscribe {
+                project_id,
+                pipeline_id,
+            } => {
+                self.topics.remove(&Topic {
+                    project_id: project_id.clone(),
+                    pipeline_id: pipeline_id.clone(),
+                });
+                Some(ServerMessage::Unsubscribed {
+                    project_id,
+                    pipeline_id,
+                })
+            }
+            ClientMessage::Ack { id } => {
+                while self.in_flight.front().is_some_and(|sent| *sent <= id) {
+                    self.in_flight.pop_front();
+                }
+                None
+            }
+            ClientMessage::Ping => Some(ServerMessage::Pong),
+        }
+    }
+}
+
+async fn send(socket: &mut WebSocket, message: &ServerMessage<'_>) -> bool {
+    let text = match serde_json::to_string(message) {
+        Ok(text) => text,
+        Err(err) => {
+            warn!("serialize websocket message failed. {:?}", err);
+            return true;
+        }
+    };
+    socket.send(Message::Text(text.into())).await.is_ok()
+}
@@ -0,0 +1,219 @@
+use crate::application::supervisor::ManagedService;
+use crate::devops::DevOpsApiClient;
+use crate::repository::DatabaseRepository;
+use crate::repository::entity::{ProjectMemberEntity, ProjectRole};
+use crate::repository::sqlite::ProjectMemberRepository;
+use chrono::Utc;
+use log::{error, info};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::select;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/**
+ * 项目级访问控制
+ */
+#[derive(Clone)]
+pub struct AccessService {
+    repository: ProjectMemberRepository,
+}
+
+impl AccessService {
+    pub fn new(repository: ProjectMemberRepository) -> Self {
+        Self { repository }
+    }
+
+    /**
+     * 调用方在项目中的角色
+     */
+    pub async fn role_of(
+        &self,
+        source: &str,
+        project_id: &str,
+        subject: &str,
+    ) -> Result<Option<ProjectRole>, anyhow::Error> {
+        self.repository.find_role(source, project_id, subject).await
+    }
+
+    /**
+     * 调用方所在的项目, 指定实例时只取该实例的项目
+     */
+    pub async fn projects_of(
+        &self,
+        source: Option<&str>,
+        subject: &str,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        self.repository.find_projects(source, subject).await
+    }
+
+    pub async fn members(
+        &self,
+        source: &str,
+        project_id: &str,
+    ) -> Result<Vec<ProjectMemberEntity>, anyhow::Error> {
+        self.repository.find_by_project(source, project_id).await
+    }
+
+    pub async fn set_role(
+        &self,
+        source: &str,
+        project_id: &str,
+        subject: &str,
+        role: ProjectRole,
+    ) -> Result<ProjectMemberEntity, anyhow::Error> {
+        let now = Utc::now();
+        self.repository
+            .save_or_update(ProjectMemberEntity {
+                source: source.to_string(),
+                project_id: project_id.to_string(),
+                subject: subject.to_string(),
+                role,
+                origin: "manual".to_string(),
+                created_at: now,
+                updated_at: now,
+            })
+            .await
+    }
+
+    pub async fn remove(
+        &self,
+        source: &str,
+        project_id: &str,
+        subject: &str,
+    ) -> Result<bool, anyhow::Error> {
+        self.repository.delete(source, project_id, subject).await
+    }
+
+    /**
+     * 从 DevOps 同步项目成员, 成员归属客户端对应的实例
+     */
+    pub async fn sync_project(
+        &self,
+        client: &DevOpsApiClient,
+        project_id: &str,
+    ) -> Result<usize, anyhow::Error> {
+        let members: Vec<(String, ProjectRole)> = client
+            .get_project_members(project_id.to_string())
+            .await?
+            .into_iter()
+            .map(|member| {
+                let role = Self::map_group(&member.group_code);
+                (member.user_id, role)
+            })
+            .collect();
+        let count = members.len();
+        self.repository
+            .replace_synced(client.name(), project_id, members)
+            .await?;
+        Ok(count)
+    }
+
+    /**
+     * DevOps 用户组映射为项目角色
+     */
+    fn map_group(group_code: &str) -> ProjectRole {
+        match group_code {
+            "manager" => ProjectRole::Admin,
+            "developer" | "maintainer" => ProjectRole::Editor,
+            _ => ProjectRole::Viewer,
+        }
+    }
+}
+
+/**
+ * 定时同步项目成员
+ */
+pub struct MemberSync {
+    cancel_token: CancellationToken,
+    client: Arc<DevOpsApiClient>,
+    access: AccessService,
+    projects: Arc<RwLock<Vec<String>>>,
+    interval: Duration,
+}
+
+impl MemberSync {
+    pub fn new(
+        token: CancellationToken,
+        client: Arc<DevOpsApiClient>,
+        access: AccessService,
+        projects: Vec<String>,
+        interval: u64,
+    ) -> Self {
+        Self {
+            cancel_token: token,
+            client,
+            access,
+            projects: Arc::new(RwLock::new(projects)),
+            interval: Duration::from_secs(interval),
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        self.client.name()
+    }
+
+    pub fn projects(&self) -> Vec<String> {
+        self.projects
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /**
+     * 更新同步的项目, 下一轮生效
+     */
+    pub fn set_projects(&self, projects: Vec<String>) {
+        info!(
+            "member sync of {} projects changed to {:?}",
+            self.client.name(),
+            projects
+        );
+        *self.projects.write().unwrap_or_else(|err| err.into_inner()) = projects;
+    }
+}
+
+impl ManagedService for MemberSync {
+    fn name(&self) -> String {
+        format!("member_sync.{}", self.client.name())
+    }
+
+    fn start(&self) -> Result<JoinHandle<Result<(), anyhow::Error>>, anyhow::Error> {
+        info!(
+            "starting member sync of {} for projects {:?}",
+            self.client.name(),
+            self.projects()
+        );
+        let token = self.cancel_token.clone();
+        let client = self.client.clone();
+        let access = self.access.clone();
+        let projects = self.projects.clone();
+        let mut ticker = tokio::time::interval(self.interval);
+        Ok(tokio::spawn(async move {
+            loop {
+                select! {
+                    _ = token.cancelled() => {
+                        info!("received shutdown member sync signal");
+                        break;
+                    },
+                    _ = ticker.tick() => {
+                        let projects = projects.read().unwrap_or_else(|err| err.into_inner()).clone();
+                        for project_id in projects.iter() {
+                            match access.sync_project(&client, project_id).await {
+                                Ok(count) => info!("synced {} members of project {}", count, project_id),
+                                Err(err) => error!("sync project {} members failed. {:?}", project_id, err),
+                            }
+                        }
+                    },
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn stop(&self) -> Result<(), anyhow::Error> {
+        info!("Stopping MemberSync");
+        self.cancel_token.cancel();
+        Ok(())
+    }
+}
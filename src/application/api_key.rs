@@ -0,0 +1,101 @@
+use crate::repository::DatabaseRepository;
+use crate::repository::entity::ApiKeyEntity;
+use crate::repository::sqlite::ApiKeyRepository;
+use chrono::{DateTime, Utc};
+use sha2::{Digest, Sha256};
+use sqlx::types::Json;
+
+/// 生成的 key 前缀, 便于在日志和密钥扫描中识别
+const KEY_PREFIX: &str = "vn_";
+/// 列表中展示的 key 前缀长度
+const DISPLAY_PREFIX_LEN: usize = 11;
+
+/**
+ * API Key 管理
+ */
+#[derive(Clone)]
+pub struct ApiKeyService {
+    repository: ApiKeyRepository,
+}
+
+impl ApiKeyService {
+    pub fn new(repository: ApiKeyRepository) -> Self {
+        Self { repository }
+    }
+
+    /**
+     * 创建 key, 明文只在创建时返回一次
+     */
+    pub async fn create(
+        &self,
+        name: String,
+        scopes: Vec<String>,
+        expires_at: Option<DateTime<Utc>>,
+        created_by: String,
+    ) -> Result<(ApiKeyEntity, String), anyhow::Error> {
+        let key = Self::generate();
+        let entity = ApiKeyEntity {
+            id: None,
+            name,
+            prefix: key[..DISPLAY_PREFIX_LEN].to_string(),
+            key_hash: Self::hash(&key),
+            scopes: Json(scopes),
+            created_by,
+            created_at: Utc::now(),
+            expires_at,
+            revoked_at: None,
+            rotated_at: None,
+            last_used_at: None,
+            usage_count: 0,
+        };
+        let entity = self.repository.save(entity).await?;
+        Ok((entity, key))
+    }
+
+    pub async fn list(&self) -> Result<Vec<ApiKeyEntity>, anyhow::Error> {
+        self.repository.find_all().await
+    }
+
+    pub async fn revoke(&self, id: i64) -> Result<Option<ApiKeyEntity>, anyhow::Error> {
+        self.repository.revoke(id).await
+    }
+
+    /**
+     * 轮换 key, 旧的明文立即失效
+     */
+    pub async fn rotate(&self, id: i64) -> Result<Option<(ApiKeyEntity, String)>, anyhow::Error> {
+        let key = Self::generate();
+        let entity = self
+            .repository
+            .rotate(id, &key[..DISPLAY_PREFIX_LEN], &Self::hash(&key))
+            .await?;
+        Ok(entity.map(|entity| (entity, key)))
+    }
+
+    /**
+     * 校验明文 key 并记录使用
+     */
+    pub async fn authenticate(&self, key: &str) -> Result<Option<ApiKeyEntity>, anyhow::Error> {
+        let entity = self
+            .repository
+            .find_active_by_hash(&Self::hash(key))
+            .await?;
+        if let Some(id) = entity.as_ref().and_then(|entity| entity.id) {
+            self.repository.touch(id).await?;
+        }
+        Ok(entity)
+    }
+
+    fn generate() -> String {
+        format!(
+            "{}{}{}",
+            KEY_PREFIX,
+            uuid::Uuid::new_v4().simple(),
+            uuid::Uuid::new_v4().simple()
+        )
+    }
+
+    fn hash(key: &str) -> String {
+        hex::encode(Sha256::digest(key.as_bytes()))
+    }
+}
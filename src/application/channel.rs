@@ -0,0 +1,176 @@
+use crate::application::crypto::SecretCipher;
+use crate::channel::{ChannelClient, ChannelMessage, ChannelResponse};
+use crate::repository::DatabaseRepository;
+use crate::repository::entity::{ChannelEntity, ChannelKind};
+use crate::repository::sqlite::ChannelRepository;
+use crate::telemetry;
+use anyhow::{Context, anyhow};
+use opentelemetry::KeyValue;
+use opentelemetry::context::FutureExt;
+use opentelemetry::trace::{SpanKind, TraceContextExt};
+use serde_json::Value;
+use sqlx::types::Json;
+use std::collections::BTreeMap;
+
+/**
+ * 通知渠道管理, 敏感字段以 JSON 整体加密保存
+ */
+#[derive(Clone)]
+pub struct ChannelService {
+    repository: ChannelRepository,
+    cipher: Option<SecretCipher>,
+    client: ChannelClient,
+}
+
+impl ChannelService {
+    pub fn new(
+        repository: ChannelRepository,
+        encryption_key: Option<&str>,
+        client: ChannelClient,
+    ) -> Result<Self, anyhow::Error> {
+        let cipher = encryption_key.map(SecretCipher::new).transpose()?;
+        Ok(Self {
+            repository,
+            cipher,
+            client,
+        })
+    }
+
+    /**
+     * 是否配置了加密密钥, 未配置时不能保存敏感字段
+     */
+    pub fn encryption_enabled(&self) -> bool {
+        self.cipher.is_some()
+    }
+
+    /**
+     * 校验渠道的非敏感配置, 包括覆盖的 webhook 地址是否允许
+     */
+    pub fn validate_config(&self, kind: ChannelKind, config: &Value) -> Result<(), anyhow::Error> {
+        self.client.validate_config(kind, config)
+    }
+
+    /**
+     * 运行时更新允许的 webhook 地址
+     */
+    pub fn set_allowed_base_urls(&self, allowed_base_urls: Vec<String>) {
+        self.client.set_allowed_base_urls(allowed_base_urls);
+    }
+
+    pub async fn create(
+        &self,
+        mut channel: ChannelEntity,
+        secrets: &BTreeMap<String, String>,
+    ) -> Result<ChannelEntity, anyhow::Error> {
+        self.seal(&mut channel, secrets)?;
+        self.repository.save(channel).await
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<ChannelEntity>, anyhow::Error> {
+        self.repository.find_by_id(id).await
+    }
+
+    pub async fn list(
+        &self,
+        project_ids: Option<&[String]>,
+    ) -> Result<Vec<ChannelEntity>, anyhow::Error> {
+        self.repository.find_by_projects(project_ids).await
+    }
+
+    pub async fn update(
+        &self,
+        mut channel: ChannelEntity,
+        secrets: &BTreeMap<String, String>,
+    ) -> Result<ChannelEntity, anyhow::Error> {
+        self.seal(&mut channel, secrets)?;
+        self.repository.update(channel).await
+    }
+
+    /**
+     * 引用该渠道的订阅数量
+     */
+    pub async fn subscriptions(&self, id: i64) -> Result<i64, anyhow::Error> {
+        self.repository.count_subscriptions(id).await
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<ChannelEntity, anyhow::Error> {
+        self.repository.delete_by_id(id).await
+    }
+
+    /**
+     * 解密渠道的敏感字段
+     */
+    pub fn secrets(
+        &self,
+        channel: &ChannelEntity,
+    ) -> Result<BTreeMap<String, String>, anyhow::Error> {
+        let Some(secret) = &channel.secret else {
+            return Ok(BTreeMap::new());
+        };
+        let cipher = self
+            .cipher
+            .as_ref()
+            .ok_or_else(|| anyhow!("channel encryption key is not configured"))?;
+        let plaintext = cipher.decrypt(secret)?;
+        serde_json::from_str(&plaintext).context("Failed to parse channel secrets")
+    }
+
+    /**
+     * 通过渠道发送消息, 在 client span 内记录渠道和应答状态
+     */
+    pub async fn send(
+        &self,
+        channel: &ChannelEntity,
+        message: &ChannelMessage,
+    ) -> Result<ChannelResponse, anyhow::Error> {
+        let cx = telemetry::start_span(
+            format!("channel.send {}", channel.kind),
+            SpanKind::Client,
+            vec![
+                KeyValue::new("channel.id", channel.id.unwrap_or_default()),
+                KeyValue::new("channel.kind", channel.kind.to_string()),
+                KeyValue::new("project_id", channel.project_id.clone()),
+            ],
+        );
+        let result = async {
+            let secrets = self.secrets(channel)?;
+            self.client
+                .send(channel.kind, &channel.config.0, &secrets, message)
+                .await
+        }
+        .with_context(cx.clone())
+        .await;
+        match &result {
+            Ok(response) => {
+                cx.span().set_attributes([
+                    KeyValue::new("channel.response.status", response.status as i64),
+                    KeyValue::new("channel.success", response.success),
+                ]);
+                if !response.success {
+                    telemetry::record_error(&cx, format!("channel responded {}", response.status));
+                }
+            }
+            Err(err) => telemetry::record_error(&cx, err),
+        }
+        cx.span().end();
+        result
+    }
+
+    fn seal(
+        &self,
+        channel: &mut ChannelEntity,
+        secrets: &BTreeMap<String, String>,
+    ) -> Result<(), anyhow::Error> {
+        channel.secret_fields = Json(secrets.keys().cloned().collect());
+        channel.secret = if secrets.is_empty() {
+            None
+        } else {
+            let cipher = self
+                .cipher
+                .as_ref()
+                .ok_or_else(|| anyhow!("channel encryption key is not configured"))?;
+            Some(cipher.encrypt(&serde_json::to_string(secrets)?)?)
+        };
+        Ok(())
+    }
+}
@@ -0,0 +1,62 @@
+use aes_gcm::aead::{Aead, AeadCore, KeyInit, OsRng};
+use aes_gcm::{Aes256Gcm, Key, Nonce};
+use anyhow::{Context, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+
+/// AES-GCM nonce 长度
+const NONCE_LEN: usize = 12;
+
+/**
+ * 敏感字段加密, 密文格式为 base64(nonce || ciphertext)
+ */
+#[derive(Clone)]
+pub struct SecretCipher {
+    cipher: Aes256Gcm,
+}
+
+impl SecretCipher {
+    /**
+     * key 为 base64 编码的 32 字节密钥
+     */
+    pub fn new(key: &str) -> Result<Self, anyhow::Error> {
+        let key = STANDARD
+            .decode(key.trim())
+            .context("encryption key is not valid base64")?;
+        if key.len() != 32 {
+            return Err(anyhow!(
+                "encryption key must be 32 bytes, got {} bytes",
+                key.len()
+            ));
+        }
+        Ok(Self {
+            cipher: Aes256Gcm::new(Key::<Aes256Gcm>::from_slice(&key)),
+        })
+    }
+
+    pub fn encrypt(&self, plaintext: &str) -> Result<String, anyhow::Error> {
+        let nonce = Aes256Gcm::generate_nonce(&mut OsRng);
+        let ciphertext = self
+            .cipher
+            .encrypt(&nonce, plaintext.as_bytes())
+            .map_err(|_| anyhow!("encrypt secret failed"))?;
+        let mut data = nonce.to_vec();
+        data.extend(ciphertext);
+        Ok(STANDARD.encode(data))
+    }
+
+    pub fn decrypt(&self, encoded: &str) -> Result<String, anyhow::Error> {
+        let data = STANDARD
+            .decode(encoded)
+            .context("secret is not valid base64")?;
+        if data.len() < NONCE_LEN {
+            return Err(anyhow!("secret is too short"));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let plaintext = self
+            .cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| anyhow!("decrypt secret failed, is the encryption key changed?"))?;
+        String::from_utf8(plaintext).context("secret is not valid utf-8")
+    }
+}
@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use serde_json::Value;
+use std::collections::VecDeque;
+use std::sync::{Arc, Mutex};
+use tokio::sync::broadcast;
+
+/// 订阅者未及时消费时可积压的事件数
+const CHANNEL_CAPACITY: usize = 256;
+
+/**
+ * 系统内部事件, 推送给实时订阅方
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct Event {
+    /// 单调递增, 以启动时间(微秒)为起点, 重启后仍大于之前的编号
+    pub id: u64,
+    /// 事件类型, 如 build.started, delivery.failed
+    pub kind: String,
+    pub project_id: String,
+    pub pipeline_id: Option<String>,
+    pub data: Value,
+    pub time: DateTime<Utc>,
+}
+
+struct Buffer {
+    next_id: u64,
+    events: VecDeque<Arc<Event>>,
+}
+
+/**
+ * 进程内事件总线, 保留最近的事件用于断线续传
+ */
+#[derive(Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<Arc<Event>>,
+    buffer: Arc<Mutex<Buffer>>,
+    capacity: usize,
+}
+
+impl EventBus {
+    pub fn new(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(CHANNEL_CAPACITY);
+        Self {
+            sender,
+            buffer: Arc::new(Mutex::new(Buffer {
+                next_id: Utc::now().timestamp_micros() as u64,
+                events: VecDeque::with_capacity(capacity),
+            })),
+            capacity,
+        }
+    }
+
+    pub fn publish(&self, kind: &str, project_id: &str, pipeline_id: Option<&str>, data: Value) {
+        let mut buffer = self.buffer.lock().unwrap_or_else(|err| err.into_inner());
+        let event = Arc::new(Event {
+            id: buffer.next_id,
+            kind: kind.to_string(),
+            project_id: project_id.to_string(),
+            pipeline_id: pipeline_id.map(str::to_string),
+            data,
+            time: Utc::now(),
+        });
+        buffer.next_id += 1;
+        if self.capacity > 0 {
+            if buffer.events.len() >= self.capacity {
+                buffer.events.pop_front();
+            }
+            buffer.events.push_back(event.clone());
+        }
+        // 持有锁时发送, 保证订阅时的补发与实时事件之间没有遗漏或重复
+        let _ = self.sender.send(event);
+    }
+
+    /**
+     * 订阅实时事件, 同时返回缓冲区中编号大于 last_id 的事件
+     */
+    pub fn subscribe(
+        &self,
+        last_id: Option<u64>,
+    ) -> (Vec<Arc<Event>>, broadcast::Receiver<Arc<Event>>) {
+        let buffer = self.buffer.lock().unwrap_or_else(|err| err.into_inner());
+        let receiver = self.sender.subscribe();
+        let missed = match last_id {
+            Some(last_id) => buffer
+                .events
+                .iter()
+                .filter(|event| event.id > last_id)
+                .cloned()
+                .collect(),
+            None => vec![],
+        };
+        (missed, receiver)
+    }
+}
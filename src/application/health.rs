@@ -0,0 +1,250 @@
+use crate::application::supervisor::{ServiceState, Supervisor};
+use crate::conf::HealthOptions;
+use crate::devops::DevOpsClients;
+use crate::repository::sqlite::{DeliveryRepository, HealthRepository};
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use serde::Serialize;
+use serde_json::{Value, json};
+use std::collections::{BTreeMap, HashMap};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::Mutex;
+use utoipa::ToSchema;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, ToSchema)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Ok,
+    /// 非关键依赖异常, 仍可提供服务
+    Degraded,
+    Unavailable,
+}
+
+/**
+ * 单项检查结果
+ */
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct CheckResult {
+    pub status: HealthStatus,
+    pub message: Option<String>,
+    pub latency_ms: i64,
+    #[schema(value_type = Option<Object>)]
+    pub details: Option<Value>,
+    pub checked_at: DateTime<Utc>,
+}
+
+impl CheckResult {
+    fn new(
+        started: Instant,
+        result: Result<Option<Value>, anyhow::Error>,
+        failure: HealthStatus,
+    ) -> Self {
+        let (status, message, details) = match result {
+            Ok(details) => (HealthStatus::Ok, None, details),
+            Err(err) => (failure, Some(format!("{:#}", err)), None),
+        };
+        Self {
+            status,
+            message,
+            latency_ms: started.elapsed().as_millis() as i64,
+            details,
+            checked_at: Utc::now(),
+        }
+    }
+}
+
+/**
+ * 就绪检查报告
+ */
+#[derive(Debug, Clone, Serialize, ToSchema)]
+pub struct HealthReport {
+    pub status: HealthStatus,
+    /// 正在停止, 不再接收新流量
+    pub draining: bool,
+    pub checks: BTreeMap<String, CheckResult>,
+}
+
+/**
+ * 依赖检查: 数据库、迁移和关键后台服务为关键依赖, DevOps、通知渠道和其他后台服务异常只降级
+ */
+#[derive(Clone)]
+pub struct HealthService {
+    repository: HealthRepository,
+    deliveries: DeliveryRepository,
+    clients: DevOpsClients,
+    supervisor: Supervisor,
+    options: HealthOptions,
+    /// 各实例最近一次检查结果
+    devops: Arc<Mutex<HashMap<String, CheckResult>>>,
+    draining: Arc<AtomicBool>,
+}
+
+impl HealthService {
+    pub fn new(
+        repository: HealthRepository,
+        deliveries: DeliveryRepository,
+        clients: DevOpsClients,
+        supervisor: Supervisor,
+        options: HealthOptions,
+    ) -> Self {
+        Self {
+            repository,
+            deliveries,
+            clients,
+            supervisor,
+            options,
+            devops: Arc::new(Mutex::new(HashMap::new())),
+            draining: Arc::new(AtomicBool::new(false)),
+        }
+    }
+
+    /**
+     * 开始停止服务, 之后就绪检查始终失败
+     */
+    pub fn drain(&self) {
+        self.draining.store(true, Ordering::SeqCst);
+    }
+
+    pub fn is_draining(&self) -> bool {
+        self.draining.load(Ordering::SeqCst)
+    }
+
+    pub async fn check(&self) -> HealthReport {
+        if self.is_draining() {
+            return HealthReport {
+                status: HealthStatus::Unavailable,
+                draining: true,
+                checks: BTreeMap::new(),
+            };
+        }
+        let (database, migrations, devops, channels) = tokio::join!(
+            self.check_database(),
+            self.check_migrations(),
+            self.check_devops(),
+            self.check_channels(),
+        );
+        let mut checks = BTreeMap::from([
+            ("database".to_string(), database),
+            ("migrations".to_string(), migrations),
+            ("channels".to_string(), channels),
+        ]);
+        checks.extend(devops);
+        checks.extend(self.check_services());
+        let status = checks
+            .values()
+            .map(|check| check.status)
+            .max()
+            .unwrap_or(HealthStatus::Ok);
+        HealthReport {
+            status,
+            draining: false,
+            checks,
+        }
+    }
+
+    async fn check_database(&self) -> CheckResult {
+        let started = Instant::now();
+        let result = self.repository.ping().await.map(|_| None);
+        CheckResult::new(started, result, HealthStatus::Unavailable)
+    }
+
+    async fn check_migrations(&self) -> CheckResult {
+        let started = Instant::now();
+        let result = self
+            .repository
+            .pending_migrations()
+            .await
+            .and_then(|pending| {
+                if pending.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(anyhow::anyhow!("pending migrations {:?}", pending))
+                }
+            });
+        CheckResult::new(started, result, HealthStatus::Unavailable)
+    }
+
+    /**
+     * 逐个检查 DevOps 实例, 结果缓存一段时间, 避免每次探测都请求 DevOps
+     */
+    async fn check_devops(&self) -> Vec<(String, CheckResult)> {
+        let mut cached = self.devops.lock().await;
+        let ttl = ChronoDuration::seconds(*self.options.devops_check_ttl() as i64);
+        let timeout = Duration::from_secs(*self.options.devops_check_timeout());
+        let mut checks = Vec::with_capacity(self.clients.all().len());
+        for client in self.clients.all() {
+            let key = format!("devops.{}", client.name());
+            if let Some(check) = cached.get(&key)
+                && Utc::now() - check.checked_at < ttl
+            {
+                checks.push((key, check.clone()));
+                continue;
+            }
+            let started = Instant::now();
+            let result = client
+                .ping(timeout)
+                .await
+                .map(|status| Some(json!({ "http_status": status })));
+            let check = CheckResult::new(started, result, HealthStatus::Degraded);
+            cached.insert(key.clone(), check.clone());
+            checks.push((key, check));
+        }
+        checks
+    }
+
+    /**
+     * 后台服务的运行状态, 关键服务不在运行时不可用
+     */
+    fn check_services(&self) -> Vec<(String, CheckResult)> {
+        self.supervisor
+            .statuses()
+            .into_iter()
+            .map(|(name, (status, health))| {
+                let failure = if status.critical {
+                    HealthStatus::Unavailable
+                } else {
+                    HealthStatus::Degraded
+                };
+                let (status_code, message) = match (status.state, health) {
+                    (ServiceState::Running, Ok(())) => (HealthStatus::Ok, None),
+                    (ServiceState::Running, Err(err)) => {
+                        (HealthStatus::Degraded, Some(format!("{:#}", err)))
+                    }
+                    (_, _) => (failure, status.last_error.clone()),
+                };
+                let check = CheckResult {
+                    status: status_code,
+                    message,
+                    latency_ms: 0,
+                    details: Some(json!({
+                        "state": status.state,
+                        "restarts": status.restarts,
+                    })),
+                    checked_at: Utc::now(),
+                };
+                (format!("service.{}", name), check)
+            })
+            .collect()
+    }
+
+    async fn check_channels(&self) -> CheckResult {
+        let started = Instant::now();
+        let since = Utc::now() - ChronoDuration::seconds(*self.options.channel_window() as i64);
+        let result = self
+            .deliveries
+            .find_failing_channels(since)
+            .await
+            .and_then(|failing| {
+                if failing.is_empty() {
+                    Ok(None)
+                } else {
+                    Err(anyhow::anyhow!(
+                        "latest deliveries to channels {:?} failed",
+                        failing
+                    ))
+                }
+            });
+        CheckResult::new(started, result, HealthStatus::Degraded)
+    }
+}
@@ -0,0 +1,248 @@
+use crate::application::event::EventBus;
+use crate::application::supervisor::ManagedService;
+use crate::conf::HistoryOptions;
+use crate::devops::BuildInfo;
+use crate::repository::DatabaseRepository;
+use crate::repository::entity::{BuildEntity, BuildFilter, BuildStage};
+use crate::repository::sqlite::BuildRepository;
+use chrono::{DateTime, Duration as ChronoDuration, Utc};
+use log::{debug, error, info};
+use serde_json::json;
+use sqlx::types::Json;
+use std::time::Duration;
+use tokio::select;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/**
+ * 构建历史: 记录观测到的构建, 按保留策略清理并聚合
+ */
+#[derive(Clone)]
+pub struct BuildHistoryService {
+    repository: BuildRepository,
+    options: HistoryOptions,
+    events: EventBus,
+}
+
+impl BuildHistoryService {
+    pub fn new(repository: BuildRepository, options: HistoryOptions, events: EventBus) -> Self {
+        Self {
+            repository,
+            options,
+            events,
+        }
+    }
+
+    /**
+     * 保存流水线的构建记录, 已超出保留策略的构建不再写入,
+     * 避免清理后被重新拉取并重复聚合
+     */
+    pub async fn record(
+        &self,
+        source: &str,
+        project_id: &str,
+        pipeline_id: &str,
+        builds: Vec<BuildInfo>,
+    ) -> Result<usize, anyhow::Error> {
+        let retention = self.options.retention();
+        let cutoff = retention
+            .days()
+            .map(|days| Utc::now() - ChronoDuration::days(days as i64));
+        let min_build_num = match retention.builds() {
+            Some(keep) => {
+                let stored = self
+                    .repository
+                    .latest_build_num(source, project_id, pipeline_id)
+                    .await?;
+                let observed = builds.iter().map(|build| build.build_num).max();
+                stored.max(observed).map(|latest| latest - *keep as i32)
+            }
+            None => None,
+        };
+
+        let mut saved = 0;
+        for build in builds {
+            let entity = Self::to_entity(source, project_id, pipeline_id, build);
+            if cutoff.is_some_and(|cutoff| entity.start_time < cutoff)
+                || min_build_num.is_some_and(|min| entity.build_num <= min)
+            {
+                continue;
+            }
+            let previous = self
+                .repository
+                .find_by_key(source, project_id, pipeline_id, &entity.build_id)
+                .await?
+                .map(|build| build.status);
+            let build = self.repository.save_or_update(entity).await?;
+            saved += 1;
+            // 新构建或状态变化时推送事件
+            if previous.as_deref() != Some(build.status.as_str()) {
+                self.publish(&build, previous);
+            }
+        }
+        Ok(saved)
+    }
+
+    pub async fn find_builds(
+        &self,
+        filter: &BuildFilter,
+    ) -> Result<Vec<BuildEntity>, anyhow::Error> {
+        self.repository.find_by(filter).await
+    }
+
+    /**
+     * 按构建 id 查找, 未指定实例时可能匹配多个实例的构建
+     */
+    pub async fn find_build(
+        &self,
+        source: Option<&str>,
+        build_id: &str,
+    ) -> Result<Vec<BuildEntity>, anyhow::Error> {
+        self.repository.find_by_build_id(source, build_id).await
+    }
+
+    /**
+     * 先聚合已结束的构建, 再按保留策略清理原始记录
+     */
+    pub async fn compact(&self) -> Result<(), anyhow::Error> {
+        let aggregated = self.repository.aggregate_daily().await?;
+        let retention = self.options.retention();
+        let mut purged = 0;
+        if let Some(days) = retention.days() {
+            let cutoff = Utc::now() - ChronoDuration::days(*days as i64);
+            purged += self.repository.purge_before(cutoff).await?;
+        }
+        if let Some(builds) = retention.builds() {
+            purged += self.repository.purge_exceeding(*builds).await?;
+        }
+        info!(
+            "build history compacted, aggregated {} builds, purged {} builds",
+            aggregated, purged
+        );
+        Ok(())
+    }
+
+    fn publish(&self, build: &BuildEntity, previous_status: Option<String>) {
+        let kind = if previous_status.is_none() {
+            "build.created"
+        } else {
+            "build.updated"
+        };
+        debug!(
+            build_id = build.build_id.as_str(), build_num = build.build_num;
+            "{} {:?} -> {}", kind, previous_status, build.status
+        );
+        self.events.publish(
+            kind,
+            &build.project_id,
+            Some(&build.pipeline_id),
+            json!({
+                "source": build.source,
+                "build_id": build.build_id,
+                "build_num": build.build_num,
+                "status": build.status,
+                "previous_status": previous_status,
+                "trigger": build.trigger,
+                "start_user": build.start_user,
+                "start_time": build.start_time,
+                "end_time": build.end_time,
+                "duration": build.duration,
+            }),
+        );
+    }
+
+    fn to_entity(
+        source: &str,
+        project_id: &str,
+        pipeline_id: &str,
+        build: BuildInfo,
+    ) -> BuildEntity {
+        let now = Utc::now();
+        let start_time = DateTime::from_timestamp_millis(build.start_time).unwrap_or(now);
+        let end_time = build.end_time.and_then(DateTime::from_timestamp_millis);
+        let duration = end_time.map(|end_time| (end_time - start_time).num_seconds());
+        let stages = build
+            .stage_status
+            .unwrap_or_default()
+            .into_iter()
+            .map(|stage| BuildStage {
+                stage_id: stage.stage_id,
+                name: stage.name,
+                status: stage.status,
+                elapsed: stage.elapsed,
+            })
+            .collect();
+        BuildEntity {
+            id: None,
+            project_id: project_id.to_string(),
+            pipeline_id: pipeline_id.to_string(),
+            build_id: build.id,
+            build_num: build.build_num,
+            status: build.status,
+            trigger: build.trigger,
+            start_user: build.user_id,
+            start_time,
+            end_time,
+            duration,
+            stages: Json(stages),
+            aggregated: false,
+            created_at: now,
+            updated_at: now,
+            source: source.to_string(),
+        }
+    }
+}
+
+/**
+ * 定时执行构建历史压缩
+ */
+pub struct HistoryCompactor {
+    cancel_token: CancellationToken,
+    history: BuildHistoryService,
+    interval: Duration,
+}
+
+impl HistoryCompactor {
+    pub fn new(token: CancellationToken, history: BuildHistoryService, interval: u64) -> Self {
+        Self {
+            cancel_token: token,
+            history,
+            interval: Duration::from_secs(interval),
+        }
+    }
+}
+
+impl ManagedService for HistoryCompactor {
+    fn name(&self) -> String {
+        "compactor".to_string()
+    }
+
+    fn start(&self) -> Result<JoinHandle<Result<(), anyhow::Error>>, anyhow::Error> {
+        info!("starting history compactor");
+        let token = self.cancel_token.clone();
+        let history = self.history.clone();
+        let mut ticker = tokio::time::interval(self.interval);
+        Ok(tokio::spawn(async move {
+            loop {
+                select! {
+                    _ = token.cancelled() => {
+                        info!("received shutdown history compactor signal");
+                        break;
+                    },
+                    _ = ticker.tick() => {
+                        if let Err(err) = history.compact().await {
+                            error!("compact build history failed. {:?}", err);
+                        }
+                    },
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn stop(&self) -> Result<(), anyhow::Error> {
+        info!("Stopping HistoryCompactor");
+        self.cancel_token.cancel();
+        Ok(())
+    }
+}
@@ -1,18 +1,12 @@
-pub trait PipelineService {
-    /**
-     * 获取项目下所有流水线
-     */
-    fn get_project_pipelines(&self, project_id: &str) -> Result<Vec<String>, anyhow::Error> {
-        Err(anyhow::anyhow!("Not implemented"))
-    }
-}
-
-pub struct BKDevOpsService {}
-
-impl BKDevOpsService {
-    pub fn new() -> Self {
-        BKDevOpsService {}
-    }
-}
-
-impl PipelineService for BKDevOpsService {}
+pub mod access;
+pub mod api_key;
+pub mod channel;
+pub mod crypto;
+pub mod event;
+pub mod health;
+pub mod history;
+pub mod notification;
+pub mod pipeline;
+pub mod poller;
+pub mod subscription;
+pub mod supervisor;
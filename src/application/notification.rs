@@ -0,0 +1,487 @@
+use crate::application::channel::ChannelService;
+use crate::application::event::EventBus;
+use crate::application::supervisor::ManagedService;
+use crate::channel::{ChannelMessage, ChannelResponse};
+use crate::conf::{DeliveryOptions, NotificationOptions};
+use crate::context;
+use crate::repository::DatabaseRepository;
+use crate::repository::entity::{
+    DeliveryAttemptEntity, DeliveryAttemptFilter, DeliveryEntity, DeliveryStatus, DeliveryTarget,
+    NotificationEntity,
+};
+use crate::repository::sqlite::{DeliveryRepository, NotificationRepository};
+use crate::telemetry;
+use anyhow::anyhow;
+use chrono::{Duration as ChronoDuration, Utc};
+use log::{debug, error, info, warn};
+use opentelemetry::KeyValue;
+use opentelemetry::trace::SpanKind;
+use serde_json::json;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::select;
+use tokio::sync::Notify;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/// 重试等待上限(秒)
+const MAX_RETRY_DELAY: u64 = 3600;
+/// 审计记录中保留的响应体长度
+const EXCERPT_LEN: usize = 512;
+
+/**
+ * 通知发件箱: 写入通知及各渠道的投递记录, 由投递任务异步发送
+ */
+#[derive(Clone)]
+pub struct NotificationService {
+    notifications: NotificationRepository,
+    deliveries: DeliveryRepository,
+    templates: Arc<RwLock<HashMap<String, String>>>,
+    wakeup: Arc<Notify>,
+    events: EventBus,
+}
+
+impl NotificationService {
+    pub fn new(
+        notifications: NotificationRepository,
+        deliveries: DeliveryRepository,
+        options: &NotificationOptions,
+        wakeup: Arc<Notify>,
+        events: EventBus,
+    ) -> Self {
+        Self {
+            notifications,
+            deliveries,
+            templates: Arc::new(RwLock::new(options.templates().clone())),
+            wakeup,
+            events,
+        }
+    }
+
+    pub fn has_template(&self, template_id: &str) -> bool {
+        self.templates
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .contains_key(template_id)
+    }
+
+    /**
+     * 替换通知模板, 对之后写入的通知生效
+     */
+    pub fn set_templates(&self, templates: HashMap<String, String>) {
+        info!("notification templates changed to {:?}", templates.keys());
+        *self
+            .templates
+            .write()
+            .unwrap_or_else(|err| err.into_inner()) = templates;
+    }
+
+    /**
+     * 按模板渲染内容后写入发件箱, 并唤醒投递任务
+     */
+    pub async fn enqueue(
+        &self,
+        mut notification: NotificationEntity,
+        targets: &[DeliveryTarget],
+    ) -> Result<(NotificationEntity, Vec<DeliveryEntity>), anyhow::Error> {
+        if let Some(template_id) = &notification.template_id {
+            let template = self
+                .templates
+                .read()
+                .unwrap_or_else(|err| err.into_inner())
+                .get(template_id)
+                .cloned()
+                .ok_or_else(|| anyhow!("template {} not found", template_id))?;
+            notification.content = render(&template, |name| match name {
+                "title" => Some(notification.title.clone()),
+                "severity" => Some(notification.severity.to_string()),
+                "project_id" => Some(notification.project_id.clone()),
+                "content" => Some(notification.content.clone()),
+                _ => None,
+            });
+        }
+        let (notification, deliveries) = self.notifications.enqueue(notification, targets).await?;
+        self.wakeup.notify_one();
+        self.events.publish(
+            "notification.created",
+            &notification.project_id,
+            notification.pipeline_id.as_deref(),
+            json!({
+                "notification_id": notification.id,
+                "title": notification.title,
+                "severity": notification.severity,
+                "source": notification.source,
+                "build_id": notification.build_id,
+                "channel_ids": deliveries.iter().map(|delivery| delivery.channel_id).collect::<Vec<_>>(),
+            }),
+        );
+        Ok((notification, deliveries))
+    }
+
+    pub async fn get(
+        &self,
+        id: i64,
+    ) -> Result<Option<(NotificationEntity, Vec<DeliveryEntity>)>, anyhow::Error> {
+        let Some(notification) = self.notifications.find_by_id(id).await? else {
+            return Ok(None);
+        };
+        let deliveries = self.deliveries.find_by_notification(id).await?;
+        Ok(Some((notification, deliveries)))
+    }
+
+    /**
+     * 投递尝试审计记录, 返回当前页及总数
+     */
+    pub async fn attempts(
+        &self,
+        filter: &DeliveryAttemptFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<(Vec<DeliveryAttemptEntity>, i64), anyhow::Error> {
+        let attempts = self.deliveries.find_attempts(filter, offset, limit).await?;
+        let total = self.deliveries.count_attempts(filter).await?;
+        Ok((attempts, total))
+    }
+}
+
+/**
+ * 投递任务: 定时或被唤醒时发送到期的投递, 失败后按指数退避重试
+ */
+#[derive(Clone)]
+pub struct DeliveryWorker {
+    cancel_token: CancellationToken,
+    /// 停机时触发, 发送完已到期的投递后退出
+    drain_token: CancellationToken,
+    notifications: NotificationRepository,
+    deliveries: DeliveryRepository,
+    channels: ChannelService,
+    options: Arc<RwLock<DeliveryOptions>>,
+    wakeup: Arc<Notify>,
+    events: EventBus,
+}
+
+impl DeliveryWorker {
+    pub fn new(
+        token: CancellationToken,
+        notifications: NotificationRepository,
+        deliveries: DeliveryRepository,
+        channels: ChannelService,
+        options: DeliveryOptions,
+        wakeup: Arc<Notify>,
+        events: EventBus,
+    ) -> Self {
+        Self {
+            cancel_token: token,
+            drain_token: CancellationToken::new(),
+            notifications,
+            deliveries,
+            channels,
+            options: Arc::new(RwLock::new(options)),
+            wakeup,
+            events,
+        }
+    }
+
+    pub fn options(&self) -> DeliveryOptions {
+        self.options
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /**
+     * 更新批量大小和重试策略, 轮询间隔需要重启后生效
+     */
+    pub fn set_options(&self, options: DeliveryOptions) {
+        info!("delivery options changed to {:?}", options);
+        *self.options.write().unwrap_or_else(|err| err.into_inner()) = options;
+    }
+
+    async fn deliver_due(&self) {
+        loop {
+            let batch = match self
+                .deliveries
+                .claim_due(Utc::now(), *self.options().batch_size())
+                .await
+            {
+                Ok(batch) => batch,
+                Err(err) => {
+                    error!("claim due deliveries failed. {:?}", err);
+                    return;
+                }
+            };
+            let claimed = batch.len();
+            for delivery in batch {
+                let id = delivery.id;
+                let fields = vec![
+                    ("delivery_id", id.unwrap_or_default().to_string()),
+                    ("notification_id", delivery.notification_id.to_string()),
+                    ("channel_id", delivery.channel_id.to_string()),
+                ];
+                let attributes = vec![
+                    KeyValue::new("delivery.id", id.unwrap_or_default()),
+                    KeyValue::new("notification.id", delivery.notification_id),
+                    KeyValue::new("channel.id", delivery.channel_id),
+                    KeyValue::new("delivery.attempt", delivery.attempts as i64),
+                ];
+                context::with_log_fields(fields, async {
+                    let delivered = self.deliver(delivery);
+                    if let Err(err) =
+                        telemetry::in_span("deliver", SpanKind::Internal, attributes, delivered)
+                            .await
+                    {
+                        error!("update delivery {:?} failed. {:?}", id, err);
+                    }
+                })
+                .await;
+            }
+            if claimed < *self.options().batch_size() as usize || self.cancel_token.is_cancelled() {
+                return;
+            }
+        }
+    }
+
+    async fn deliver(&self, delivery: DeliveryEntity) -> Result<(), anyhow::Error> {
+        let notification = self
+            .notifications
+            .find_by_id(delivery.notification_id)
+            .await?
+            .ok_or_else(|| anyhow!("notification {} not found", delivery.notification_id))?;
+        let mut fields = vec![("project_id", notification.project_id.clone())];
+        if let Some(pipeline_id) = &notification.pipeline_id {
+            fields.push(("pipeline_id", pipeline_id.clone()));
+        }
+        if let Some(build_id) = &notification.build_id {
+            fields.push(("build_id", build_id.clone()));
+        }
+        context::with_log_fields(fields, self.attempt(delivery, notification)).await
+    }
+
+    /**
+     * 发送一次并记录结果, 失败时按策略安排重试
+     */
+    async fn attempt(
+        &self,
+        mut delivery: DeliveryEntity,
+        notification: NotificationEntity,
+    ) -> Result<(), anyhow::Error> {
+        let message = ChannelMessage {
+            title: notification.title.clone(),
+            content: notification.content.clone(),
+        };
+        let started = Instant::now();
+        let result = self.send(delivery.channel_id, &message).await;
+        let latency = started.elapsed();
+
+        let now = Utc::now();
+        delivery.updated_at = now;
+        match result {
+            Ok(response) if response.success => {
+                delivery.status = DeliveryStatus::Succeed;
+                delivery.last_error = None;
+                delivery.delivered_at = Some(now);
+                delivery.response_status = Some(response.status as i32);
+                delivery.response_body = Some(response.body);
+            }
+            Ok(response) => {
+                delivery.response_status = Some(response.status as i32);
+                delivery.last_error = Some(format!("channel responded {}", response.status));
+                delivery.response_body = Some(response.body);
+                self.retry_or_fail(&mut delivery);
+            }
+            Err(err) => {
+                delivery.response_status = None;
+                delivery.response_body = None;
+                delivery.last_error = Some(format!("{:#}", err));
+                self.retry_or_fail(&mut delivery);
+            }
+        }
+        debug!(
+            "delivery {:?} of notification {} to channel {} is {:?}",
+            delivery.id, delivery.notification_id, delivery.channel_id, delivery.status
+        );
+        let attempt = DeliveryAttemptEntity {
+            id: None,
+            delivery_id: delivery.id.unwrap_or_default(),
+            notification_id: delivery.notification_id,
+            project_id: notification.project_id,
+            pipeline_id: notification.pipeline_id,
+            build_id: notification.build_id,
+            source: notification.source,
+            event: notification.event,
+            subscription_id: delivery.subscription_id,
+            channel_id: delivery.channel_id,
+            attempt: delivery.attempts,
+            status: match delivery.status {
+                DeliveryStatus::Succeed => DeliveryStatus::Succeed,
+                _ => DeliveryStatus::Failed,
+            },
+            payload_hash: payload_hash(&message),
+            response_status: delivery.response_status,
+            response_excerpt: delivery.response_body.as_deref().map(excerpt),
+            error: delivery.last_error.clone(),
+            latency_ms: latency.as_millis() as i64,
+            attempted_at: now,
+        };
+        let attempt = self.deliveries.record_attempt(attempt).await?;
+        let delivery = self.deliveries.update(delivery).await?;
+        let kind = match delivery.status {
+            DeliveryStatus::Succeed => "delivery.succeed",
+            DeliveryStatus::Failed => "delivery.failed",
+            _ => "delivery.retrying",
+        };
+        self.events.publish(
+            kind,
+            &attempt.project_id,
+            attempt.pipeline_id.as_deref(),
+            json!({
+                "delivery_id": delivery.id,
+                "notification_id": delivery.notification_id,
+                "channel_id": delivery.channel_id,
+                "attempt": attempt.attempt,
+                "status": delivery.status,
+                "error": delivery.last_error,
+                "next_attempt_at": (delivery.status == DeliveryStatus::Pending)
+                    .then_some(delivery.next_attempt_at),
+            }),
+        );
+        Ok(())
+    }
+
+    async fn send(
+        &self,
+        channel_id: i64,
+        message: &ChannelMessage,
+    ) -> Result<ChannelResponse, anyhow::Error> {
+        let channel = self
+            .channels
+            .get(channel_id)
+            .await?
+            .ok_or_else(|| anyhow!("channel {} not found", channel_id))?;
+        self.channels.send(&channel, message).await
+    }
+
+    fn retry_or_fail(&self, delivery: &mut DeliveryEntity) {
+        if delivery.attempts >= *self.options().max_attempts() {
+            delivery.status = DeliveryStatus::Failed;
+            return;
+        }
+        let exponent = (delivery.attempts.max(1) - 1).min(16) as u32;
+        let delay = self
+            .options()
+            .retry_delay()
+            .saturating_mul(2u64.pow(exponent))
+            .min(MAX_RETRY_DELAY);
+        delivery.status = DeliveryStatus::Pending;
+        delivery.next_attempt_at = Utc::now() + ChronoDuration::seconds(delay as i64);
+    }
+
+    /**
+     * 中止时已领取但未完成的投递恢复为待发送, 不必等到下次启动
+     */
+    pub async fn requeue(&self) -> Result<(), anyhow::Error> {
+        let requeued = self.deliveries.reset_sending().await?;
+        if requeued > 0 {
+            warn!("requeued {} in-flight deliveries", requeued);
+        }
+        Ok(())
+    }
+}
+
+impl ManagedService for DeliveryWorker {
+    fn name(&self) -> String {
+        "delivery".to_string()
+    }
+
+    fn start(&self) -> Result<JoinHandle<Result<(), anyhow::Error>>, anyhow::Error> {
+        info!("starting delivery worker");
+        let token = self.cancel_token.clone();
+        let drain = self.drain_token.clone();
+        let worker = self.clone();
+        let mut ticker = tokio::time::interval(Duration::from_secs(*self.options().interval()));
+        Ok(tokio::spawn(async move {
+            // 上次退出时未完成的投递重新排队
+            match worker.deliveries.reset_sending().await {
+                Ok(0) => {}
+                Ok(reset) => warn!("requeued {} interrupted deliveries", reset),
+                Err(err) => error!("requeue interrupted deliveries failed. {:?}", err),
+            }
+            loop {
+                select! {
+                    biased;
+                    _ = token.cancelled() => {
+                        info!("received shutdown delivery worker signal");
+                        break;
+                    },
+                    _ = drain.cancelled() => {
+                        info!("flushing due deliveries before shutdown");
+                        worker.deliver_due().await;
+                        break;
+                    },
+                    _ = ticker.tick() => worker.deliver_due().await,
+                    _ = worker.wakeup.notified() => worker.deliver_due().await,
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn stop(&self) -> Result<(), anyhow::Error> {
+        info!("Stopping DeliveryWorker");
+        self.cancel_token.cancel();
+        Ok(())
+    }
+
+    /**
+     * 不再等待下一轮, 发送完已到期的投递后退出
+     */
+    fn drain(&self) -> Result<(), anyhow::Error> {
+        info!("Draining DeliveryWorker");
+        self.drain_token.cancel();
+        Ok(())
+    }
+}
+
+/**
+ * 渲染后消息的 sha256, 用于核对实际发出的内容
+ */
+fn payload_hash(message: &ChannelMessage) -> String {
+    let mut hasher = Sha256::new();
+    hasher.update(message.title.as_bytes());
+    hasher.update(b"\n");
+    hasher.update(message.content.as_bytes());
+    hex::encode(hasher.finalize())
+}
+
+/**
+ * 截取渠道响应的开头部分, 审计记录和测试消息使用同样的长度
+ */
+pub fn excerpt(body: &str) -> String {
+    body.chars().take(EXCERPT_LEN).collect()
+}
+
+/**
+ * 单次扫描替换 {{name}} 占位符, 替换进来的值不会再被当作模板解析;
+ * 未知的占位符原样保留
+ */
+fn render(template: &str, value: impl Fn(&str) -> Option<String>) -> String {
+    let mut rendered = String::with_capacity(template.len());
+    let mut rest = template;
+    while let Some(start) = rest.find("{{") {
+        rendered.push_str(&rest[..start]);
+        rest = &rest[start + 2..];
+        let Some((end, replacement)) = rest
+            .find("}}")
+            .and_then(|end| value(&rest[..end]).map(|replacement| (end, replacement)))
+        else {
+            rendered.push_str("{{");
+            continue;
+        };
+        rendered.push_str(&replacement);
+        rest = &rest[end + 2..];
+    }
+    rendered.push_str(rest);
+    rendered
+}
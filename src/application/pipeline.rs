@@ -0,0 +1,158 @@
+use crate::application::history::BuildHistoryService;
+use crate::devops::{DevOpsApiClient, DevOpsClients, PipelineInfo};
+use crate::repository::DatabaseRepository;
+use crate::repository::entity::PipelineEntity;
+use crate::repository::sqlite::PipelineRepository;
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use std::sync::Arc;
+
+/**
+ * 流水线查询, 数据来自本地缓存, 需要时从 DevOps 实时刷新
+ */
+#[derive(Clone)]
+pub struct PipelineService {
+    repository: PipelineRepository,
+    history: BuildHistoryService,
+    clients: DevOpsClients,
+}
+
+impl PipelineService {
+    pub fn new(
+        repository: PipelineRepository,
+        history: BuildHistoryService,
+        clients: DevOpsClients,
+    ) -> Self {
+        Self {
+            repository,
+            history,
+            clients,
+        }
+    }
+
+    /**
+     * 已配置的 DevOps 实例名称
+     */
+    pub fn sources(&self) -> Vec<String> {
+        self.clients
+            .all()
+            .iter()
+            .map(|client| client.name().to_string())
+            .collect()
+    }
+
+    /**
+     * 项目所属的 DevOps 实例: 指定时直接使用, 否则取已缓存流水线的实例或配置了该项目的实例
+     */
+    pub async fn source_of(
+        &self,
+        source: Option<&str>,
+        project_id: &str,
+    ) -> Result<Option<String>, anyhow::Error> {
+        if source.is_none()
+            && let Some(pipeline) = self.repository.find_by_project(project_id).await?.first()
+        {
+            return Ok(Some(pipeline.source.clone()));
+        }
+        Ok(self
+            .clients
+            .resolve(source, project_id)
+            .map(|client| client.name().to_string()))
+    }
+
+    fn client(&self, source: &str) -> Result<Arc<DevOpsApiClient>, anyhow::Error> {
+        self.clients
+            .get(source)
+            .ok_or_else(|| anyhow!("devops source {} is not configured", source))
+    }
+
+    pub async fn list(&self, project_id: &str) -> Result<Vec<PipelineEntity>, anyhow::Error> {
+        self.repository.find_by_project(project_id).await
+    }
+
+    /**
+     * 按流水线 id 查找, 未指定实例时可能匹配多个实例的流水线
+     */
+    pub async fn get(
+        &self,
+        source: Option<&str>,
+        pipeline_id: &str,
+    ) -> Result<Vec<PipelineEntity>, anyhow::Error> {
+        self.repository
+            .find_by_pipeline_id(source, pipeline_id)
+            .await
+    }
+
+    /**
+     * 从 DevOps 实例拉取项目的流水线并更新缓存, 已删除的流水线同时移除
+     */
+    pub async fn refresh_pipelines(
+        &self,
+        source: &str,
+        project_id: &str,
+    ) -> Result<Vec<PipelineEntity>, anyhow::Error> {
+        let pipelines = self
+            .client(source)?
+            .get_project_pipelines(project_id.to_string())
+            .await?;
+        let mut saved = Vec::with_capacity(pipelines.len());
+        for pipeline in pipelines.into_iter().filter(|pipeline| !pipeline.delete) {
+            let entity = Self::to_entity(source, project_id, pipeline);
+            saved.push(self.repository.save_or_update(entity).await?);
+        }
+        let pipeline_ids: Vec<String> = saved
+            .iter()
+            .map(|pipeline| pipeline.pipeline_id.clone())
+            .collect();
+        self.repository
+            .delete_missing(source, project_id, &pipeline_ids)
+            .await?;
+        Ok(saved)
+    }
+
+    /**
+     * 从 DevOps 拉取流水线的构建并写入构建历史
+     */
+    pub async fn refresh_builds(
+        &self,
+        source: &str,
+        project_id: &str,
+        pipeline_id: &str,
+    ) -> Result<usize, anyhow::Error> {
+        let builds = self
+            .client(source)?
+            .get_pipeline_builds(project_id.to_string(), pipeline_id.to_string())
+            .await?;
+        self.history
+            .record(source, project_id, pipeline_id, builds)
+            .await
+    }
+
+    fn to_entity(source: &str, project_id: &str, pipeline: PipelineInfo) -> PipelineEntity {
+        let now = Utc::now();
+        // DevOps 以 0 表示没有构建
+        let timestamp = |millis: i64| {
+            (millis > 0)
+                .then_some(millis)
+                .and_then(DateTime::from_timestamp_millis)
+        };
+        let has_build = pipeline.latest_build_num > 0;
+        PipelineEntity {
+            id: None,
+            project_id: project_id.to_string(),
+            pipeline_id: pipeline.pipeline_id,
+            name: pipeline.pipeline_name,
+            description: pipeline.pipeline_desc,
+            creator: pipeline.creator,
+            latest_build_num: has_build.then_some(pipeline.latest_build_num),
+            latest_build_status: pipeline.latest_build_status,
+            latest_build_user: has_build.then_some(pipeline.latest_build_user_id),
+            latest_build_start_time: timestamp(pipeline.latest_build_start_time),
+            latest_build_end_time: timestamp(pipeline.latest_build_end_time),
+            running_build_count: pipeline.running_build_count,
+            created_at: now,
+            updated_at: now,
+            source: source.to_string(),
+        }
+    }
+}
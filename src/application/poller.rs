@@ -0,0 +1,143 @@
+use crate::application::pipeline::PipelineService;
+use crate::application::supervisor::ManagedService;
+use crate::context;
+use crate::telemetry;
+use log::{debug, error, info};
+use opentelemetry::KeyValue;
+use opentelemetry::context::FutureExt;
+use opentelemetry::trace::{SpanKind, TraceContextExt};
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::select;
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+
+/**
+ * 定时从一个 DevOps 实例拉取项目下流水线的构建
+ */
+pub struct BuildPoller {
+    cancel_token: CancellationToken,
+    pipelines: PipelineService,
+    source: String,
+    projects: Arc<RwLock<Vec<String>>>,
+    interval: Duration,
+}
+
+impl BuildPoller {
+    pub fn new(
+        token: CancellationToken,
+        pipelines: PipelineService,
+        source: String,
+        projects: Vec<String>,
+        interval: u64,
+    ) -> Self {
+        Self {
+            cancel_token: token,
+            pipelines,
+            source,
+            projects: Arc::new(RwLock::new(projects)),
+            interval: Duration::from_secs(interval),
+        }
+    }
+
+    pub fn source(&self) -> &str {
+        &self.source
+    }
+
+    pub fn projects(&self) -> Vec<String> {
+        self.projects
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /**
+     * 更新拉取的项目, 下一轮生效
+     */
+    pub fn set_projects(&self, projects: Vec<String>) {
+        info!(
+            "build poller of {} projects changed to {:?}",
+            self.source, projects
+        );
+        *self.projects.write().unwrap_or_else(|err| err.into_inner()) = projects;
+    }
+
+    async fn poll_project(pipelines: &PipelineService, source: &str, project_id: &str) {
+        let cached = match pipelines.refresh_pipelines(source, project_id).await {
+            Ok(cached) => cached,
+            Err(err) => {
+                error!(
+                    "poll project {} pipelines from {} failed. {:?}",
+                    project_id, source, err
+                );
+                return;
+            }
+        };
+        for pipeline in cached {
+            let fields = vec![("pipeline_id", pipeline.pipeline_id.clone())];
+            let refreshed = context::with_log_fields(
+                fields,
+                pipelines.refresh_builds(source, project_id, &pipeline.pipeline_id),
+            );
+            match refreshed.await {
+                Ok(saved) => debug!(
+                    "recorded {} builds of pipeline {}",
+                    saved, pipeline.pipeline_id
+                ),
+                Err(err) => error!(
+                    "poll pipeline {} builds failed. {:?}",
+                    pipeline.pipeline_id, err
+                ),
+            }
+        }
+    }
+}
+
+impl ManagedService for BuildPoller {
+    fn name(&self) -> String {
+        format!("poller.{}", self.source)
+    }
+
+    fn start(&self) -> Result<JoinHandle<Result<(), anyhow::Error>>, anyhow::Error> {
+        info!(
+            "starting build poller of {} for projects {:?}",
+            self.source,
+            self.projects()
+        );
+        let token = self.cancel_token.clone();
+        let pipelines = self.pipelines.clone();
+        let source = self.source.clone();
+        let projects = self.projects.clone();
+        let mut ticker = tokio::time::interval(self.interval);
+        Ok(tokio::spawn(async move {
+            loop {
+                select! {
+                    _ = token.cancelled() => {
+                        info!("received shutdown build poller signal");
+                        break;
+                    },
+                    _ = ticker.tick() => {
+                        let projects = projects.read().unwrap_or_else(|err| err.into_inner()).clone();
+                        for project_id in projects.iter() {
+                            let fields = vec![("source", source.clone()), ("project_id", project_id.clone())];
+                            let cx = telemetry::start_span("poll_project", SpanKind::Internal, vec![
+                                KeyValue::new("devops.source", source.clone()),
+                                KeyValue::new("project_id", project_id.clone()),
+                            ]);
+                            let polled = Self::poll_project(&pipelines, &source, project_id).with_context(cx.clone());
+                            context::with_log_fields(fields, polled).await;
+                            cx.span().end();
+                        }
+                    },
+                }
+            }
+            Ok(())
+        }))
+    }
+
+    fn stop(&self) -> Result<(), anyhow::Error> {
+        info!("Stopping BuildPoller");
+        self.cancel_token.cancel();
+        Ok(())
+    }
+}
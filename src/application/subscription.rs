@@ -0,0 +1,71 @@
+use crate::repository::DatabaseRepository;
+use crate::repository::entity::{DeliveryTarget, SubscriptionEntity, SubscriptionFilter};
+use crate::repository::sqlite::SubscriptionRepository;
+
+/// 可订阅的构建事件
+pub const EVENTS: &[&str] = &["started", "succeed", "failed", "canceled"];
+
+/**
+ * 通知订阅管理
+ */
+#[derive(Clone)]
+pub struct SubscriptionService {
+    repository: SubscriptionRepository,
+}
+
+impl SubscriptionService {
+    pub fn new(repository: SubscriptionRepository) -> Self {
+        Self { repository }
+    }
+
+    pub async fn create(
+        &self,
+        subscription: SubscriptionEntity,
+    ) -> Result<SubscriptionEntity, anyhow::Error> {
+        self.repository.save(subscription).await
+    }
+
+    pub async fn get(&self, id: i64) -> Result<Option<SubscriptionEntity>, anyhow::Error> {
+        self.repository.find_by_id(id).await
+    }
+
+    pub async fn list(
+        &self,
+        filter: &SubscriptionFilter,
+    ) -> Result<Vec<SubscriptionEntity>, anyhow::Error> {
+        self.repository.find_by(filter).await
+    }
+
+    pub async fn update(
+        &self,
+        subscription: SubscriptionEntity,
+    ) -> Result<SubscriptionEntity, anyhow::Error> {
+        self.repository.update(subscription).await
+    }
+
+    pub async fn set_enabled(
+        &self,
+        id: i64,
+        enabled: bool,
+    ) -> Result<Option<SubscriptionEntity>, anyhow::Error> {
+        self.repository.set_enabled(id, enabled).await
+    }
+
+    /**
+     * 项目内带有任一标签的启用订阅所使用的渠道, 跳过限定了其他实例的订阅
+     */
+    pub async fn targets_by_tags(
+        &self,
+        source: Option<&str>,
+        project_id: &str,
+        tags: &[String],
+    ) -> Result<Vec<DeliveryTarget>, anyhow::Error> {
+        self.repository
+            .find_targets_by_tags(source, project_id, tags)
+            .await
+    }
+
+    pub async fn delete(&self, id: i64) -> Result<SubscriptionEntity, anyhow::Error> {
+        self.repository.delete_by_id(id).await
+    }
+}
@@ -0,0 +1,343 @@
+use crate::conf::SupervisorOptions;
+use anyhow::anyhow;
+use log::{error, info, warn};
+use serde::Serialize;
+use std::any::Any;
+use std::collections::BTreeMap;
+use std::sync::{Arc, Mutex, RwLock};
+use std::time::{Duration, Instant};
+use tokio::select;
+use tokio::task::{AbortHandle, JoinHandle};
+use tokio_util::sync::{CancellationToken, WaitForCancellationFuture};
+
+/// 运行超过该时长后再失败, 重新计算连续重启次数
+const STABLE_AFTER: Duration = Duration::from_secs(300);
+
+/**
+ * 由 Supervisor 管理生命周期的后台服务
+ */
+pub trait ManagedService: Send + Sync {
+    /// 服务名, 用于日志和健康检查
+    fn name(&self) -> String;
+
+    /// 启动后台任务, 任务返回错误或 panic 视为失败
+    fn start(&self) -> Result<JoinHandle<Result<(), anyhow::Error>>, anyhow::Error>;
+
+    /// 通知后台任务尽快退出
+    fn stop(&self) -> Result<(), anyhow::Error>;
+
+    /// 停机时调用, 完成手上的工作后退出, 默认与 stop 相同
+    fn drain(&self) -> Result<(), anyhow::Error> {
+        self.stop()
+    }
+
+    /// 服务自身的健康状况, 默认只看任务是否在运行
+    fn health(&self) -> Result<(), anyhow::Error> {
+        Ok(())
+    }
+}
+
+/**
+ * 重启策略
+ */
+#[derive(Debug, Clone)]
+pub struct RestartPolicy {
+    /// 连续重启次数上限
+    pub max_restarts: u32,
+    pub delay: Duration,
+    pub max_delay: Duration,
+    /// 关键服务无法恢复时停止整个进程
+    pub critical: bool,
+}
+
+impl RestartPolicy {
+    pub fn new(options: &SupervisorOptions, critical: bool) -> Self {
+        Self {
+            max_restarts: *options.max_restarts(),
+            delay: Duration::from_secs(*options.restart_delay()),
+            max_delay: Duration::from_secs(*options.max_restart_delay()),
+            critical,
+        }
+    }
+
+    fn backoff(&self, restarts: u32) -> Duration {
+        let exponent = restarts.saturating_sub(1).min(16);
+        self.delay
+            .saturating_mul(2u32.pow(exponent))
+            .min(self.max_delay)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ServiceState {
+    Running,
+    /// 失败后等待重启
+    Restarting,
+    Stopped,
+    /// 重启次数用尽
+    Failed,
+}
+
+/**
+ * 服务运行状态
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct ServiceStatus {
+    pub state: ServiceState,
+    pub critical: bool,
+    /// 累计重启次数
+    pub restarts: u32,
+    pub last_error: Option<String>,
+}
+
+struct Supervised {
+    service: Arc<dyn ManagedService>,
+    policy: RestartPolicy,
+    status: RwLock<ServiceStatus>,
+    /// 主动停止后不再重启
+    stopping: CancellationToken,
+    task: Mutex<Option<AbortHandle>>,
+    monitor: Mutex<Option<JoinHandle<()>>>,
+}
+
+impl Supervised {
+    fn status(&self) -> ServiceStatus {
+        self.status
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    fn update(&self, update: impl FnOnce(&mut ServiceStatus)) {
+        update(&mut self.status.write().unwrap_or_else(|err| err.into_inner()));
+    }
+}
+
+/**
+ * 监督后台服务: 观察任务句柄, 失败后按策略退避重启, 关键服务无法恢复时通知进程退出
+ */
+#[derive(Clone, Default)]
+pub struct Supervisor {
+    services: Arc<RwLock<Vec<Arc<Supervised>>>>,
+    /// 关键服务失败
+    fatal: CancellationToken,
+    failure: Arc<Mutex<Option<String>>>,
+}
+
+impl Supervisor {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /**
+     * 启动服务并开始监督, 首次启动失败直接返回错误
+     */
+    pub fn spawn(
+        &self,
+        service: Arc<dyn ManagedService>,
+        policy: RestartPolicy,
+    ) -> Result<(), anyhow::Error> {
+        let name = service.name();
+        info!("starting service {}", name);
+        let handle = service.start()?;
+        let entry = Arc::new(Supervised {
+            service,
+            status: RwLock::new(ServiceStatus {
+                state: ServiceState::Running,
+                critical: policy.critical,
+                restarts: 0,
+                last_error: None,
+            }),
+            policy,
+            stopping: CancellationToken::new(),
+            task: Mutex::new(Some(handle.abort_handle())),
+            monitor: Mutex::new(None),
+        });
+        let monitor = tokio::spawn(self.clone().monitor(entry.clone(), handle));
+        *entry.monitor.lock().unwrap_or_else(|err| err.into_inner()) = Some(monitor);
+        self.services
+            .write()
+            .unwrap_or_else(|err| err.into_inner())
+            .push(entry);
+        Ok(())
+    }
+
+    async fn monitor(self, entry: Arc<Supervised>, handle: JoinHandle<Result<(), anyhow::Error>>) {
+        let name = entry.service.name();
+        let mut next: Result<_, anyhow::Error> = Ok(handle);
+        let mut restarts = 0;
+        let mut started = Instant::now();
+        loop {
+            let result = match next {
+                Ok(handle) => match handle.await {
+                    Ok(result) => result,
+                    // 停机超时被中止
+                    Err(err) if err.is_cancelled() => Ok(()),
+                    Err(err) => Err(anyhow!("panicked: {}", panic_message(err.into_panic()))),
+                },
+                Err(err) => Err(err.context("restart failed")),
+            };
+            let err = match result {
+                Ok(()) => {
+                    info!("service {} stopped", name);
+                    entry.update(|status| status.state = ServiceState::Stopped);
+                    return;
+                }
+                Err(err) if entry.stopping.is_cancelled() => {
+                    warn!("service {} stopped with error. {:#}", name, err);
+                    entry.update(|status| {
+                        status.state = ServiceState::Stopped;
+                        status.last_error = Some(format!("{:#}", err));
+                    });
+                    return;
+                }
+                Err(err) => err,
+            };
+            error!("service {} failed. {:#}", name, err);
+            if started.elapsed() >= STABLE_AFTER {
+                restarts = 0;
+            }
+            if restarts >= entry.policy.max_restarts {
+                entry.update(|status| {
+                    status.state = ServiceState::Failed;
+                    status.last_error = Some(format!("{:#}", err));
+                });
+                if entry.policy.critical {
+                    error!(
+                        "critical service {} failed after {} restarts, shutting down",
+                        name, restarts
+                    );
+                    self.failure
+                        .lock()
+                        .unwrap_or_else(|err| err.into_inner())
+                        .get_or_insert_with(|| format!("service {} failed. {:#}", name, err));
+                    self.fatal.cancel();
+                }
+                return;
+            }
+            restarts += 1;
+            let delay = entry.policy.backoff(restarts);
+            entry.update(|status| {
+                status.state = ServiceState::Restarting;
+                status.restarts += 1;
+                status.last_error = Some(format!("{:#}", err));
+            });
+            warn!(
+                "restarting service {} in {:?} ({}/{})",
+                name, delay, restarts, entry.policy.max_restarts
+            );
+            select! {
+                _ = entry.stopping.cancelled() => {
+                    entry.update(|status| status.state = ServiceState::Stopped);
+                    return;
+                },
+                _ = tokio::time::sleep(delay) => {},
+            }
+            started = Instant::now();
+            next = entry.service.start();
+            if let Ok(handle) = &next {
+                *entry.task.lock().unwrap_or_else(|err| err.into_inner()) =
+                    Some(handle.abort_handle());
+                entry.update(|status| status.state = ServiceState::Running);
+            }
+        }
+    }
+
+    /**
+     * 让服务完成手上的工作后退出, 超时则中止任务; 返回是否按时退出
+     */
+    pub async fn shutdown(&self, name: &str, timeout: Duration) -> Result<bool, anyhow::Error> {
+        let Some(entry) = self.find(name) else {
+            return Ok(true);
+        };
+        entry.stopping.cancel();
+        entry.service.drain()?;
+        let monitor = entry
+            .monitor
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take();
+        let Some(mut monitor) = monitor else {
+            return Ok(true);
+        };
+        if tokio::time::timeout(timeout, &mut monitor).await.is_ok() {
+            return Ok(true);
+        }
+        warn!("service {} not stopped in {:?}, aborting", name, timeout);
+        if let Some(task) = entry
+            .task
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .take()
+        {
+            task.abort();
+        }
+        let _ = monitor.await;
+        Ok(false)
+    }
+
+    /**
+     * 关键服务无法恢复时完成
+     */
+    pub fn failed(&self) -> WaitForCancellationFuture<'_> {
+        self.fatal.cancelled()
+    }
+
+    pub fn failure(&self) -> Option<String> {
+        self.failure
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
+    }
+
+    /**
+     * 按启动顺序列出服务名
+     */
+    pub fn names(&self) -> Vec<String> {
+        self.services
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .map(|entry| entry.service.name())
+            .collect()
+    }
+
+    /**
+     * 各服务的运行状态, 运行中的服务附带其自身的健康检查结果
+     */
+    pub fn statuses(&self) -> BTreeMap<String, (ServiceStatus, Result<(), anyhow::Error>)> {
+        self.services
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .map(|entry| {
+                let status = entry.status();
+                let health = match status.state {
+                    ServiceState::Running => entry.service.health(),
+                    _ => Ok(()),
+                };
+                (entry.service.name(), (status, health))
+            })
+            .collect()
+    }
+
+    fn find(&self, name: &str) -> Option<Arc<Supervised>> {
+        self.services
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .iter()
+            .find(|entry| entry.service.name() == name)
+            .cloned()
+    }
+}
+
+fn panic_message(payload: Box<dyn Any + Send>) -> String {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message.to_string()
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message.clone()
+    } else {
+        "unknown panic".to_string()
+    }
+}
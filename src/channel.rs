@@ -0,0 +1,387 @@
+use crate::repository::entity::ChannelKind;
+use anyhow::{Context, anyhow};
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use hmac::{Hmac, Mac};
+use lettre::message::header::ContentType;
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{AsyncSmtpTransport, AsyncTransport, Message, Tokio1Executor};
+use log::info;
+use serde::{Deserialize, Serialize};
+use serde_json::{Value, json};
+use sha2::Sha256;
+use std::collections::BTreeMap;
+use std::sync::{Arc, RwLock};
+
+const WECOM_BASE_URL: &str = "https://qyapi.weixin.qq.com/cgi-bin/webhook/send";
+const DINGTALK_BASE_URL: &str = "https://oapi.dingtalk.com/robot/send";
+/// 返回给调用方的响应体最大长度
+const MAX_BODY_LEN: usize = 4096;
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct WecomConfig {
+    /// 覆盖默认的 webhook 地址, 用于私有化部署, 须在 channels.allowed_base_urls 中
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub mentioned_list: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Default)]
+#[serde(deny_unknown_fields)]
+pub struct DingtalkConfig {
+    #[serde(default)]
+    pub base_url: Option<String>,
+    #[serde(default)]
+    pub at_mobiles: Vec<String>,
+}
+
+#[derive(Debug, Deserialize, Clone, Copy, Default, PartialEq, Eq)]
+#[serde(rename_all = "lowercase")]
+pub enum EmailTls {
+    #[default]
+    Starttls,
+    Tls,
+    None,
+}
+
+#[derive(Debug, Deserialize, Clone)]
+#[serde(deny_unknown_fields)]
+pub struct EmailConfig {
+    pub smtp_host: String,
+    #[serde(default = "default_smtp_port")]
+    pub smtp_port: u16,
+    #[serde(default)]
+    pub tls: EmailTls,
+    #[serde(default)]
+    pub username: Option<String>,
+    pub from: String,
+    pub to: Vec<String>,
+}
+
+fn default_smtp_port() -> u16 {
+    587
+}
+
+/**
+ * 渠道必填的敏感字段
+ */
+pub fn required_secrets(kind: ChannelKind) -> &'static [&'static str] {
+    match kind {
+        ChannelKind::Wecom => &["webhook_key"],
+        ChannelKind::Dingtalk => &["access_token"],
+        ChannelKind::Email => &[],
+    }
+}
+
+/**
+ * 渠道可选的敏感字段
+ */
+pub fn optional_secrets(kind: ChannelKind) -> &'static [&'static str] {
+    match kind {
+        ChannelKind::Wecom => &[],
+        ChannelKind::Dingtalk => &["sign_secret"],
+        ChannelKind::Email => &["password"],
+    }
+}
+
+/**
+ * 消息发往的目的地: webhook 地址或 SMTP 服务器, 变化时已保存的敏感字段不能沿用
+ */
+pub fn destination(kind: ChannelKind, config: &Value) -> Option<String> {
+    match kind {
+        ChannelKind::Wecom => serde_json::from_value::<WecomConfig>(config.clone())
+            .ok()
+            .map(|config| {
+                config
+                    .base_url
+                    .unwrap_or_else(|| WECOM_BASE_URL.to_string())
+            }),
+        ChannelKind::Dingtalk => serde_json::from_value::<DingtalkConfig>(config.clone())
+            .ok()
+            .map(|config| {
+                config
+                    .base_url
+                    .unwrap_or_else(|| DINGTALK_BASE_URL.to_string())
+            }),
+        ChannelKind::Email => serde_json::from_value::<EmailConfig>(config.clone())
+            .ok()
+            .map(|config| {
+                format!(
+                    "{:?}://{}:{}",
+                    config.tls, config.smtp_host, config.smtp_port
+                )
+            }),
+    }
+}
+
+/**
+ * 通知消息, content 为 markdown
+ */
+#[derive(Debug, Clone)]
+pub struct ChannelMessage {
+    pub title: String,
+    pub content: String,
+}
+
+/**
+ * 渠道原始响应
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct ChannelResponse {
+    pub success: bool,
+    /// HTTP 状态码或 SMTP 应答码
+    pub status: u16,
+    pub body: String,
+}
+
+impl ChannelResponse {
+    /**
+     * 机器人接口 HTTP 200 时以 errcode 判断是否成功
+     */
+    fn from_webhook(status: u16, body: String) -> Self {
+        let errcode = serde_json::from_str::<Value>(&body)
+            .ok()
+            .and_then(|value| value.get("errcode").and_then(Value::as_i64));
+        Self {
+            success: (200..300).contains(&status) && errcode.unwrap_or(0) == 0,
+            status,
+            body: truncate(body),
+        }
+    }
+}
+
+fn truncate(mut body: String) -> String {
+    if body.len() > MAX_BODY_LEN {
+        let mut end = MAX_BODY_LEN;
+        while !body.is_char_boundary(end) {
+            end -= 1;
+        }
+        body.truncate(end);
+    }
+    body
+}
+
+/**
+ * 通知渠道客户端
+ */
+#[derive(Clone)]
+pub struct ChannelClient {
+    client: reqwest::Client,
+    /// 允许渠道覆盖的 webhook 地址, 避免把密钥发往任意主机
+    allowed_base_urls: Arc<RwLock<Vec<String>>>,
+}
+
+impl ChannelClient {
+    pub fn new(allowed_base_urls: Vec<String>) -> Self {
+        Self {
+            client: reqwest::Client::new(),
+            allowed_base_urls: Arc::new(RwLock::new(allowed_base_urls)),
+        }
+    }
+
+    /**
+     * 替换允许的 webhook 地址, 对之后的校验和发送生效
+     */
+    pub fn set_allowed_base_urls(&self, allowed_base_urls: Vec<String>) {
+        info!(
+            "allowed channel base urls changed to {:?}",
+            allowed_base_urls
+        );
+        *self
+            .allowed_base_urls
+            .write()
+            .unwrap_or_else(|err| err.into_inner()) = allowed_base_urls;
+    }
+
+    /**
+     * 校验渠道的非敏感配置
+     */
+    pub fn validate_config(&self, kind: ChannelKind, config: &Value) -> Result<(), anyhow::Error> {
+        match kind {
+            ChannelKind::Wecom => {
+                let config = serde_json::from_value::<WecomConfig>(config.clone())?;
+                self.base_url(config.base_url.as_deref(), WECOM_BASE_URL)?;
+            }
+            ChannelKind::Dingtalk => {
+                let config = serde_json::from_value::<DingtalkConfig>(config.clone())?;
+                self.base_url(config.base_url.as_deref(), DINGTALK_BASE_URL)?;
+            }
+            ChannelKind::Email => {
+                let config = serde_json::from_value::<EmailConfig>(config.clone())?;
+                if config.to.is_empty() {
+                    return Err(anyhow!("to must not be empty"));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /**
+     * 渠道覆盖的地址须在允许列表中, 未覆盖时使用官方地址
+     */
+    fn base_url<'a>(
+        &self,
+        base_url: Option<&'a str>,
+        default: &'a str,
+    ) -> Result<&'a str, anyhow::Error> {
+        let Some(base_url) = base_url else {
+            return Ok(default);
+        };
+        let normalized = base_url.trim_end_matches('/');
+        if base_url == default
+            || self
+                .allowed_base_urls
+                .read()
+                .unwrap_or_else(|err| err.into_inner())
+                .iter()
+                .any(|allowed| allowed.trim_end_matches('/') == normalized)
+        {
+            Ok(base_url)
+        } else {
+            Err(anyhow!(
+                "base_url {} is not in channels.allowed_base_urls",
+                base_url
+            ))
+        }
+    }
+
+    pub async fn send(
+        &self,
+        kind: ChannelKind,
+        config: &Value,
+        secrets: &BTreeMap<String, String>,
+        message: &ChannelMessage,
+    ) -> Result<ChannelResponse, anyhow::Error> {
+        match kind {
+            ChannelKind::Wecom => {
+                let config = serde_json::from_value::<WecomConfig>(config.clone())?;
+                self.send_wecom(&config, secrets, message).await
+            }
+            ChannelKind::Dingtalk => {
+                let config = serde_json::from_value::<DingtalkConfig>(config.clone())?;
+                self.send_dingtalk(&config, secrets, message).await
+            }
+            ChannelKind::Email => {
+                let config = serde_json::from_value::<EmailConfig>(config.clone())?;
+                self.send_email(&config, secrets, message).await
+            }
+        }
+    }
+
+    async fn send_wecom(
+        &self,
+        config: &WecomConfig,
+        secrets: &BTreeMap<String, String>,
+        message: &ChannelMessage,
+    ) -> Result<ChannelResponse, anyhow::Error> {
+        let key = secrets
+            .get("webhook_key")
+            .ok_or_else(|| anyhow!("webhook_key is not configured"))?;
+        let mut content = format!("## {}\n{}", message.title, message.content);
+        for user in config.mentioned_list.iter() {
+            content.push_str(&format!("\n<@{}>", user));
+        }
+        let base_url = self.base_url(config.base_url.as_deref(), WECOM_BASE_URL)?;
+        let response = self
+            .client
+            .post(base_url)
+            .query(&[("key", key)])
+            .json(&json!({
+                "msgtype": "markdown",
+                "markdown": { "content": content },
+            }))
+            .send()
+            .await
+            // 地址中带有密钥, 不能出现在错误信息中
+            .map_err(reqwest::Error::without_url)
+            .context("send wecom message failed")?;
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        Ok(ChannelResponse::from_webhook(status, body))
+    }
+
+    async fn send_dingtalk(
+        &self,
+        config: &DingtalkConfig,
+        secrets: &BTreeMap<String, String>,
+        message: &ChannelMessage,
+    ) -> Result<ChannelResponse, anyhow::Error> {
+        let token = secrets
+            .get("access_token")
+            .ok_or_else(|| anyhow!("access_token is not configured"))?;
+        let mut query = vec![("access_token", token.clone())];
+        // 加签: HmacSHA256("{timestamp}\n{secret}")
+        if let Some(secret) = secrets.get("sign_secret") {
+            let timestamp = chrono::Utc::now().timestamp_millis().to_string();
+            let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes())?;
+            mac.update(format!("{}\n{}", timestamp, secret).as_bytes());
+            let sign = STANDARD.encode(mac.finalize().into_bytes());
+            query.push(("timestamp", timestamp));
+            query.push(("sign", sign));
+        }
+        let base_url = self.base_url(config.base_url.as_deref(), DINGTALK_BASE_URL)?;
+        let response = self
+            .client
+            .post(base_url)
+            .query(&query)
+            .json(&json!({
+                "msgtype": "markdown",
+                "markdown": {
+                    "title": message.title,
+                    "text": format!("## {}\n{}", message.title, message.content),
+                },
+                "at": { "atMobiles": config.at_mobiles },
+            }))
+            .send()
+            .await
+            // 地址中带有密钥, 不能出现在错误信息中
+            .map_err(reqwest::Error::without_url)
+            .context("send dingtalk message failed")?;
+        let status = response.status().as_u16();
+        let body = response.text().await.unwrap_or_default();
+        Ok(ChannelResponse::from_webhook(status, body))
+    }
+
+    async fn send_email(
+        &self,
+        config: &EmailConfig,
+        secrets: &BTreeMap<String, String>,
+        message: &ChannelMessage,
+    ) -> Result<ChannelResponse, anyhow::Error> {
+        let mut builder = Message::builder()
+            .from(config.from.parse().context("invalid from address")?)
+            .subject(message.title.clone())
+            .header(ContentType::TEXT_PLAIN);
+        for to in config.to.iter() {
+            builder = builder.to(to.parse().context("invalid to address")?);
+        }
+        let email = builder.body(message.content.clone())?;
+
+        let mut transport = match config.tls {
+            EmailTls::Tls => AsyncSmtpTransport::<Tokio1Executor>::relay(&config.smtp_host)?,
+            EmailTls::Starttls => {
+                AsyncSmtpTransport::<Tokio1Executor>::starttls_relay(&config.smtp_host)?
+            }
+            EmailTls::None => {
+                AsyncSmtpTransport::<Tokio1Executor>::builder_dangerous(&config.smtp_host)
+            }
+        }
+        .port(config.smtp_port);
+        if let Some(username) = &config.username {
+            let password = secrets.get("password").cloned().unwrap_or_default();
+            transport = transport.credentials(Credentials::new(username.clone(), password));
+        }
+        let response = transport
+            .build()
+            .send(email)
+            .await
+            .context("send email failed")?;
+        Ok(ChannelResponse {
+            success: response.is_positive(),
+            status: response.code().to_string().parse().unwrap_or_default(),
+            body: truncate(response.message().collect::<Vec<_>>().join("\n")),
+        })
+    }
+}
@@ -0,0 +1,557 @@
+use crate::api;
+use crate::application::channel::ChannelService;
+use crate::application::subscription::{EVENTS, SubscriptionService};
+use crate::channel::{ChannelClient, ChannelMessage};
+use crate::conf::{Secret, Settings};
+use crate::devops::DevOpsClients;
+use crate::repository;
+use crate::repository::entity::{ChannelEntity, SubscriptionEntity, SubscriptionFilter};
+use crate::repository::sqlite::{ChannelRepository, MigrationRepository, SubscriptionRepository};
+use anyhow::anyhow;
+use chrono::{DateTime, Utc};
+use clap::{Args, Subcommand, ValueEnum};
+use serde::Serialize;
+use serde_json::Value;
+use sqlx::SqlitePool;
+use sqlx::types::Json;
+use std::io::{ErrorKind, Write};
+
+/**
+ * 输出格式, json 便于脚本处理
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, ValueEnum)]
+pub enum OutputFormat {
+    Table,
+    Json,
+}
+
+#[derive(Args, Debug)]
+pub struct CommandArgs {
+    /// Configuration file path
+    #[arg(short, long, default_value = "conf/settings.toml")]
+    pub path: String,
+    /// Output format
+    #[arg(short, long, value_enum, default_value_t = OutputFormat::Table)]
+    pub output: OutputFormat,
+}
+
+#[derive(Subcommand)]
+pub enum MigrateCommands {
+    /// Apply pending database migrations
+    Run(CommandArgs),
+    /// Show applied and pending database migrations
+    Status(CommandArgs),
+}
+
+#[derive(Subcommand)]
+pub enum PipelineCommands {
+    /// List pipelines of a project directly from DevOps
+    List(PipelineListArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct PipelineListArgs {
+    #[command(flatten)]
+    pub common: CommandArgs,
+    /// DevOps project code
+    #[arg(long)]
+    pub project: String,
+    /// DevOps source, defaults to the source configured with the project
+    #[arg(long)]
+    pub source: Option<String>,
+}
+
+#[derive(Subcommand)]
+pub enum SubscriptionCommands {
+    /// List subscriptions
+    List(SubscriptionListArgs),
+    /// Create a subscription
+    Add(SubscriptionAddArgs),
+    /// Remove a subscription
+    Remove(IdArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SubscriptionListArgs {
+    #[command(flatten)]
+    pub common: CommandArgs,
+    #[arg(long)]
+    pub project: Option<String>,
+    #[arg(long)]
+    pub source: Option<String>,
+}
+
+#[derive(Args, Debug)]
+pub struct SubscriptionAddArgs {
+    #[command(flatten)]
+    pub common: CommandArgs,
+    #[arg(long)]
+    pub name: String,
+    #[arg(long)]
+    pub project: String,
+    /// Subscribe to all pipelines of the project when omitted
+    #[arg(long)]
+    pub pipeline: Option<String>,
+    /// Build events, comma separated: started,succeed,failed,canceled
+    #[arg(long, value_delimiter = ',', required = true)]
+    pub events: Vec<String>,
+    #[arg(long)]
+    pub channel: i64,
+    #[arg(long, default_value = "cli")]
+    pub owner: String,
+    /// Tags, comma separated
+    #[arg(long, value_delimiter = ',')]
+    pub tags: Vec<String>,
+    /// Restrict to a DevOps source
+    #[arg(long)]
+    pub source: Option<String>,
+    /// Create the subscription disabled
+    #[arg(long)]
+    pub disabled: bool,
+}
+
+#[derive(Args, Debug)]
+pub struct IdArgs {
+    #[command(flatten)]
+    pub common: CommandArgs,
+    pub id: i64,
+}
+
+#[derive(Subcommand)]
+pub enum ChannelCommands {
+    /// Send a test message through a channel
+    Test(IdArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct SendArgs {
+    #[command(flatten)]
+    pub common: CommandArgs,
+    /// Channel id
+    #[arg(long)]
+    pub channel: i64,
+    /// Markdown content
+    #[arg(long)]
+    pub message: String,
+    #[arg(long, default_value = "Notification")]
+    pub title: String,
+}
+
+/**
+ * 写到标准输出, 下游管道提前关闭(如 | head)时正常结束
+ */
+fn emit(text: &str) -> Result<(), anyhow::Error> {
+    let mut stdout = std::io::stdout().lock();
+    match writeln!(stdout, "{}", text).and_then(|_| stdout.flush()) {
+        Err(err) if err.kind() != ErrorKind::BrokenPipe => Err(err.into()),
+        _ => Ok(()),
+    }
+}
+
+/**
+ * 按格式输出记录, 表格只展示指定的列
+ */
+fn print_rows<T: Serialize>(
+    output: OutputFormat,
+    rows: &[T],
+    columns: &[&str],
+) -> Result<(), anyhow::Error> {
+    if output == OutputFormat::Json {
+        return emit(&serde_json::to_string_pretty(rows)?);
+    }
+    let cell = |value: Option<&Value>| match value {
+        None | Some(Value::Null) => "-".to_string(),
+        Some(Value::String(value)) => value.clone(),
+        Some(Value::Array(values)) => values
+            .iter()
+            .map(|value| value.as_str().map_or(value.to_string(), str::to_string))
+            .collect::<Vec<_>>()
+            .join(","),
+        Some(value) => value.to_string(),
+    };
+    let cells: Vec<Vec<String>> = rows
+        .iter()
+        .map(|row| {
+            let row = serde_json::to_value(row)?;
+            Ok(columns.iter().map(|column| cell(row.get(column))).collect())
+        })
+        .collect::<Result<_, anyhow::Error>>()?;
+    let widths: Vec<usize> = columns
+        .iter()
+        .enumerate()
+        .map(|(index, column)| {
+            cells
+                .iter()
+                .map(|row| row[index].chars().count())
+                .chain([column.len()])
+                .max()
+                .unwrap_or_default()
+        })
+        .collect();
+    let line = |values: Vec<String>| {
+        values
+            .iter()
+            .zip(&widths)
+            .map(|(value, width)| format!("{:<width$}", value, width = width))
+            .collect::<Vec<_>>()
+            .join("  ")
+            .trim_end()
+            .to_string()
+    };
+    let mut lines = vec![line(
+        columns.iter().map(|column| column.to_uppercase()).collect(),
+    )];
+    lines.extend(cells.into_iter().map(line));
+    emit(&lines.join("\n"))
+}
+
+fn print_one<T: Serialize>(
+    output: OutputFormat,
+    row: &T,
+    columns: &[&str],
+) -> Result<(), anyhow::Error> {
+    match output {
+        OutputFormat::Json => emit(&serde_json::to_string_pretty(row)?),
+        OutputFormat::Table => print_rows(output, std::slice::from_ref(row), columns),
+    }
+}
+
+fn load_settings(args: &CommandArgs) -> Result<Settings, anyhow::Error> {
+    let settings = Settings::new(args.path.clone())?;
+    settings.validate()?;
+    Ok(settings)
+}
+
+/**
+ * 连接数据库但不执行迁移, 结构变更只由 migrate run 完成; 有未执行的迁移时拒绝继续
+ */
+async fn connect(settings: &Settings) -> Result<SqlitePool, anyhow::Error> {
+    let database = settings.database();
+    let pool = repository::open(database.url().expose(), *database.pool_size()).await?;
+    let pending = MigrationRepository::new(pool.clone())
+        .status()
+        .await?
+        .into_iter()
+        .filter(|migration| migration.success != Some(true))
+        .map(|migration| migration.version)
+        .collect::<Vec<_>>();
+    if !pending.is_empty() {
+        return Err(anyhow!(
+            "database has pending migrations {:?}, run `migrate run` first",
+            pending
+        ));
+    }
+    Ok(pool)
+}
+
+fn channel_service(
+    settings: &Settings,
+    pool: &SqlitePool,
+) -> Result<ChannelService, anyhow::Error> {
+    ChannelService::new(
+        ChannelRepository::new(pool.clone()),
+        settings
+            .channels()
+            .encryption_key()
+            .as_ref()
+            .map(Secret::expose),
+        ChannelClient::new(settings.channels().allowed_base_urls().clone()),
+    )
+}
+
+async fn find_channel(channels: &ChannelService, id: i64) -> Result<ChannelEntity, anyhow::Error> {
+    channels
+        .get(id)
+        .await?
+        .ok_or_else(|| anyhow!("channel {} not found", id))
+}
+
+pub async fn migrate(command: MigrateCommands) -> Result<(), anyhow::Error> {
+    let (args, apply) = match &command {
+        MigrateCommands::Run(args) => (args, true),
+        MigrateCommands::Status(args) => (args, false),
+    };
+    let settings = load_settings(args)?;
+    let database = settings.database();
+    let pool = repository::open(database.url().expose(), *database.pool_size()).await?;
+    let migrations = MigrationRepository::new(pool);
+    if apply {
+        let applied = migrations.run().await?;
+        if args.output == OutputFormat::Table {
+            return emit(&format!(
+                "applied {} migration(s) {:?}",
+                applied.len(),
+                applied
+            ));
+        }
+    }
+    print_rows(
+        args.output,
+        &migrations.status().await?,
+        &["version", "description", "success", "installed_on"],
+    )
+}
+
+/**
+ * 直接从 DevOps 查询的流水线
+ */
+#[derive(Debug, Serialize)]
+struct PipelineRow {
+    source: String,
+    project_id: String,
+    pipeline_id: String,
+    name: String,
+    latest_build_num: i32,
+    latest_build_status: Option<String>,
+    running_build_count: i32,
+}
+
+pub async fn pipelines(command: PipelineCommands) -> Result<(), anyhow::Error> {
+    let PipelineCommands::List(args) = command;
+    let settings = load_settings(&args.common)?;
+    let clients = DevOpsClients::new(settings.devops());
+    let client = clients
+        .resolve(args.source.as_deref(), &args.project)
+        .ok_or_else(|| match &args.source {
+            Some(source) => anyhow!("devops source {} is not configured", source),
+            None => anyhow!(
+                "project {} is not configured in any devops source, specify --source",
+                args.project
+            ),
+        })?;
+    let rows: Vec<PipelineRow> = client
+        .get_project_pipelines(args.project.clone())
+        .await?
+        .into_iter()
+        .filter(|pipeline| !pipeline.delete)
+        .map(|pipeline| PipelineRow {
+            source: client.name().to_string(),
+            project_id: args.project.clone(),
+            pipeline_id: pipeline.pipeline_id,
+            name: pipeline.pipeline_name,
+            latest_build_num: pipeline.latest_build_num,
+            latest_build_status: pipeline.latest_build_status,
+            running_build_count: pipeline.running_build_count,
+        })
+        .collect();
+    print_rows(
+        args.common.output,
+        &rows,
+        &[
+            "pipeline_id",
+            "name",
+            "latest_build_num",
+            "latest_build_status",
+            "running_build_count",
+        ],
+    )
+}
+
+/**
+ * 订阅信息
+ */
+#[derive(Debug, Serialize)]
+struct SubscriptionRow {
+    id: i64,
+    name: String,
+    project_id: String,
+    pipeline_id: Option<String>,
+    events: Vec<String>,
+    channel_id: i64,
+    owner: String,
+    tags: Vec<String>,
+    enabled: bool,
+    source: Option<String>,
+    created_at: DateTime<Utc>,
+}
+
+impl From<SubscriptionEntity> for SubscriptionRow {
+    fn from(entity: SubscriptionEntity) -> Self {
+        Self {
+            id: entity.id.unwrap_or_default(),
+            name: entity.name,
+            project_id: entity.project_id,
+            pipeline_id: entity.pipeline_id,
+            events: entity.events.0,
+            channel_id: entity.channel_id,
+            owner: entity.owner,
+            tags: entity.tags.0,
+            enabled: entity.enabled,
+            source: entity.source,
+            created_at: entity.created_at,
+        }
+    }
+}
+
+const SUBSCRIPTION_COLUMNS: &[&str] = &[
+    "id",
+    "name",
+    "project_id",
+    "pipeline_id",
+    "events",
+    "channel_id",
+    "source",
+    "enabled",
+];
+
+pub async fn subscriptions(command: SubscriptionCommands) -> Result<(), anyhow::Error> {
+    match command {
+        SubscriptionCommands::List(args) => {
+            let settings = load_settings(&args.common)?;
+            let pool = connect(&settings).await?;
+            let filter = SubscriptionFilter {
+                project_id: args.project,
+                source: args.source,
+                ..Default::default()
+            };
+            let rows: Vec<SubscriptionRow> =
+                SubscriptionService::new(SubscriptionRepository::new(pool))
+                    .list(&filter)
+                    .await?
+                    .into_iter()
+                    .map(SubscriptionRow::from)
+                    .collect();
+            print_rows(args.common.output, &rows, SUBSCRIPTION_COLUMNS)
+        }
+        SubscriptionCommands::Add(args) => {
+            let settings = load_settings(&args.common)?;
+            if let Some(event) = args
+                .events
+                .iter()
+                .find(|event| !EVENTS.contains(&event.as_str()))
+            {
+                return Err(anyhow!(
+                    "unknown event {}, expected one of {:?}",
+                    event,
+                    EVENTS
+                ));
+            }
+            if let Some(source) = &args.source
+                && !settings
+                    .devops()
+                    .iter()
+                    .any(|devops| devops.name() == source)
+            {
+                return Err(anyhow!("devops source {} is not configured", source));
+            }
+            let pool = connect(&settings).await?;
+            let channel = find_channel(&channel_service(&settings, &pool)?, args.channel).await?;
+            if channel.project_id != args.project {
+                return Err(anyhow!(
+                    "channel {} belongs to project {}",
+                    channel.id.unwrap_or_default(),
+                    channel.project_id
+                ));
+            }
+            let now = Utc::now();
+            let entity = SubscriptionEntity {
+                id: None,
+                name: args.name,
+                project_id: args.project,
+                pipeline_id: args.pipeline,
+                events: Json(args.events),
+                channel_id: args.channel,
+                owner: args.owner,
+                tags: Json(args.tags),
+                enabled: !args.disabled,
+                created_at: now,
+                updated_at: now,
+                source: args.source,
+            };
+            let entity = SubscriptionService::new(SubscriptionRepository::new(pool))
+                .create(entity)
+                .await?;
+            print_one(
+                args.common.output,
+                &SubscriptionRow::from(entity),
+                SUBSCRIPTION_COLUMNS,
+            )
+        }
+        SubscriptionCommands::Remove(args) => {
+            let settings = load_settings(&args.common)?;
+            let subscriptions =
+                SubscriptionService::new(SubscriptionRepository::new(connect(&settings).await?));
+            if subscriptions.get(args.id).await?.is_none() {
+                return Err(anyhow!("subscription {} not found", args.id));
+            }
+            let entity = subscriptions.delete(args.id).await?;
+            print_one(
+                args.common.output,
+                &SubscriptionRow::from(entity),
+                SUBSCRIPTION_COLUMNS,
+            )
+        }
+    }
+}
+
+/**
+ * 渠道发送结果, 包含渠道的原始响应
+ */
+#[derive(Debug, Serialize)]
+struct SendResult {
+    channel_id: i64,
+    success: bool,
+    /// HTTP 状态码或 SMTP 应答码, 请求未送达时为空
+    status: Option<u16>,
+    body: String,
+}
+
+const SEND_COLUMNS: &[&str] = &["channel_id", "success", "status", "body"];
+
+/**
+ * 通过渠道直接发送, 不经过发件箱; 发送失败时返回错误以便脚本判断
+ */
+async fn send_message(
+    args: &CommandArgs,
+    channel_id: i64,
+    message: impl FnOnce(&ChannelEntity) -> ChannelMessage,
+) -> Result<(), anyhow::Error> {
+    let settings = load_settings(args)?;
+    let pool = connect(&settings).await?;
+    let channels = channel_service(&settings, &pool)?;
+    let channel = find_channel(&channels, channel_id).await?;
+    let result = match channels.send(&channel, &message(&channel)).await {
+        Ok(response) => SendResult {
+            channel_id,
+            success: response.success,
+            status: Some(response.status),
+            body: response.body,
+        },
+        Err(err) => SendResult {
+            channel_id,
+            success: false,
+            status: None,
+            body: format!("{:#}", err),
+        },
+    };
+    print_one(args.output, &result, SEND_COLUMNS)?;
+    if result.success {
+        Ok(())
+    } else {
+        Err(anyhow!("send to channel {} failed", channel_id))
+    }
+}
+
+pub async fn channels(command: ChannelCommands) -> Result<(), anyhow::Error> {
+    let ChannelCommands::Test(args) = command;
+    send_message(&args.common, args.id, |channel| ChannelMessage {
+        title: "Test notification".to_string(),
+        content: format!("This is a test message from channel **{}**.", channel.name),
+    })
+    .await
+}
+
+pub async fn send(args: SendArgs) -> Result<(), anyhow::Error> {
+    send_message(&args.common, args.channel, |_| ChannelMessage {
+        title: args.title,
+        content: args.message,
+    })
+    .await
+}
+
+/**
+ * 输出 OpenAPI 文档, 不需要配置文件
+ */
+pub fn openapi() -> Result<(), anyhow::Error> {
+    emit(&api::openapi().to_pretty_json()?)
+}
@@ -1,34 +1,499 @@
 use crate::api::ApiServiceArgs;
+use crate::devops::{ApiVersion, Endpoints};
+use crate::logging::LogFormat;
 use config::{Config, ConfigError, Environment, File};
 use getset::Getters;
-use serde::Deserialize;
+use serde::de::value::{MapAccessDeserializer, SeqAccessDeserializer};
+use serde::de::{MapAccess, SeqAccess, Visitor};
+use serde::{Deserialize, Deserializer};
+use std::collections::HashMap;
+use std::fmt::Formatter;
+
+mod secret;
+mod validate;
+
+pub use secret::Secret;
+pub use validate::ConfigIssues;
 
 #[allow(unused)]
-#[derive(Debug, Deserialize, Clone, Getters)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
 #[get = "pub"]
 pub struct DevOpsArgs {
+    /// 实例名称, 流水线和构建记录以此标记来源
+    #[serde(default = "default_source")]
+    name: String,
     #[serde(default)]
     base_url: String,
+    /// 支持 file:/path 或 env:NAME 引用
     #[serde(default)]
-    access_token: String,
+    access_token: Secret,
     #[serde(default)]
     user_id: String,
+    /// 需要轮询的项目
+    #[serde(default)]
+    projects: Vec<String>,
+    /// 轮询间隔(秒)
+    #[serde(default = "default_poll_interval")]
+    poll_interval: u64,
+    /// 是否从 DevOps 同步项目成员
+    #[serde(default)]
+    sync_members: bool,
+    /// 成员同步间隔(秒)
+    #[serde(default = "default_member_sync_interval")]
+    member_sync_interval: u64,
+    /// 每秒最多请求数, 未配置时不限制
+    #[serde(default)]
+    rate_limit: Option<u32>,
+    /// 开放接口版本: open_v1 或 apigw_v4
+    #[serde(default)]
+    api_version: ApiVersion,
+    /// 网关前缀, 拼接在 base_url 与接口路径之间
+    #[serde(default)]
+    path_prefix: String,
+    /// 覆盖单个接口的路径, 支持 {project_id} 占位符
+    #[serde(default)]
+    endpoints: Endpoints,
+    /// 连接失败或 5xx 时的重试次数
+    #[serde(default = "default_max_retries")]
+    max_retries: u32,
+}
+
+fn default_source() -> String {
+    "default".to_string()
+}
+
+/**
+ * 兼容单个 [devops] 表和多个 [[devops]] 表
+ */
+fn one_or_many<'de, D>(deserializer: D) -> Result<Vec<DevOpsArgs>, D::Error>
+where
+    D: Deserializer<'de>,
+{
+    // 按值的形态分派, 字段错误原样返回而不是被 untagged 吞掉
+    struct OneOrMany;
+
+    impl<'de> Visitor<'de> for OneOrMany {
+        type Value = Vec<DevOpsArgs>;
+
+        fn expecting(&self, formatter: &mut Formatter) -> std::fmt::Result {
+            formatter.write_str("a [devops] table or an array of [[devops]] tables")
+        }
+
+        fn visit_seq<A: SeqAccess<'de>>(self, seq: A) -> Result<Self::Value, A::Error> {
+            Vec::<DevOpsArgs>::deserialize(SeqAccessDeserializer::new(seq))
+        }
+
+        fn visit_map<A: MapAccess<'de>>(self, map: A) -> Result<Self::Value, A::Error> {
+            DevOpsArgs::deserialize(MapAccessDeserializer::new(map)).map(|source| vec![source])
+        }
+    }
+
+    deserializer.deserialize_any(OneOrMany)
+}
+
+fn default_poll_interval() -> u64 {
+    60
+}
+
+fn default_member_sync_interval() -> u64 {
+    3600
+}
+
+fn default_max_retries() -> u32 {
+    2
 }
 
 #[allow(unused)]
-#[derive(Debug, Deserialize, Clone, Getters)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
 #[get = "pub"]
 pub struct DataBaseOptions {
-    url: String,
+    /// 可能包含密码, 支持 file:/path 或 env:NAME 引用
+    url: Secret,
+    #[serde(default = "default_pool_size")]
+    pool_size: u32,
+}
+
+fn default_pool_size() -> u32 {
+    10
+}
+
+/**
+ * 构建历史保留策略, 未配置的条件不生效
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters, Default)]
+#[get = "pub"]
+pub struct RetentionPolicy {
+    /// 保留最近 N 天的构建
+    #[serde(default)]
+    days: Option<u32>,
+    /// 每条流水线保留最近 N 次构建
+    #[serde(default)]
+    builds: Option<u32>,
 }
 
 #[allow(unused)]
-#[derive(Debug, Deserialize, Clone, Getters)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct HistoryOptions {
+    #[serde(default)]
+    retention: RetentionPolicy,
+    /// 压缩任务执行间隔(秒)
+    #[serde(default = "default_compaction_interval")]
+    compaction_interval: u64,
+}
+
+fn default_compaction_interval() -> u64 {
+    3600
+}
+
+impl Default for HistoryOptions {
+    fn default() -> Self {
+        Self {
+            retention: RetentionPolicy::default(),
+            compaction_interval: default_compaction_interval(),
+        }
+    }
+}
+
+/**
+ * 通知渠道配置
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters, Default)]
+#[get = "pub"]
+pub struct ChannelOptions {
+    /// 渠道敏感字段加密密钥, base64 编码的 32 字节, 支持 file:/path 或 env:NAME 引用
+    #[serde(default)]
+    encryption_key: Option<Secret>,
+    /// 渠道可以使用的私有化 webhook 地址, 未列出的 base_url 会被拒绝
+    #[serde(default)]
+    allowed_base_urls: Vec<String>,
+}
+
+/**
+ * 通知投递配置
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct DeliveryOptions {
+    /// 扫描待投递记录的间隔(秒)
+    #[serde(default = "default_delivery_interval")]
+    interval: u64,
+    /// 每次扫描处理的最大记录数
+    #[serde(default = "default_batch_size")]
+    batch_size: u32,
+    /// 最大尝试次数, 超过后标记为失败
+    #[serde(default = "default_max_attempts")]
+    max_attempts: i32,
+    /// 首次重试等待(秒), 之后按指数退避, 最长一小时
+    #[serde(default = "default_retry_delay")]
+    retry_delay: u64,
+}
+
+fn default_delivery_interval() -> u64 {
+    5
+}
+
+fn default_batch_size() -> u32 {
+    50
+}
+
+fn default_max_attempts() -> i32 {
+    5
+}
+
+fn default_retry_delay() -> u64 {
+    30
+}
+
+impl Default for DeliveryOptions {
+    fn default() -> Self {
+        Self {
+            interval: default_delivery_interval(),
+            batch_size: default_batch_size(),
+            max_attempts: default_max_attempts(),
+            retry_delay: default_retry_delay(),
+        }
+    }
+}
+
+/**
+ * 通知配置
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters, Default)]
+#[get = "pub"]
+pub struct NotificationOptions {
+    /// 消息模板, 支持 {{title}} {{content}} {{severity}} {{project_id}} 占位符
+    #[serde(default)]
+    templates: HashMap<String, String>,
+    #[serde(default)]
+    delivery: DeliveryOptions,
+}
+
+/**
+ * 实时事件流配置
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct EventOptions {
+    /// 内存中保留的最近事件数, 用于断线续传
+    #[serde(default = "default_event_buffer_size")]
+    buffer_size: usize,
+    /// 心跳间隔(秒)
+    #[serde(default = "default_heartbeat")]
+    heartbeat: u64,
+    /// WebSocket 最大连接数
+    #[serde(default = "default_max_connections")]
+    max_connections: usize,
+    /// WebSocket 单个连接未确认的最大事件数, 达到后暂停推送
+    #[serde(default = "default_ack_window")]
+    ack_window: usize,
+}
+
+fn default_event_buffer_size() -> usize {
+    1024
+}
+
+fn default_heartbeat() -> u64 {
+    15
+}
+
+fn default_max_connections() -> usize {
+    256
+}
+
+fn default_ack_window() -> usize {
+    100
+}
+
+impl Default for EventOptions {
+    fn default() -> Self {
+        Self {
+            buffer_size: default_event_buffer_size(),
+            heartbeat: default_heartbeat(),
+            max_connections: default_max_connections(),
+            ack_window: default_ack_window(),
+        }
+    }
+}
+
+/**
+ * 健康检查配置
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct HealthOptions {
+    /// DevOps 连通性检查结果的缓存时间(秒)
+    #[serde(default = "default_devops_check_ttl")]
+    devops_check_ttl: u64,
+    /// DevOps 连通性检查超时(秒)
+    #[serde(default = "default_devops_check_timeout")]
+    devops_check_timeout: u64,
+    /// 统计渠道投递失败的时间窗口(秒)
+    #[serde(default = "default_channel_window")]
+    channel_window: u64,
+}
+
+fn default_devops_check_ttl() -> u64 {
+    30
+}
+
+fn default_devops_check_timeout() -> u64 {
+    3
+}
+
+fn default_channel_window() -> u64 {
+    900
+}
+
+impl Default for HealthOptions {
+    fn default() -> Self {
+        Self {
+            devops_check_ttl: default_devops_check_ttl(),
+            devops_check_timeout: default_devops_check_timeout(),
+            channel_window: default_channel_window(),
+        }
+    }
+}
+
+/**
+ * 日志配置
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct LogOptions {
+    /// 日志级别, 支持按模块设置, 例如 "info,vision_notification::api=debug,sqlx=warn"; RUST_LOG 优先
+    #[serde(default = "default_log_level")]
+    level: String,
+    /// 输出格式: pretty 或 json
+    #[serde(default)]
+    format: LogFormat,
+}
+
+fn default_log_level() -> String {
+    "info".to_string()
+}
+
+impl Default for LogOptions {
+    fn default() -> Self {
+        Self {
+            level: default_log_level(),
+            format: LogFormat::default(),
+        }
+    }
+}
+
+/**
+ * 优雅停机配置
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct ShutdownOptions {
+    /// 整个停机过程的最长时间(秒), 超时后中止剩余任务, 未完成的投递重新排队
+    #[serde(default = "default_shutdown_timeout")]
+    timeout: u64,
+}
+
+fn default_shutdown_timeout() -> u64 {
+    30
+}
+
+impl Default for ShutdownOptions {
+    fn default() -> Self {
+        Self {
+            timeout: default_shutdown_timeout(),
+        }
+    }
+}
+
+/**
+ * 后台服务失败后的重启策略
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct SupervisorOptions {
+    /// 连续重启次数上限, 超过后关键服务失败会停止进程, 0 表示不重启
+    #[serde(default = "default_max_restarts")]
+    max_restarts: u32,
+    /// 首次重启等待(秒), 之后每次翻倍
+    #[serde(default = "default_restart_delay")]
+    restart_delay: u64,
+    /// 重启等待上限(秒)
+    #[serde(default = "default_max_restart_delay")]
+    max_restart_delay: u64,
+}
+
+fn default_max_restarts() -> u32 {
+    5
+}
+
+fn default_restart_delay() -> u64 {
+    1
+}
+
+fn default_max_restart_delay() -> u64 {
+    60
+}
+
+impl Default for SupervisorOptions {
+    fn default() -> Self {
+        Self {
+            max_restarts: default_max_restarts(),
+            restart_delay: default_restart_delay(),
+            max_restart_delay: default_max_restart_delay(),
+        }
+    }
+}
+
+/**
+ * 链路追踪, 通过 OTLP/HTTP 导出到 collector
+ */
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
+#[get = "pub"]
+pub struct TelemetryOptions {
+    #[serde(default)]
+    enabled: bool,
+    /// collector 的 traces 接收地址
+    #[serde(default = "default_telemetry_endpoint")]
+    endpoint: String,
+    #[serde(default = "default_service_name")]
+    service_name: String,
+    /// 采样比例 0~1, 调用方已采样的请求始终采样
+    #[serde(default = "default_sample_ratio")]
+    sample_ratio: f64,
+    /// 导出超时(秒)
+    #[serde(default = "default_telemetry_timeout")]
+    timeout: u64,
+    /// 导出时附带的请求头, 例如 collector 的认证信息, 值支持 file:/path 或 env:NAME 引用
+    #[serde(default)]
+    headers: HashMap<String, Secret>,
+}
+
+fn default_telemetry_endpoint() -> String {
+    "http://127.0.0.1:4318/v1/traces".to_string()
+}
+
+fn default_service_name() -> String {
+    "vision-notification".to_string()
+}
+
+fn default_sample_ratio() -> f64 {
+    1.0
+}
+
+fn default_telemetry_timeout() -> u64 {
+    10
+}
+
+impl Default for TelemetryOptions {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            endpoint: default_telemetry_endpoint(),
+            service_name: default_service_name(),
+            sample_ratio: default_sample_ratio(),
+            timeout: default_telemetry_timeout(),
+            headers: HashMap::new(),
+        }
+    }
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, PartialEq, Getters)]
 #[get = "pub"]
 pub struct Settings {
-    devops: DevOpsArgs,
+    /// DevOps 实例, 每个实例有独立的地址、凭证和项目
+    #[serde(deserialize_with = "one_or_many")]
+    devops: Vec<DevOpsArgs>,
     database: DataBaseOptions,
     api: ApiServiceArgs,
+    #[serde(default)]
+    history: HistoryOptions,
+    #[serde(default)]
+    channels: ChannelOptions,
+    #[serde(default)]
+    notifications: NotificationOptions,
+    #[serde(default)]
+    events: EventOptions,
+    #[serde(default)]
+    health: HealthOptions,
+    #[serde(default)]
+    log: LogOptions,
+    #[serde(default)]
+    shutdown: ShutdownOptions,
+    #[serde(default)]
+    supervisor: SupervisorOptions,
+    #[serde(default)]
+    telemetry: TelemetryOptions,
 }
 
 impl Settings {
@@ -41,4 +506,173 @@ impl Settings {
 
         settings.try_deserialize()
     }
+
+    /**
+     * 运行时可直接生效的配置项, 其余变更需要重启
+     */
+    pub fn is_reloadable(key: &str) -> bool {
+        matches!(
+            key,
+            "devops.projects"
+                | "api.rate_limit"
+                | "channels.allowed_base_urls"
+                | "notifications.templates"
+                | "notifications.delivery.batch_size"
+                | "notifications.delivery.max_attempts"
+                | "notifications.delivery.retry_delay"
+                | "log.level"
+                | "log.format"
+                | "shutdown.timeout"
+        )
+    }
+
+    /**
+     * 与新配置相比发生变化的配置项
+     */
+    pub fn changed_keys(&self, other: &Settings) -> Vec<&'static str> {
+        // 实例的增减和改名需要重启, 其余按字段比较
+        let sources_changed = self.devops.len() != other.devops.len()
+            || self
+                .devops
+                .iter()
+                .zip(&other.devops)
+                .any(|(source, other)| source.name != other.name);
+        let devops_changed = |changed: fn(&DevOpsArgs, &DevOpsArgs) -> bool| {
+            !sources_changed
+                && self
+                    .devops
+                    .iter()
+                    .zip(&other.devops)
+                    .any(|(source, other)| changed(source, other))
+        };
+        let (api, other_api) = (&self.api, &other.api);
+        let (delivery, other_delivery) =
+            (&self.notifications.delivery, &other.notifications.delivery);
+        [
+            ("devops", sources_changed),
+            (
+                "devops.base_url",
+                devops_changed(|source, other| source.base_url != other.base_url),
+            ),
+            (
+                "devops.access_token",
+                devops_changed(|source, other| source.access_token != other.access_token),
+            ),
+            (
+                "devops.user_id",
+                devops_changed(|source, other| source.user_id != other.user_id),
+            ),
+            (
+                "devops.projects",
+                devops_changed(|source, other| source.projects != other.projects),
+            ),
+            (
+                "devops.poll_interval",
+                devops_changed(|source, other| source.poll_interval != other.poll_interval),
+            ),
+            (
+                "devops.sync_members",
+                devops_changed(|source, other| source.sync_members != other.sync_members),
+            ),
+            (
+                "devops.member_sync_interval",
+                devops_changed(|source, other| {
+                    source.member_sync_interval != other.member_sync_interval
+                }),
+            ),
+            (
+                "devops.rate_limit",
+                devops_changed(|source, other| source.rate_limit != other.rate_limit),
+            ),
+            (
+                "devops.api_version",
+                devops_changed(|source, other| source.api_version != other.api_version),
+            ),
+            (
+                "devops.path_prefix",
+                devops_changed(|source, other| source.path_prefix != other.path_prefix),
+            ),
+            (
+                "devops.endpoints",
+                devops_changed(|source, other| source.endpoints != other.endpoints),
+            ),
+            (
+                "devops.max_retries",
+                devops_changed(|source, other| source.max_retries != other.max_retries),
+            ),
+            ("database", self.database != other.database),
+            ("api.address", api.address != other_api.address),
+            ("api.port", api.port != other_api.port),
+            ("api.timeout", api.timeout != other_api.timeout),
+            ("api.auth", api.auth != other_api.auth),
+            ("api.cors", api.cors != other_api.cors),
+            ("api.rate_limit", api.rate_limit != other_api.rate_limit),
+            ("history", self.history != other.history),
+            (
+                "channels.encryption_key",
+                self.channels.encryption_key != other.channels.encryption_key,
+            ),
+            (
+                "channels.allowed_base_urls",
+                self.channels.allowed_base_urls != other.channels.allowed_base_urls,
+            ),
+            (
+                "notifications.templates",
+                self.notifications.templates != other.notifications.templates,
+            ),
+            (
+                "notifications.delivery.interval",
+                delivery.interval != other_delivery.interval,
+            ),
+            (
+                "notifications.delivery.batch_size",
+                delivery.batch_size != other_delivery.batch_size,
+            ),
+            (
+                "notifications.delivery.max_attempts",
+                delivery.max_attempts != other_delivery.max_attempts,
+            ),
+            (
+                "notifications.delivery.retry_delay",
+                delivery.retry_delay != other_delivery.retry_delay,
+            ),
+            ("events", self.events != other.events),
+            ("health", self.health != other.health),
+            ("log.level", self.log.level != other.log.level),
+            ("log.format", self.log.format != other.log.format),
+            (
+                "shutdown.timeout",
+                self.shutdown.timeout != other.shutdown.timeout,
+            ),
+            ("supervisor", self.supervisor != other.supervisor),
+            ("telemetry", self.telemetry != other.telemetry),
+        ]
+        .into_iter()
+        .filter(|(_, changed)| *changed)
+        .map(|(key, _)| key)
+        .collect()
+    }
+
+    /**
+     * 只合并运行时可生效的配置项, 得到当前实际生效的配置
+     */
+    pub fn merge_reloadable(&self, other: &Settings) -> Settings {
+        let mut merged = self.clone();
+        if merged.devops.len() == other.devops.len() {
+            for (source, other) in merged.devops.iter_mut().zip(&other.devops) {
+                if source.name == other.name {
+                    source.projects = other.projects.clone();
+                }
+            }
+        }
+        merged.api.rate_limit = other.api.rate_limit.clone();
+        merged.channels.allowed_base_urls = other.channels.allowed_base_urls.clone();
+        merged.notifications.templates = other.notifications.templates.clone();
+        merged.notifications.delivery.batch_size = other.notifications.delivery.batch_size;
+        merged.notifications.delivery.max_attempts = other.notifications.delivery.max_attempts;
+        merged.notifications.delivery.retry_delay = other.notifications.delivery.retry_delay;
+        merged.log = other.log.clone();
+        merged.shutdown = other.shutdown.clone();
+        merged
+    }
 }
@@ -0,0 +1,50 @@
+use serde::{Deserialize, Deserializer};
+use std::fmt::{Debug, Formatter};
+
+/// 从文件读取, 如 file:/run/secrets/devops_token
+const FILE_PREFIX: &str = "file:";
+/// 从环境变量读取, 如 env:DEVOPS_ACCESS_TOKEN
+const ENV_PREFIX: &str = "env:";
+
+/**
+ * 敏感配置, 加载时解析 file:/env: 引用, Debug 输出时脱敏
+ */
+#[derive(Clone, Default, PartialEq, Eq)]
+pub struct Secret(String);
+
+impl Secret {
+    /**
+     * 解析引用, 其他值按字面量处理
+     */
+    pub fn resolve(value: &str) -> Result<Self, String> {
+        if let Some(path) = value.strip_prefix(FILE_PREFIX) {
+            let content = std::fs::read_to_string(path)
+                .map_err(|err| format!("read secret file {} failed: {}", path, err))?;
+            // 挂载的密钥文件通常带有结尾换行
+            return Ok(Self(content.trim_end_matches(['\r', '\n']).to_string()));
+        }
+        if let Some(name) = value.strip_prefix(ENV_PREFIX) {
+            return std::env::var(name)
+                .map(Self)
+                .map_err(|_| format!("environment variable {} is not set", name));
+        }
+        Ok(Self(value.to_string()))
+    }
+
+    pub fn expose(&self) -> &str {
+        &self.0
+    }
+}
+
+impl Debug for Secret {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        f.write_str("Secret(******)")
+    }
+}
+
+impl<'de> Deserialize<'de> for Secret {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        Self::resolve(&value).map_err(serde::de::Error::custom)
+    }
+}
@@ -0,0 +1,312 @@
+use crate::conf::{DataBaseOptions, DevOpsArgs, Settings};
+use crate::devops::PATH_PLACEHOLDERS;
+use crate::logging::LogFilter;
+use base64::Engine;
+use base64::engine::general_purpose::STANDARD;
+use sqlx::sqlite::SqliteConnectOptions;
+use std::collections::HashSet;
+use std::fmt::{Display, Formatter};
+use std::str::FromStr;
+use thiserror::Error;
+
+/**
+ * 配置问题, key 为配置项路径, 如 devops.base_url
+ */
+#[derive(Debug, Clone)]
+pub struct ConfigIssue {
+    pub key: String,
+    pub message: String,
+    pub hint: String,
+}
+
+impl Display for ConfigIssue {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}: {} (hint: {})", self.key, self.message, self.hint)
+    }
+}
+
+/**
+ * 校验收集的所有配置问题
+ */
+#[derive(Debug, Default)]
+pub struct ConfigIssues(Vec<ConfigIssue>);
+
+impl ConfigIssues {
+    pub fn push(&mut self, key: impl ToString, message: impl ToString, hint: impl ToString) {
+        self.0.push(ConfigIssue {
+            key: key.to_string(),
+            message: message.to_string(),
+            hint: hint.to_string(),
+        });
+    }
+
+    /**
+     * 条件不成立时记录问题
+     */
+    pub fn check(
+        &mut self,
+        valid: bool,
+        key: impl ToString,
+        message: impl ToString,
+        hint: impl ToString,
+    ) {
+        if !valid {
+            self.push(key, message, hint);
+        }
+    }
+
+    /**
+     * 数值必须大于 0
+     */
+    pub fn positive<T: Default + PartialOrd>(&mut self, value: T, key: impl AsRef<str>) {
+        let key = key.as_ref();
+        self.check(
+            value > T::default(),
+            key,
+            "must be greater than 0",
+            format!("set {} to a positive number", key),
+        );
+    }
+
+    pub fn into_result(self) -> Result<(), InvalidSettings> {
+        if self.0.is_empty() {
+            Ok(())
+        } else {
+            Err(InvalidSettings(self.0))
+        }
+    }
+}
+
+#[derive(Debug, Error)]
+pub struct InvalidSettings(pub Vec<ConfigIssue>);
+
+impl Display for InvalidSettings {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        write!(f, "found {} configuration problem(s):", self.0.len())?;
+        for issue in self.0.iter() {
+            write!(f, "\n  - {}", issue)?;
+        }
+        Ok(())
+    }
+}
+
+impl Settings {
+    /**
+     * 校验配置, 一次返回所有问题
+     */
+    pub fn validate(&self) -> Result<(), InvalidSettings> {
+        let mut issues = ConfigIssues::default();
+        DevOpsArgs::validate_sources(&self.devops, &mut issues);
+        self.database.validate(&mut issues);
+        self.api.validate(&mut issues);
+
+        let history = &self.history;
+        issues.positive(history.compaction_interval, "history.compaction_interval");
+        if let Some(days) = history.retention.days {
+            issues.positive(days, "history.retention.days");
+        }
+        if let Some(builds) = history.retention.builds {
+            issues.positive(builds, "history.retention.builds");
+        }
+
+        if let Some(key) = &self.channels.encryption_key {
+            let valid = STANDARD
+                .decode(key.expose().trim())
+                .is_ok_and(|key| key.len() == 32);
+            issues.check(
+                valid,
+                "channels.encryption_key",
+                "must be 32 bytes encoded in base64",
+                "generate one with `openssl rand -base64 32`",
+            );
+        }
+
+        let delivery = &self.notifications.delivery;
+        issues.positive(delivery.interval, "notifications.delivery.interval");
+        issues.positive(delivery.batch_size, "notifications.delivery.batch_size");
+        issues.positive(delivery.max_attempts, "notifications.delivery.max_attempts");
+        for (id, template) in self.notifications.templates.iter() {
+            issues.check(
+                !template.trim().is_empty(),
+                format!("notifications.templates.{}", id),
+                "must not be empty",
+                "remove the template or give it content, e.g. \"{{content}}\"",
+            );
+        }
+
+        if let Err(err) = LogFilter::default().apply(&self.log.level) {
+            issues.check(
+                false,
+                "log.level",
+                format!("{:#}", err),
+                "use a level or module=level pairs, e.g. info,sqlx=warn",
+            );
+        }
+        issues.positive(self.supervisor.restart_delay, "supervisor.restart_delay");
+        issues.check(
+            self.supervisor.max_restart_delay >= self.supervisor.restart_delay,
+            "supervisor.max_restart_delay",
+            "is less than supervisor.restart_delay",
+            "raise max_restart_delay or lower restart_delay",
+        );
+        let telemetry = &self.telemetry;
+        issues.check(
+            (0.0..=1.0).contains(&telemetry.sample_ratio),
+            "telemetry.sample_ratio",
+            "must be between 0 and 1",
+            "use 1.0 to sample every trace or e.g. 0.1 for 10%",
+        );
+        if telemetry.enabled {
+            issues.check(
+                reqwest::Url::parse(&telemetry.endpoint)
+                    .is_ok_and(|url| matches!(url.scheme(), "http" | "https")),
+                "telemetry.endpoint",
+                "is not a valid http(s) url",
+                "set the collector traces endpoint, e.g. http://127.0.0.1:4318/v1/traces",
+            );
+            issues.positive(telemetry.timeout, "telemetry.timeout");
+        }
+        issues.positive(self.events.heartbeat, "events.heartbeat");
+        issues.positive(self.events.ack_window, "events.ack_window");
+        issues.positive(
+            self.health.devops_check_timeout,
+            "health.devops_check_timeout",
+        );
+        issues.into_result()
+    }
+}
+
+impl DevOpsArgs {
+    /**
+     * 校验所有 DevOps 实例, 实例名称不能为空且不能重复
+     */
+    fn validate_sources(sources: &[DevOpsArgs], issues: &mut ConfigIssues) {
+        issues.check(
+            !sources.is_empty(),
+            "devops",
+            "no devops source configured",
+            "add a [devops] table or one [[devops]] table per DevOps installation",
+        );
+        let mut names = HashSet::new();
+        for (index, source) in sources.iter().enumerate() {
+            let prefix = if sources.len() == 1 {
+                "devops".to_string()
+            } else {
+                format!("devops[{}]", index)
+            };
+            issues.check(
+                !source.name.trim().is_empty(),
+                format!("{}.name", prefix),
+                "is empty",
+                "name the source, e.g. prod",
+            );
+            issues.check(
+                names.insert(source.name.as_str()),
+                format!("{}.name", prefix),
+                format!("duplicate source {}", source.name),
+                "give every devops source a unique name",
+            );
+            source.validate(&prefix, issues);
+        }
+    }
+
+    fn validate(&self, prefix: &str, issues: &mut ConfigIssues) {
+        match reqwest::Url::parse(&self.base_url) {
+            Ok(url) => issues.check(
+                matches!(url.scheme(), "http" | "https"),
+                format!("{}.base_url", prefix),
+                format!("unsupported scheme {}", url.scheme()),
+                "use an http:// or https:// url",
+            ),
+            Err(err) => issues.push(
+                format!("{}.base_url", prefix),
+                if self.base_url.is_empty() {
+                    "is empty".to_string()
+                } else {
+                    format!("is not a valid url: {}", err)
+                },
+                "set the DevOps open api address, e.g. https://devops.example.com/api/open",
+            ),
+        }
+        issues.check(
+            !self.access_token.expose().trim().is_empty(),
+            format!("{}.access_token", prefix),
+            "is empty",
+            "set the DevOps access token, preferably as env:NAME or file:/path",
+        );
+        issues.check(
+            !self.user_id.trim().is_empty(),
+            format!("{}.user_id", prefix),
+            "is empty",
+            "set the DevOps user the access token belongs to",
+        );
+        for (index, project) in self.projects.iter().enumerate() {
+            issues.check(
+                !project.trim().is_empty(),
+                format!("{}.projects[{}]", prefix, index),
+                "is empty",
+                "remove the entry or set a project code",
+            );
+        }
+        issues.positive(self.poll_interval, format!("{}.poll_interval", prefix));
+        if self.sync_members {
+            issues.positive(
+                self.member_sync_interval,
+                format!("{}.member_sync_interval", prefix),
+            );
+        }
+        if let Some(rate_limit) = self.rate_limit {
+            issues.positive(rate_limit, format!("{}.rate_limit", prefix));
+        }
+        issues.check(
+            self.path_prefix.is_empty() || self.path_prefix.starts_with('/'),
+            format!("{}.path_prefix", prefix),
+            "must start with /",
+            "set a gateway prefix such as /prod, or leave it empty",
+        );
+        for (name, path) in self.endpoints.overrides() {
+            let key = format!("{}.endpoints.{}", prefix, name);
+            issues.check(
+                path.starts_with('/'),
+                &key,
+                "must start with /",
+                "set the path relative to base_url, e.g. /v4/projects/{project_id}/pipeline/pipeline_list",
+            );
+            let unknown: Vec<&str> = path
+                .match_indices('{')
+                .filter_map(|(start, _)| {
+                    path[start..]
+                        .find('}')
+                        .map(|end| &path[start..=start + end])
+                })
+                .filter(|placeholder| !PATH_PLACEHOLDERS.contains(placeholder))
+                .collect();
+            issues.check(
+                unknown.is_empty(),
+                &key,
+                format!("unknown placeholders {:?}", unknown),
+                format!("use only {:?}", PATH_PLACEHOLDERS),
+            );
+        }
+    }
+}
+
+impl DataBaseOptions {
+    fn validate(&self, issues: &mut ConfigIssues) {
+        let url = self.url.expose();
+        if !url.starts_with("sqlite:") {
+            issues.push(
+                "database.url",
+                "only sqlite databases are supported",
+                "use a url like sqlite:data.db?mode=rwc",
+            );
+        } else if let Err(err) = SqliteConnectOptions::from_str(url) {
+            issues.push(
+                "database.url",
+                format!("is not a valid sqlite url: {}", err),
+                "use a url like sqlite:data.db?mode=rwc",
+            );
+        }
+        issues.positive(self.pool_size, "database.pool_size");
+    }
+}
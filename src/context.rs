@@ -0,0 +1,48 @@
+use std::future::Future;
+
+/// 请求 ID 的请求头
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/**
+ * 当前请求的 ID, 不在请求处理过程中时为 None
+ */
+pub fn request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/**
+ * 在请求 ID 的作用域内执行
+ */
+pub async fn with_request_id<F: Future>(request_id: String, future: F) -> F::Output {
+    REQUEST_ID.scope(request_id, future).await
+}
+
+tokio::task_local! {
+    static LOG_FIELDS: Vec<(&'static str, String)>;
+}
+
+/**
+ * 当前任务的日志上下文字段, 输出日志时附加到每一行
+ */
+pub fn log_fields() -> Vec<(&'static str, String)> {
+    LOG_FIELDS
+        .try_with(|fields| fields.clone())
+        .unwrap_or_default()
+}
+
+/**
+ * 在日志上下文字段的作用域内执行, 与外层字段合并, 同名字段以内层为准
+ */
+pub async fn with_log_fields<F: Future>(
+    fields: Vec<(&'static str, String)>,
+    future: F,
+) -> F::Output {
+    let mut merged = log_fields();
+    merged.retain(|(key, _)| fields.iter().all(|(inner, _)| inner != key));
+    merged.extend(fields);
+    LOG_FIELDS.scope(merged, future).await
+}
@@ -1,7 +1,21 @@
 use crate::conf::DevOpsArgs;
-use anyhow::{anyhow, Context};
+use crate::context;
+use crate::telemetry;
+use anyhow::{Context, anyhow};
 use getset::Getters;
+use log::warn;
+use opentelemetry::KeyValue;
+use opentelemetry::context::FutureExt;
+use opentelemetry::trace::{SpanKind, TraceContextExt};
 use serde::Deserialize;
+use std::fmt::{Display, Formatter};
+use std::sync::Arc;
+use std::time::Duration;
+use tokio::sync::Mutex;
+use tokio::time::Instant;
+
+/// 首次重试等待, 之后每次翻倍
+const RETRY_DELAY: Duration = Duration::from_millis(500);
 
 #[allow(unused)]
 #[derive(Debug, Deserialize, Clone, Getters)]
@@ -51,7 +65,7 @@ impl<T> Default for DevOpsApiBody<T> {
 }
 
 #[allow(unused)]
-#[derive(Debug, Deserialize, Clone, Getters)]
+#[derive(Debug, Deserialize, Clone, Getters, Default)]
 #[serde(rename_all = "camelCase")]
 #[get = "pub"]
 pub struct PipelineInfo {
@@ -66,6 +80,7 @@ pub struct PipelineInfo {
     pub latest_build_start_time: i64,
     pub latest_build_end_time: i64,
     pub latest_build_num: i32,
+    pub latest_build_status: Option<String>,
     pub latest_build_estimated_execution_seconds: i32,
     pub deployment_time: i64,
     pub create_time: i64,
@@ -87,76 +102,340 @@ pub struct PipelineInfo {
     pub delete: bool,
 }
 
-impl Default for PipelineInfo {
-    fn default() -> Self {
-        Self {
-            project_id: String::default(),
-            pipeline_id: String::default(),
-            pipeline_name: String::default(),
-            pipeline_desc: String::default(),
-            task_count: 0,
-            build_count: 0,
-            lock: false,
-            can_manual_startup: false,
-            latest_build_start_time: 0,
-            latest_build_end_time: 0,
-            latest_build_num: 0,
-            latest_build_estimated_execution_seconds: 0,
-            deployment_time: 0,
-            create_time: 0,
-            update_time: 0,
-            pipeline_version: 0,
-            current_timestamp: 0,
-            running_build_count: 0,
-            has_permission: false,
-            has_collect: false,
-            latest_build_user_id: String::default(),
-            instance_from_template: false,
-            template_id: String::default(),
-            version_name: String::default(),
-            version: 0,
-            updater: String::default(),
-            creator: String::default(),
-            last_build_total_count: 0,
-            last_build_finish_count: 0,
-            delete: false,
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, Getters, Default)]
+#[serde(rename_all = "camelCase", default)]
+#[get = "pub"]
+pub struct StageStatus {
+    pub stage_id: String,
+    pub name: String,
+    pub status: Option<String>,
+    pub start_epoch: Option<i64>,
+    pub elapsed: Option<i64>,
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, Getters, Default)]
+#[serde(rename_all = "camelCase", default)]
+#[get = "pub"]
+pub struct BuildInfo {
+    pub id: String,
+    pub build_num: i32,
+    pub status: String,
+    pub trigger: String,
+    pub user_id: String,
+    pub start_time: i64,
+    pub end_time: Option<i64>,
+    pub total_time: Option<i64>,
+    pub execute_time: Option<i64>,
+    pub stage_status: Option<Vec<StageStatus>>,
+}
+
+#[allow(unused)]
+#[derive(Debug, Deserialize, Clone, Getters, Default)]
+#[serde(rename_all = "camelCase", default)]
+#[get = "pub"]
+pub struct ProjectMember {
+    pub user_id: String,
+    /// 用户组, 如 manager/developer/maintainer/visitor
+    pub group_code: String,
+    pub group_name: String,
+}
+
+/**
+ * DevOps 开放接口版本, 决定各接口的默认路径
+ */
+#[derive(Debug, Deserialize, Clone, Copy, PartialEq, Eq, Default)]
+#[serde(rename_all = "snake_case")]
+pub enum ApiVersion {
+    /// 网关 CCI 项目下的 open 接口, 项目通过 projectCode 参数传递
+    #[default]
+    OpenV1,
+    /// BK-CI APIGW v4 接口, 项目在路径中
+    ApigwV4,
+}
+
+impl Display for ApiVersion {
+    fn fmt(&self, f: &mut Formatter<'_>) -> std::fmt::Result {
+        let version = match self {
+            ApiVersion::OpenV1 => "open_v1",
+            ApiVersion::ApigwV4 => "apigw_v4",
+        };
+        write!(f, "{}", version)
+    }
+}
+
+/// 接口路径中可用的占位符
+pub const PATH_PLACEHOLDERS: &[&str] = &["{project_id}"];
+
+/**
+ * 解析后的接口路径
+ */
+#[derive(Debug, Clone)]
+struct EndpointPaths {
+    pipelines: String,
+    builds: String,
+    members: String,
+}
+
+/**
+ * 接口路径覆盖项, 未配置的接口使用版本的默认路径
+ */
+#[derive(Debug, Deserialize, Clone, PartialEq, Eq, Default)]
+pub struct Endpoints {
+    pub pipelines: Option<String>,
+    pub builds: Option<String>,
+    pub members: Option<String>,
+}
+
+impl Endpoints {
+    /**
+     * 版本的默认路径表
+     */
+    fn defaults(version: ApiVersion) -> [&'static str; 3] {
+        match version {
+            ApiVersion::OpenV1 => [
+                "/projects/CCI/api/service/open/pipeline_get",
+                "/projects/CCI/api/service/open/build_history",
+                "/projects/CCI/api/service/open/project_members",
+            ],
+            ApiVersion::ApigwV4 => [
+                "/v4/projects/{project_id}/pipeline/pipeline_list",
+                "/v4/projects/{project_id}/build/history",
+                "/v4/projects/{project_id}/project_member",
+            ],
+        }
+    }
+
+    /**
+     * 合并版本默认路径和配置的覆盖项
+     */
+    fn resolve(&self, version: ApiVersion) -> EndpointPaths {
+        let [pipelines, builds, members] = Self::defaults(version);
+        let path = |path: &Option<String>, default: &str| {
+            path.clone().unwrap_or_else(|| default.to_string())
+        };
+        EndpointPaths {
+            pipelines: path(&self.pipelines, pipelines),
+            builds: path(&self.builds, builds),
+            members: path(&self.members, members),
         }
     }
+
+    /**
+     * 已配置的覆盖项, 用于校验
+     */
+    pub fn overrides(&self) -> Vec<(&'static str, &str)> {
+        [
+            ("pipelines", &self.pipelines),
+            ("builds", &self.builds),
+            ("members", &self.members),
+        ]
+        .into_iter()
+        .filter_map(|(name, path)| path.as_deref().map(|path| (name, path)))
+        .collect()
+    }
 }
 
 #[allow(unused)]
 pub struct DevOpsApiClient {
     client: reqwest::Client,
     options: DevOpsArgs,
+    /// 按版本和覆盖项解析后的接口路径
+    endpoints: EndpointPaths,
+    /// 按实例限流时下一次允许请求的时间
+    next_request: Mutex<Instant>,
 }
 
 impl DevOpsApiClient {
     pub(crate) fn new(options: DevOpsArgs) -> Self {
+        let endpoints = options.endpoints().resolve(*options.api_version());
         DevOpsApiClient {
             client: reqwest::Client::new(),
+            endpoints,
             options,
+            next_request: Mutex::new(Instant::now()),
+        }
+    }
+
+    /**
+     * 实例名称
+     */
+    pub fn name(&self) -> &str {
+        self.options.name()
+    }
+
+    pub fn projects(&self) -> &[String] {
+        self.options.projects()
+    }
+
+    /**
+     * 拼接接口地址: base_url + path_prefix + 接口路径, 并替换路径中的项目;
+     * 项目作为路径段时只允许字母, 数字, '_' 和 '-', 避免改写请求路径
+     */
+    fn url(&self, path: &str, project_id: &str) -> Result<String, anyhow::Error> {
+        let template = self.template(path);
+        if template.contains("{project_id}")
+            && (project_id.is_empty()
+                || !project_id
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-'))
+        {
+            return Err(anyhow!("invalid project id {:?}", project_id));
+        }
+        Ok(format!(
+            "{}{}",
+            self.options.base_url().trim_end_matches('/'),
+            template.replace("{project_id}", project_id),
+        ))
+    }
+
+    /**
+     * 未替换占位符的接口路径, 作为 span 的 url.template
+     */
+    fn template(&self, path: &str) -> String {
+        format!(
+            "{}{}",
+            self.options.path_prefix().trim_end_matches('/'),
+            path
+        )
+    }
+
+    /**
+     * 按配置的每秒请求数均匀排队, 未配置时不等待
+     */
+    async fn throttle(&self) {
+        let Some(rate_limit) = self.options.rate_limit().filter(|limit| *limit > 0) else {
+            return;
+        };
+        let interval = Duration::from_secs_f64(1.0 / rate_limit as f64);
+        let mut next_request = self.next_request.lock().await;
+        let now = Instant::now();
+        if *next_request > now {
+            tokio::time::sleep_until(*next_request).await;
+        }
+        *next_request = (*next_request).max(now) + interval;
+    }
+
+    /**
+     * 带认证头的 GET 请求, 处理 API 请求时同时透传请求 ID
+     */
+    fn get(&self, url: &str) -> reqwest::RequestBuilder {
+        let mut builder = self
+            .client
+            .get(url)
+            .header(
+                "X-DEVOPS-ACCESS-TOKEN",
+                self.options.access_token().expose(),
+            )
+            .header("X-DEVOPS-UID", self.options.user_id());
+        if let Some(request_id) = context::request_id() {
+            builder = builder.header(context::REQUEST_ID_HEADER, request_id);
+        }
+        builder
+    }
+
+    /**
+     * 在 client span 内发送请求并透传 traceparent, 连接失败或 5xx 时按 max_retries 退避重试,
+     * 重试同样受实例限流约束
+     */
+    async fn send(
+        &self,
+        template: &str,
+        builder: reqwest::RequestBuilder,
+        max_retries: u32,
+    ) -> Result<reqwest::Response, reqwest::Error> {
+        let request = builder.build()?;
+        let cx = telemetry::start_span(
+            format!("{} {}", request.method(), template),
+            SpanKind::Client,
+            vec![
+                KeyValue::new("http.request.method", request.method().to_string()),
+                KeyValue::new("url.template", template.to_string()),
+                KeyValue::new("url.full", request.url().to_string()),
+                KeyValue::new("devops.source", self.name().to_string()),
+            ],
+        );
+        let mut resend_count = 0;
+        let result = loop {
+            // GET 请求没有 body, 总能复制
+            let mut attempt = request
+                .try_clone()
+                .expect("request without a streaming body");
+            telemetry::inject(&cx, attempt.headers_mut());
+            let result = self.client.execute(attempt).with_context(cx.clone()).await;
+            let retryable = match &result {
+                Ok(response) => response.status().is_server_error(),
+                Err(err) => err.is_connect() || err.is_timeout(),
+            };
+            if !retryable || resend_count >= max_retries {
+                break result;
+            }
+            resend_count += 1;
+            let delay = RETRY_DELAY.saturating_mul(2u32.pow(resend_count.min(8) - 1));
+            warn!(
+                "{} {} failed, retrying in {:?} ({}/{})",
+                request.method(),
+                template,
+                delay,
+                resend_count,
+                max_retries
+            );
+            tokio::time::sleep(delay).await;
+            self.throttle().await;
+        };
+        let span = cx.span();
+        if resend_count > 0 {
+            span.set_attribute(KeyValue::new(
+                "http.request.resend_count",
+                resend_count as i64,
+            ));
+        }
+        match &result {
+            Ok(response) => {
+                span.set_attribute(KeyValue::new(
+                    "http.response.status_code",
+                    response.status().as_u16() as i64,
+                ));
+                if response.status().is_server_error() {
+                    telemetry::record_error(&cx, response.status());
+                }
+            }
+            Err(err) => telemetry::record_error(&cx, err),
+        }
+        span.end();
+        result
+    }
+
+    /**
+     * 探测 DevOps 是否可达, 返回 HTTP 状态码, 5xx 视为不可用
+     */
+    pub async fn ping(&self, timeout: Duration) -> Result<u16, anyhow::Error> {
+        let response = self
+            .send("/", self.get(self.options.base_url()).timeout(timeout), 0)
+            .await
+            .context("devops api is unreachable")?;
+        let status = response.status();
+        if status.is_server_error() {
+            return Err(anyhow!("devops api responded {}", status));
         }
+        Ok(status.as_u16())
     }
 
     pub async fn get_project_pipelines(
         &self,
         project_id: String,
     ) -> Result<Vec<PipelineInfo>, anyhow::Error> {
-        let url = format!(
-            "{}/projects/CCI/api/service/open/pipeline_get",
-            self.options.base_url(),
-        );
+        let url = self.url(&self.endpoints.pipelines, &project_id)?;
+        let template = self.template(&self.endpoints.pipelines);
+        self.throttle().await;
+        let request = self.get(&url).query(&[
+            ("projectCode", project_id.as_str()),
+            ("page", "1"),
+            ("pageSize", "100"),
+        ]);
         let response = self
-            .client
-            .get(url)
-            .header("X-DEVOPS-ACCESS-TOKEN", self.options.access_token())
-            .header("X-DEVOPS-UID", self.options.user_id())
-            .query(&[
-                ("projectCode", project_id.as_str()),
-                ("page", "1"),
-                ("pageSize", "100"),
-            ])
-            .send()
+            .send(&template, request, *self.options.max_retries())
             .await
             .context("get project pipelines failed")?;
 
@@ -184,4 +463,135 @@ impl DevOpsApiClient {
             )))
         }
     }
+
+    pub async fn get_pipeline_builds(
+        &self,
+        project_id: String,
+        pipeline_id: String,
+    ) -> Result<Vec<BuildInfo>, anyhow::Error> {
+        let url = self.url(&self.endpoints.builds, &project_id)?;
+        let template = self.template(&self.endpoints.builds);
+        self.throttle().await;
+        let request = self.get(&url).query(&[
+            ("projectCode", project_id.as_str()),
+            ("pipelineId", pipeline_id.as_str()),
+            ("page", "1"),
+            ("pageSize", "100"),
+        ]);
+        let response = self
+            .send(&template, request, *self.options.max_retries())
+            .await
+            .context("get pipeline builds failed")?;
+
+        if response.status().is_success() {
+            let body = response
+                .json::<DevOpsApiBody<PageRecords<BuildInfo>>>()
+                .await
+                .context("convert body to build struct failed")?;
+
+            match body.data {
+                None => Ok(vec![]),
+                Some(records) => Ok(records.records),
+            }
+        } else {
+            let body = response
+                .json::<DevOpsApiBody<String>>()
+                .await
+                .context("convert body to error struct failed")?;
+            Err(anyhow!(format!(
+                "get pipeline {} builds failed.  {}",
+                pipeline_id,
+                body.message
+                    .unwrap_or_else(|| "message is empty".to_string())
+            )))
+        }
+    }
+
+    pub async fn get_project_members(
+        &self,
+        project_id: String,
+    ) -> Result<Vec<ProjectMember>, anyhow::Error> {
+        let url = self.url(&self.endpoints.members, &project_id)?;
+        let template = self.template(&self.endpoints.members);
+        self.throttle().await;
+        let request = self
+            .get(&url)
+            .query(&[("projectCode", project_id.as_str())]);
+        let response = self
+            .send(&template, request, *self.options.max_retries())
+            .await
+            .context("get project members failed")?;
+
+        if response.status().is_success() {
+            let body = response
+                .json::<DevOpsApiBody<Vec<ProjectMember>>>()
+                .await
+                .context("convert body to member struct failed")?;
+            Ok(body.data.unwrap_or_default())
+        } else {
+            let body = response
+                .json::<DevOpsApiBody<String>>()
+                .await
+                .context("convert body to error struct failed")?;
+            Err(anyhow!(format!(
+                "get project {} members failed.  {}",
+                project_id,
+                body.message
+                    .unwrap_or_else(|| "message is empty".to_string())
+            )))
+        }
+    }
+}
+
+/**
+ * 已配置的 DevOps 实例, 按名称查找客户端
+ */
+#[derive(Clone)]
+pub struct DevOpsClients {
+    clients: Arc<Vec<Arc<DevOpsApiClient>>>,
+}
+
+impl DevOpsClients {
+    pub fn new(sources: &[DevOpsArgs]) -> Self {
+        Self {
+            clients: Arc::new(
+                sources
+                    .iter()
+                    .cloned()
+                    .map(|source| Arc::new(DevOpsApiClient::new(source)))
+                    .collect(),
+            ),
+        }
+    }
+
+    pub fn all(&self) -> &[Arc<DevOpsApiClient>] {
+        &self.clients
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<DevOpsApiClient>> {
+        self.clients
+            .iter()
+            .find(|client| client.name() == name)
+            .cloned()
+    }
+
+    /**
+     * 未指定实例时, 取配置了该项目的实例; 只有一个实例时直接使用
+     */
+    pub fn resolve(&self, source: Option<&str>, project_id: &str) -> Option<Arc<DevOpsApiClient>> {
+        match source {
+            Some(source) => self.get(source),
+            None => self
+                .clients
+                .iter()
+                .find(|client| {
+                    client
+                        .projects()
+                        .iter()
+                        .any(|project| project == project_id)
+                })
+                .or_else(|| (self.clients.len() == 1).then(|| &self.clients[0]))
+                .cloned(),
+        }
+    }
 }
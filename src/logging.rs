@@ -0,0 +1,316 @@
+use crate::context;
+use crate::telemetry;
+use chrono::{SecondsFormat, Utc};
+use log::kv::{Error as KvError, Key, Value, VisitSource};
+use log::{LevelFilter, Log, Metadata, Record};
+use once_cell::sync::OnceCell;
+use serde::{Deserialize, Serialize};
+use serde_json::{Map, Value as JsonValue};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::io::Write;
+use std::sync::RwLock;
+
+static LOGGER: OnceCell<AppLogger> = OnceCell::new();
+
+/**
+ * 日志格式
+ */
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// 便于阅读的彩色输出
+    #[default]
+    Pretty,
+    /// 每行一个 JSON 对象, 便于日志系统解析
+    Json,
+}
+
+/**
+ * 日志级别过滤: 全局级别加按模块覆盖, 模块按最长前缀匹配
+ */
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LogFilter {
+    level: LevelFilter,
+    modules: BTreeMap<String, LevelFilter>,
+}
+
+impl Default for LogFilter {
+    fn default() -> Self {
+        Self {
+            level: LevelFilter::Info,
+            modules: BTreeMap::new(),
+        }
+    }
+}
+
+impl LogFilter {
+    pub fn level(&self) -> LevelFilter {
+        self.level
+    }
+
+    pub fn modules(&self) -> &BTreeMap<String, LevelFilter> {
+        &self.modules
+    }
+
+    pub fn set_level(&mut self, level: LevelFilter) {
+        self.level = level;
+    }
+
+    /**
+     * 设置模块级别, None 表示移除覆盖, 使用全局级别
+     */
+    pub fn set_module(&mut self, module: &str, level: Option<LevelFilter>) {
+        match level {
+            Some(level) => self.modules.insert(module.to_string(), level),
+            None => self.modules.remove(module),
+        };
+    }
+
+    /**
+     * 叠加 RUST_LOG 风格的指令, 例如 "info,vision_notification::api=debug,sqlx=warn";
+     * 单独的级别设置全局级别, 单独的模块名表示 trace
+     */
+    pub fn apply(&mut self, directives: &str) -> Result<(), anyhow::Error> {
+        let mut filter = self.clone();
+        for directive in directives.split(',').map(str::trim) {
+            if directive.is_empty() {
+                continue;
+            }
+            match directive.split_once('=') {
+                Some((module, level)) => {
+                    let level = parse_level(level)?;
+                    filter.set_module(module.trim(), Some(level));
+                }
+                None => match directive.parse::<LevelFilter>() {
+                    Ok(level) => filter.level = level,
+                    Err(_) => filter.set_module(directive, Some(LevelFilter::Trace)),
+                },
+            }
+        }
+        *self = filter;
+        Ok(())
+    }
+
+    fn enabled(&self, target: &str, level: log::Level) -> bool {
+        let limit = self
+            .modules
+            .iter()
+            .filter(|(module, _)| {
+                target == module.as_str()
+                    || target
+                        .strip_prefix(module.as_str())
+                        .is_some_and(|rest| rest.starts_with("::"))
+            })
+            .max_by_key(|(module, _)| module.len())
+            .map(|(_, level)| *level)
+            .unwrap_or(self.level);
+        level <= limit
+    }
+
+    fn max_level(&self) -> LevelFilter {
+        self.modules
+            .values()
+            .copied()
+            .fold(self.level, |max, level| max.max(level))
+    }
+}
+
+impl fmt::Display for LogFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.level.as_str().to_lowercase())?;
+        for (module, level) in self.modules.iter() {
+            write!(f, ",{}={}", module, level.as_str().to_lowercase())?;
+        }
+        Ok(())
+    }
+}
+
+pub fn parse_level(level: &str) -> Result<LevelFilter, anyhow::Error> {
+    level.trim().parse::<LevelFilter>().map_err(|_| {
+        anyhow::anyhow!(
+            "unknown level {}, use one of off, error, warn, info, debug, trace",
+            level.trim()
+        )
+    })
+}
+
+/**
+ * 应用日志: 按运行时可调整的过滤条件输出, 附带请求 ID 和当前任务的上下文字段
+ */
+struct AppLogger {
+    format: RwLock<LogFormat>,
+    filter: RwLock<LogFilter>,
+    pretty: Box<dyn Log>,
+}
+
+impl AppLogger {
+    fn format(&self) -> LogFormat {
+        *self.format.read().unwrap_or_else(|err| err.into_inner())
+    }
+
+    /**
+     * 请求 ID、trace id、上下文字段以及日志调用处的键值
+     */
+    fn fields(record: &Record) -> Vec<(String, JsonValue)> {
+        let mut fields: Vec<(String, JsonValue)> = vec![];
+        if let Some(request_id) = context::request_id() {
+            fields.push(("request_id".to_string(), request_id.into()));
+        }
+        if let Some(trace_id) = telemetry::trace_id() {
+            fields.push(("trace_id".to_string(), trace_id.into()));
+        }
+        for (key, value) in context::log_fields() {
+            fields.push((key.to_string(), value.into()));
+        }
+        let mut visitor = FieldVisitor(&mut fields);
+        let _ = record.key_values().visit(&mut visitor);
+        fields
+    }
+
+    fn log_pretty(&self, record: &Record) {
+        let fields = Self::fields(record);
+        if fields.is_empty() {
+            self.pretty.log(record);
+            return;
+        }
+        let suffix = fields
+            .iter()
+            // 字符串不带引号输出, 其他值按 JSON 输出
+            .map(|(key, value)| {
+                let value = value
+                    .as_str()
+                    .map_or_else(|| value.to_string(), str::to_string);
+                format!("{}={}", key, value)
+            })
+            .collect::<Vec<_>>()
+            .join(" ");
+        self.pretty.log(
+            &Record::builder()
+                .args(format_args!("{} {}", record.args(), suffix))
+                .metadata(record.metadata().clone())
+                .module_path(record.module_path())
+                .file(record.file())
+                .line(record.line())
+                .build(),
+        );
+    }
+
+    fn log_json(&self, record: &Record) {
+        let mut line = Map::new();
+        line.insert(
+            "timestamp".to_string(),
+            Utc::now()
+                .to_rfc3339_opts(SecondsFormat::Millis, true)
+                .into(),
+        );
+        line.insert("level".to_string(), record.level().as_str().into());
+        line.insert("target".to_string(), record.target().into());
+        line.insert("message".to_string(), record.args().to_string().into());
+        for (key, value) in Self::fields(record) {
+            line.insert(key, value);
+        }
+        let mut stderr = std::io::stderr().lock();
+        let _ = writeln!(stderr, "{}", JsonValue::Object(line));
+    }
+}
+
+impl Log for AppLogger {
+    fn enabled(&self, metadata: &Metadata) -> bool {
+        self.filter
+            .read()
+            .unwrap_or_else(|err| err.into_inner())
+            .enabled(metadata.target(), metadata.level())
+    }
+
+    fn log(&self, record: &Record) {
+        if !self.enabled(record.metadata()) {
+            return;
+        }
+        match self.format() {
+            LogFormat::Pretty => self.log_pretty(record),
+            LogFormat::Json => self.log_json(record),
+        }
+    }
+
+    fn flush(&self) {
+        self.pretty.flush();
+        let _ = std::io::stderr().flush();
+    }
+}
+
+struct FieldVisitor<'a>(&'a mut Vec<(String, JsonValue)>);
+
+impl<'kvs> VisitSource<'kvs> for FieldVisitor<'_> {
+    fn visit_pair(&mut self, key: Key<'kvs>, value: Value<'kvs>) -> Result<(), KvError> {
+        let value = serde_json::to_value(&value).unwrap_or_else(|_| value.to_string().into());
+        self.0.push((key.to_string(), value));
+        Ok(())
+    }
+}
+
+/**
+ * 安装全局日志, 启动时使用 RUST_LOG 或 info, 加载配置后再调用 configure
+ */
+pub fn init() {
+    let mut filter = LogFilter::default();
+    if let Ok(directives) = std::env::var("RUST_LOG") {
+        let _ = filter.apply(&directives);
+    }
+    let pretty = pretty_env_logger::formatted_timed_builder()
+        .filter_level(LevelFilter::Trace)
+        .build();
+    let logger = LOGGER.get_or_init(|| AppLogger {
+        format: RwLock::new(LogFormat::Pretty),
+        filter: RwLock::new(filter),
+        pretty: Box::new(pretty),
+    });
+    if log::set_logger(logger).is_ok() {
+        log::set_max_level(
+            logger
+                .filter
+                .read()
+                .unwrap_or_else(|err| err.into_inner())
+                .max_level(),
+        );
+    }
+}
+
+/**
+ * 按配置设置格式和级别, RUST_LOG 中的指令优先于配置
+ */
+pub fn configure(format: LogFormat, directives: &str) -> Result<(), anyhow::Error> {
+    let mut filter = LogFilter::default();
+    filter.apply(directives)?;
+    if let Ok(env) = std::env::var("RUST_LOG") {
+        filter.apply(&env)?;
+    }
+    if let Some(logger) = LOGGER.get() {
+        *logger.format.write().unwrap_or_else(|err| err.into_inner()) = format;
+    }
+    set_filter(filter);
+    Ok(())
+}
+
+pub fn filter() -> LogFilter {
+    LOGGER
+        .get()
+        .map(|logger| {
+            logger
+                .filter
+                .read()
+                .unwrap_or_else(|err| err.into_inner())
+                .clone()
+        })
+        .unwrap_or_default()
+}
+
+/**
+ * 运行时替换过滤条件, 不会写回配置文件
+ */
+pub fn set_filter(filter: LogFilter) {
+    log::set_max_level(filter.max_level());
+    if let Some(logger) = LOGGER.get() {
+        *logger.filter.write().unwrap_or_else(|err| err.into_inner()) = filter;
+    }
+}
@@ -1,75 +1,371 @@
 mod api;
 mod application;
+mod channel;
+mod cli;
 mod conf;
+mod context;
+mod devops;
+mod logging;
 mod repository;
+mod telemetry;
 
 use clap::{Args, Parser, Subcommand};
-use std::time::Duration;
+use std::time::{Duration, Instant, SystemTime};
 use tokio::{select, signal, time};
 
-use crate::api::ApiService;
-use crate::conf::Settings;
+use crate::api::{ApiService, ApiServices};
+use crate::application::access::{AccessService, MemberSync};
+use crate::application::api_key::ApiKeyService;
+use crate::application::channel::ChannelService;
+use crate::application::event::EventBus;
+use crate::application::health::HealthService;
+use crate::application::history::{BuildHistoryService, HistoryCompactor};
+use crate::application::notification::{DeliveryWorker, NotificationService};
+use crate::application::pipeline::PipelineService;
+use crate::application::poller::BuildPoller;
+use crate::application::subscription::SubscriptionService;
+use crate::application::supervisor::{ManagedService, RestartPolicy, Supervisor};
+use crate::channel::ChannelClient;
+use crate::conf::{Secret, Settings};
+use crate::devops::DevOpsClients;
+use crate::repository::sqlite::{
+    ApiKeyRepository, BuildRepository, ChannelRepository, DeliveryRepository, HealthRepository,
+    NotificationRepository, PipelineRepository, ProjectMemberRepository, SubscriptionRepository,
+};
 use anyhow::anyhow;
-use log::{debug, info};
-use std::sync::{Arc, OnceLock};
-use tokio::sync::RwLock;
+use log::{debug, error, info, warn};
+use std::sync::{Arc, Mutex};
+use tokio::sync::{Notify, mpsc};
+use tokio::task::JoinSet;
 use tokio_util::sync::CancellationToken;
 
+/// 监听配置文件变更的检查间隔
+const WATCH_INTERVAL: Duration = Duration::from_secs(2);
+
 /**
  * ServiceManager
  */
 pub struct ServiceManager {
-    settings: OnceLock<Settings>,
+    /// 当前生效的配置
+    settings: Mutex<Settings>,
     parent_token: CancellationToken,
-    api_service: Arc<RwLock<ApiService>>,
+    supervisor: Supervisor,
+    api_service: Arc<ApiService>,
+    notifications: NotificationService,
+    channels: ChannelService,
+    pollers: Vec<Arc<BuildPoller>>,
+    compactor: Arc<HistoryCompactor>,
+    member_syncs: Vec<Arc<MemberSync>>,
+    delivery_worker: Arc<DeliveryWorker>,
+    health: HealthService,
 }
 
 impl ServiceManager {
     pub async fn new(settings: Settings) -> Result<Self, anyhow::Error> {
         debug!("server args: {:?}", settings.clone());
         let parent_token = CancellationToken::new();
-        let api_service = ApiService::new(parent_token.clone(), settings.api().clone())?;
+        let database = settings.database();
+        let pool = repository::connect(database.url().expose(), *database.pool_size()).await?;
+        let access = AccessService::new(ProjectMemberRepository::new(pool.clone()));
+        let events = EventBus::new(*settings.events().buffer_size());
+        let history = BuildHistoryService::new(
+            BuildRepository::new(pool.clone()),
+            settings.history().clone(),
+            events.clone(),
+        );
+        let clients = DevOpsClients::new(settings.devops());
+        let pipelines = PipelineService::new(
+            PipelineRepository::new(pool.clone()),
+            history.clone(),
+            clients.clone(),
+        );
+        let channels = ChannelService::new(
+            ChannelRepository::new(pool.clone()),
+            settings
+                .channels()
+                .encryption_key()
+                .as_ref()
+                .map(Secret::expose),
+            ChannelClient::new(settings.channels().allowed_base_urls().clone()),
+        )?;
+        let supervisor = Supervisor::new();
+        let health = HealthService::new(
+            HealthRepository::new(pool.clone()),
+            DeliveryRepository::new(pool.clone()),
+            clients.clone(),
+            supervisor.clone(),
+            settings.health().clone(),
+        );
+        // 写入发件箱后唤醒投递任务
+        let wakeup = Arc::new(Notify::new());
+        let notifications = NotificationService::new(
+            NotificationRepository::new(pool.clone()),
+            DeliveryRepository::new(pool.clone()),
+            settings.notifications(),
+            wakeup.clone(),
+            events.clone(),
+        );
+        let services = ApiServices {
+            api_keys: ApiKeyService::new(ApiKeyRepository::new(pool.clone())),
+            access: access.clone(),
+            subscriptions: SubscriptionService::new(SubscriptionRepository::new(pool.clone())),
+            channels: channels.clone(),
+            pipelines: pipelines.clone(),
+            history: history.clone(),
+            notifications: notifications.clone(),
+            events: events.clone(),
+            health: health.clone(),
+        };
+        let api_service = ApiService::new(
+            parent_token.child_token(),
+            settings.api().clone(),
+            settings.events().clone(),
+            services,
+        )?;
+
+        // 每个 DevOps 实例独立轮询和同步成员
+        let member_syncs = clients
+            .all()
+            .iter()
+            .zip(settings.devops())
+            .filter(|(_, source)| *source.sync_members())
+            .map(|(client, source)| {
+                Arc::new(MemberSync::new(
+                    parent_token.child_token(),
+                    client.clone(),
+                    access.clone(),
+                    source.projects().clone(),
+                    *source.member_sync_interval(),
+                ))
+            })
+            .collect();
+        let pollers = settings
+            .devops()
+            .iter()
+            .map(|source| {
+                Arc::new(BuildPoller::new(
+                    parent_token.child_token(),
+                    pipelines.clone(),
+                    source.name().clone(),
+                    source.projects().clone(),
+                    *source.poll_interval(),
+                ))
+            })
+            .collect();
+        let delivery_worker = DeliveryWorker::new(
+            parent_token.child_token(),
+            NotificationRepository::new(pool.clone()),
+            DeliveryRepository::new(pool),
+            channels.clone(),
+            settings.notifications().delivery().clone(),
+            wakeup,
+            events,
+        );
+        let compactor = HistoryCompactor::new(
+            parent_token.child_token(),
+            history,
+            *settings.history().compaction_interval(),
+        );
 
         Ok(Self {
-            settings: OnceLock::from(settings),
+            settings: Mutex::new(settings),
             parent_token,
-            api_service: Arc::new(RwLock::new(api_service)),
+            supervisor,
+            api_service: Arc::new(api_service),
+            notifications,
+            channels,
+            pollers,
+            compactor: Arc::new(compactor),
+            member_syncs,
+            delivery_worker: Arc::new(delivery_worker),
+            health,
         })
     }
 
+    /**
+     * 启动并监督后台服务, 接口和投递为关键服务
+     */
     pub fn start(&self) -> Result<(), anyhow::Error> {
-        self.start_api_service()?;
+        let options = self.settings().supervisor().clone();
+        let critical = RestartPolicy::new(&options, true);
+        let optional = RestartPolicy::new(&options, false);
+        self.supervisor
+            .spawn(self.api_service.clone(), critical.clone())?;
+        for poller in self.pollers.iter() {
+            self.supervisor.spawn(poller.clone(), optional.clone())?;
+        }
+        self.supervisor
+            .spawn(self.compactor.clone(), optional.clone())?;
+        self.supervisor
+            .spawn(self.delivery_worker.clone(), critical)?;
+        for member_sync in self.member_syncs.iter() {
+            self.supervisor
+                .spawn(member_sync.clone(), optional.clone())?;
+        }
         Ok(())
     }
 
-    pub fn stop(&self) -> Result<(), anyhow::Error> {
-        info!("Stopping ServerManager gracefully");
-        self.stop_api_service()?;
+    /**
+     * 优雅停机: 停止接收事件 -> 等待接口请求结束 -> 清空到期投递 -> 取消全部任务;
+     * 整个过程不超过 shutdown.timeout
+     */
+    pub async fn stop(&self) -> Result<(), anyhow::Error> {
+        let timeout = Duration::from_secs(*self.settings().shutdown().timeout());
+        info!("Stopping ServerManager gracefully, timeout {:?}", timeout);
+        let started = Instant::now();
+        let deadline = started + timeout;
+        let remaining = || deadline.saturating_duration_since(Instant::now());
+
+        let phase = Instant::now();
+        // 先让就绪检查失败, 负载均衡摘除后不再有新流量
+        self.health.drain();
+        let sources = self
+            .pollers
+            .iter()
+            .map(|poller| poller.name())
+            .chain(self.member_syncs.iter().map(|sync| sync.name()))
+            .chain([self.compactor.name()])
+            .collect();
+        let stopped = self.shutdown_services(sources, remaining()).await?;
+        log_phase("stop accepting events", phase, stopped);
+
+        let phase = Instant::now();
+        let finished = self
+            .shutdown_services(vec![self.api_service.name()], remaining())
+            .await?;
+        log_phase("finish api requests", phase, finished);
+
+        let phase = Instant::now();
+        let drained = self
+            .shutdown_services(vec![self.delivery_worker.name()], remaining())
+            .await?;
+        if !drained {
+            self.delivery_worker.requeue().await?;
+        }
+        log_phase("flush deliveries", phase, drained);
+
+        let phase = Instant::now();
+        self.parent_token.cancel();
+        log_phase("cancel tasks", phase, true);
+        info!("ServerManager stopped in {:?}", started.elapsed());
         Ok(())
     }
 
-    pub fn stop_force(&self) -> Result<(), anyhow::Error> {
+    /**
+     * 立即停止, 正在发送的投递重新排队
+     */
+    pub async fn stop_force(&self) -> Result<(), anyhow::Error> {
         info!("Stopping ServerManager force");
         self.parent_token.cancel();
-        Ok(())
+        self.shutdown_services(self.supervisor.names(), Duration::ZERO)
+            .await?;
+        self.delivery_worker.requeue().await
+    }
+
+    /**
+     * 关键服务无法恢复时完成, 返回失败原因
+     */
+    pub async fn failed(&self) -> String {
+        self.supervisor.failed().await;
+        self.supervisor.failure().unwrap_or_default()
     }
 
-    fn start_api_service(&self) -> Result<(), anyhow::Error> {
-        let api_service = self.api_service.clone();
-        let guard = api_service.try_write();
-        match guard {
-            Ok(guard) => guard.start(),
-            Err(err) => Err(anyhow!(err)),
+    /**
+     * 同时停止一组服务, 返回是否全部在期限内退出
+     */
+    async fn shutdown_services(
+        &self,
+        names: Vec<String>,
+        timeout: Duration,
+    ) -> Result<bool, anyhow::Error> {
+        let mut tasks = JoinSet::new();
+        for name in names {
+            let supervisor = self.supervisor.clone();
+            tasks.spawn(async move { supervisor.shutdown(&name, timeout).await });
+        }
+        let mut finished = true;
+        while let Some(result) = tasks.join_next().await {
+            finished &= result??;
         }
+        Ok(finished)
+    }
+
+    fn settings(&self) -> Settings {
+        self.settings
+            .lock()
+            .unwrap_or_else(|err| err.into_inner())
+            .clone()
     }
 
-    fn stop_api_service(&self) -> Result<(), anyhow::Error> {
-        let api_service = self.api_service.clone();
-        let guard = api_service.try_write();
-        match guard {
-            Ok(service) => service.stop(),
-            Err(err) => Err(anyhow!(err)),
+    /**
+     * 重新加载配置文件, 校验失败时保留当前配置; 只应用运行时可生效的变更, 其余变更提示重启
+     */
+    pub fn reload(&self, path: &str) -> Result<(), anyhow::Error> {
+        let settings = Settings::new(path.to_string())?;
+        settings.validate()?;
+        let mut current = self.settings.lock().unwrap_or_else(|err| err.into_inner());
+        let (applied, restart): (Vec<_>, Vec<_>) = current
+            .changed_keys(&settings)
+            .into_iter()
+            .partition(|key| Settings::is_reloadable(key));
+        if applied.is_empty() && restart.is_empty() {
+            info!("configuration {} unchanged", path);
+            return Ok(());
         }
+        let merged = current.merge_reloadable(&settings);
+        // 日志配置最先应用, 失败时其他服务仍保持当前配置
+        if applied.contains(&"log.level") || applied.contains(&"log.format") {
+            logging::configure(*merged.log().format(), merged.log().level())?;
+        }
+        if applied.contains(&"devops.projects") {
+            let projects_of = |name: &str| {
+                merged
+                    .devops()
+                    .iter()
+                    .find(|source| source.name() == name)
+                    .map(|source| source.projects().clone())
+            };
+            for poller in self.pollers.iter() {
+                if let Some(projects) = projects_of(poller.source()) {
+                    poller.set_projects(projects);
+                }
+            }
+            for member_sync in self.member_syncs.iter() {
+                if let Some(projects) = projects_of(member_sync.source()) {
+                    member_sync.set_projects(projects);
+                }
+            }
+        }
+        if applied.contains(&"api.rate_limit") {
+            self.api_service
+                .set_rate_limit(merged.api().rate_limit.clone());
+        }
+        if applied.contains(&"notifications.templates") {
+            self.notifications
+                .set_templates(merged.notifications().templates().clone());
+        }
+        if applied.contains(&"channels.allowed_base_urls") {
+            self.channels
+                .set_allowed_base_urls(merged.channels().allowed_base_urls().clone());
+        }
+        if applied
+            .iter()
+            .any(|key| key.starts_with("notifications.delivery."))
+        {
+            self.delivery_worker
+                .set_options(merged.notifications().delivery().clone());
+        }
+        *current = merged;
+        if !applied.is_empty() {
+            info!("configuration {} reloaded, applied {:?}", path, applied);
+        }
+        if !restart.is_empty() {
+            warn!(
+                "configuration {} changes {:?} require a restart to take effect",
+                path, restart
+            );
+        }
+        Ok(())
     }
 }
 
@@ -88,6 +384,38 @@ pub struct AppCli {
 pub enum SubCommands {
     /// Start the server
     Start(StartServerArgs),
+    /// Configuration utilities
+    #[command(subcommand)]
+    Config(ConfigCommands),
+    /// Database migrations
+    #[command(subcommand)]
+    Migrate(cli::MigrateCommands),
+    /// DevOps pipelines
+    #[command(subcommand)]
+    Pipelines(cli::PipelineCommands),
+    /// Notification subscriptions
+    #[command(subcommand)]
+    Subscriptions(cli::SubscriptionCommands),
+    /// Notification channels
+    #[command(subcommand)]
+    Channels(cli::ChannelCommands),
+    /// Send a message through a channel
+    Send(cli::SendArgs),
+    /// Print the OpenAPI specification
+    Openapi,
+}
+
+#[derive(Subcommand)]
+pub enum ConfigCommands {
+    /// Validate the configuration without starting the server
+    Check(ConfigArgs),
+}
+
+#[derive(Args, Debug)]
+pub struct ConfigArgs {
+    /// Configuration file path
+    #[arg(short, long, default_value = "conf/settings.toml")]
+    pub path: String,
 }
 
 #[derive(Args, Debug)]
@@ -95,14 +423,86 @@ pub struct StartServerArgs {
     /// Configuration file path
     #[arg(short, long, default_value = "conf/settings.toml")]
     pub path: String,
+    /// Drain API requests and due deliveries before exiting on Ctrl+C or SIGTERM, bounded by shutdown.timeout
     #[arg(short, long)]
     pub graceful_shutdown: bool,
+    /// Reload the configuration when the file changes, SIGHUP always triggers a reload
+    #[arg(short, long)]
+    pub watch: bool,
+}
+
+/**
+ * 记录停机阶段的耗时
+ */
+fn log_phase(name: &str, started: Instant, completed: bool) {
+    if completed {
+        info!(
+            "shutdown phase '{}' finished in {:?}",
+            name,
+            started.elapsed()
+        );
+    } else {
+        warn!(
+            "shutdown phase '{}' timed out after {:?}",
+            name,
+            started.elapsed()
+        );
+    }
+}
+
+/**
+ * 配置重新加载的触发源: SIGHUP 以及可选的配置文件变更
+ */
+fn reload_triggers(path: &str, watch: bool) -> Result<mpsc::Receiver<&'static str>, anyhow::Error> {
+    // 加载期间的多次触发合并为一次
+    let (sender, receiver) = mpsc::channel(1);
+    #[cfg(unix)]
+    {
+        let mut hangup = signal::unix::signal(signal::unix::SignalKind::hangup())?;
+        let sender = sender.clone();
+        tokio::spawn(async move {
+            while hangup.recv().await.is_some() {
+                let _ = sender.try_send("SIGHUP");
+            }
+        });
+    }
+    if watch {
+        info!("watching configuration {} for changes", path);
+        let path = path.to_string();
+        tokio::spawn(async move {
+            let modified = |path: &str| -> Option<SystemTime> {
+                std::fs::metadata(path)
+                    .and_then(|meta| meta.modified())
+                    .ok()
+            };
+            let mut last = modified(&path);
+            let mut ticker = time::interval(WATCH_INTERVAL);
+            loop {
+                ticker.tick().await;
+                let current = modified(&path);
+                if current != last {
+                    last = current;
+                    if current.is_some()
+                        && sender.try_send("file change").is_err()
+                        && sender.is_closed()
+                    {
+                        break;
+                    }
+                }
+            }
+        });
+    }
+    Ok(receiver)
 }
 
 pub async fn start_server(args: StartServerArgs) -> Result<(), anyhow::Error> {
-    let settings = Settings::new(args.path)?;
+    let settings = Settings::new(args.path.clone())?;
+    settings.validate()?;
+    logging::configure(*settings.log().format(), settings.log().level())?;
+    telemetry::init(settings.telemetry())?;
     let server = ServiceManager::new(settings.clone()).await?;
     server.start()?;
+    let mut reloads = reload_triggers(&args.path, args.watch)?;
 
     let ctrl_c = async {
         signal::ctrl_c()
@@ -120,23 +520,50 @@ pub async fn start_server(args: StartServerArgs) -> Result<(), anyhow::Error> {
 
     #[cfg(not(unix))]
     let terminate = std::future::pending::<()>();
-    let with_graceful_shutdown = args.graceful_shutdown.clone();
-    select! {
-        _ = ctrl_c => {
-            info!("receive ctrl_c to shutting down server");
-            if with_graceful_shutdown {
-                server.stop()?
-            } else {
-                server.stop_force()?
-            }
-        },
-        _ = terminate => {
-            info!("signal handler exited unexpectedly");
-            server.stop_force()?
-        },
+    tokio::pin!(ctrl_c, terminate);
+    let mut failure = None;
+    loop {
+        select! {
+            _ = &mut ctrl_c => {
+                info!("receive ctrl_c to shutting down server");
+                break;
+            },
+            _ = &mut terminate => {
+                info!("receive SIGTERM to shutting down server");
+                break;
+            },
+            reason = server.failed() => {
+                error!("shutting down server, {}", reason);
+                failure = Some(reason);
+                break;
+            },
+            Some(trigger) = reloads.recv() => {
+                info!("reloading configuration {} on {}", args.path, trigger);
+                if let Err(err) = server.reload(&args.path) {
+                    error!("reload configuration failed, keep the current one. {:#}", err);
+                }
+            },
+        }
     }
 
-    Ok(time::sleep(Duration::from_secs(1)).await)
+    if args.graceful_shutdown {
+        server.stop().await?;
+    } else {
+        server.stop_force().await?;
+    }
+    // 导出剩余的 span 会阻塞, 放到阻塞线程中执行
+    let _ = tokio::task::spawn_blocking(telemetry::shutdown).await;
+    match failure {
+        Some(reason) => Err(anyhow!(reason)),
+        None => Ok(()),
+    }
+}
+
+pub fn check_config(args: ConfigArgs) -> Result<(), anyhow::Error> {
+    let settings = Settings::new(args.path.clone())?;
+    settings.validate()?;
+    println!("configuration {} is valid", args.path);
+    Ok(())
 }
 
 pub async fn run_cli() -> Result<(), anyhow::Error> {
@@ -144,11 +571,20 @@ pub async fn run_cli() -> Result<(), anyhow::Error> {
 
     match cli.command {
         Some(SubCommands::Start(start_server_args)) => start_server(start_server_args).await,
+        Some(SubCommands::Config(ConfigCommands::Check(args))) => check_config(args),
+        Some(SubCommands::Migrate(command)) => cli::migrate(command).await,
+        Some(SubCommands::Pipelines(command)) => cli::pipelines(command).await,
+        Some(SubCommands::Subscriptions(command)) => cli::subscriptions(command).await,
+        Some(SubCommands::Channels(command)) => cli::channels(command).await,
+        Some(SubCommands::Send(args)) => cli::send(args).await,
+        Some(SubCommands::Openapi) => cli::openapi(),
         _ => Err(anyhow::Error::msg("not starting server")),
     }
 }
 
 #[tokio::main]
 async fn main() -> Result<(), anyhow::Error> {
+    // 格式和级别由配置中的 log 控制并支持重新加载, RUST_LOG 中的指令优先
+    logging::init();
     run_cli().await
 }
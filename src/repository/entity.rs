@@ -1,24 +1,377 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
+use sqlx::types::Json;
+use utoipa::ToSchema;
 
-#[derive(Debug, FromRow, Serialize, Deserialize)]
-pub struct PipeLineEntity {
+/**
+ * 流水线缓存
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct PipelineEntity {
     pub id: Option<i64>,
+    pub project_id: String,
+    pub pipeline_id: String,
     pub name: String,
-    pub email: String,
-    pub age: Option<u8>,
+    pub description: String,
+    pub creator: String,
+    pub latest_build_num: Option<i32>,
+    pub latest_build_status: Option<String>,
+    pub latest_build_user: Option<String>,
+    pub latest_build_start_time: Option<DateTime<Utc>>,
+    pub latest_build_end_time: Option<DateTime<Utc>>,
+    pub running_build_count: i32,
     pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// 来源 DevOps 实例
+    pub source: String,
 }
 
-impl PipeLineEntity {
-    pub(crate) fn new(id: Option<i64>, name: String, email: String, age: Option<u8>) -> Self {
-        Self {
-            id,
-            name,
-            email,
-            age,
-            created_at: Utc::now(),
-        }
+/**
+ * 构建阶段
+ */
+#[derive(Debug, Clone, Serialize, Deserialize, ToSchema)]
+pub struct BuildStage {
+    pub stage_id: String,
+    pub name: String,
+    pub status: Option<String>,
+    pub elapsed: Option<i64>,
+}
+
+/**
+ * 构建记录
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct BuildEntity {
+    pub id: Option<i64>,
+    pub project_id: String,
+    pub pipeline_id: String,
+    pub build_id: String,
+    pub build_num: i32,
+    pub status: String,
+    pub trigger: String,
+    pub start_user: String,
+    pub start_time: DateTime<Utc>,
+    pub end_time: Option<DateTime<Utc>>,
+    /// 耗时(秒)
+    pub duration: Option<i64>,
+    pub stages: Json<Vec<BuildStage>>,
+    pub aggregated: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// 来源 DevOps 实例
+    pub source: String,
+}
+
+/**
+ * 构建查询条件
+ */
+#[derive(Debug, Clone, Default)]
+pub struct BuildFilter {
+    pub source: Option<String>,
+    pub pipeline_id: Option<String>,
+    pub status: Option<String>,
+    pub start_user: Option<String>,
+    pub start_from: Option<DateTime<Utc>>,
+    pub start_to: Option<DateTime<Utc>>,
+    pub limit: u32,
+}
+
+/**
+ * 构建按天聚合
+ */
+#[allow(unused)]
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct BuildDailyStatEntity {
+    pub source: String,
+    pub project_id: String,
+    pub pipeline_id: String,
+    pub day: NaiveDate,
+    pub total_count: i64,
+    pub succeed_count: i64,
+    pub failed_count: i64,
+    pub canceled_count: i64,
+    pub total_duration: i64,
+    pub max_duration: i64,
+}
+
+/**
+ * API Key
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ApiKeyEntity {
+    pub id: Option<i64>,
+    pub name: String,
+    pub prefix: String,
+    pub key_hash: String,
+    pub scopes: Json<Vec<String>>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub expires_at: Option<DateTime<Utc>>,
+    pub revoked_at: Option<DateTime<Utc>>,
+    pub rotated_at: Option<DateTime<Utc>>,
+    pub last_used_at: Option<DateTime<Utc>>,
+    pub usage_count: i64,
+}
+
+/**
+ * 项目角色, 权限依次递增
+ */
+#[derive(
+    Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum ProjectRole {
+    Viewer,
+    Editor,
+    Admin,
+}
+
+impl std::fmt::Display for ProjectRole {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let role = match self {
+            ProjectRole::Viewer => "viewer",
+            ProjectRole::Editor => "editor",
+            ProjectRole::Admin => "admin",
+        };
+        write!(f, "{}", role)
+    }
+}
+
+/**
+ * 项目成员
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ProjectMemberEntity {
+    pub source: String,
+    pub project_id: String,
+    pub subject: String,
+    pub role: ProjectRole,
+    pub origin: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/**
+ * 通知订阅, pipeline_id 为空时订阅项目下所有流水线
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct SubscriptionEntity {
+    pub id: Option<i64>,
+    pub name: String,
+    pub project_id: String,
+    pub pipeline_id: Option<String>,
+    pub events: Json<Vec<String>>,
+    pub channel_id: i64,
+    pub owner: String,
+    pub tags: Json<Vec<String>>,
+    pub enabled: bool,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// 限定的 DevOps 实例, 为空时不限定
+    pub source: Option<String>,
+}
+
+/**
+ * 订阅查询条件
+ */
+#[derive(Debug, Clone, Default)]
+pub struct SubscriptionFilter {
+    pub project_id: Option<String>,
+    /// 限定在这些项目内, 用于非管理员只查看自己的项目
+    pub project_ids: Option<Vec<String>>,
+    pub pipeline_id: Option<String>,
+    pub channel_id: Option<i64>,
+    pub owner: Option<String>,
+    pub enabled: Option<bool>,
+    pub source: Option<String>,
+}
+
+/**
+ * 渠道类型
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum ChannelKind {
+    Wecom,
+    Dingtalk,
+    Email,
+}
+
+impl std::fmt::Display for ChannelKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let kind = match self {
+            ChannelKind::Wecom => "wecom",
+            ChannelKind::Dingtalk => "dingtalk",
+            ChannelKind::Email => "email",
+        };
+        write!(f, "{}", kind)
+    }
+}
+
+/**
+ * 通知渠道, 敏感字段加密后保存在 secret 中
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct ChannelEntity {
+    pub id: Option<i64>,
+    pub name: String,
+    pub project_id: String,
+    pub kind: ChannelKind,
+    pub config: Json<serde_json::Value>,
+    pub secret: Option<String>,
+    pub secret_fields: Json<Vec<String>>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+}
+
+/**
+ * 通知级别
+ */
+#[derive(
+    Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema,
+)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum Severity {
+    #[default]
+    Info,
+    Warning,
+    Critical,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let severity = match self {
+            Severity::Info => "info",
+            Severity::Warning => "warning",
+            Severity::Critical => "critical",
+        };
+        write!(f, "{}", severity)
     }
 }
+
+/**
+ * 通知, 发件箱中的一条消息
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct NotificationEntity {
+    pub id: Option<i64>,
+    pub project_id: String,
+    pub title: String,
+    /// 渲染后的 markdown 内容
+    pub content: String,
+    pub severity: Severity,
+    /// 来源, 如 manual
+    pub source: String,
+    pub template_id: Option<String>,
+    pub created_by: String,
+    pub created_at: DateTime<Utc>,
+    pub pipeline_id: Option<String>,
+    pub build_id: Option<String>,
+    /// 触发事件, 如构建的 failed
+    pub event: Option<String>,
+}
+
+/**
+ * 投递状态
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, sqlx::Type, ToSchema)]
+#[serde(rename_all = "lowercase")]
+#[sqlx(type_name = "TEXT", rename_all = "lowercase")]
+pub enum DeliveryStatus {
+    Pending,
+    Sending,
+    Succeed,
+    Failed,
+}
+
+/**
+ * 通知在单个渠道上的投递
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct DeliveryEntity {
+    pub id: Option<i64>,
+    pub notification_id: i64,
+    pub channel_id: i64,
+    pub status: DeliveryStatus,
+    pub attempts: i32,
+    pub next_attempt_at: DateTime<Utc>,
+    pub last_error: Option<String>,
+    /// 渠道返回的 HTTP 状态码或 SMTP 应答码
+    pub response_status: Option<i32>,
+    pub response_body: Option<String>,
+    pub delivered_at: Option<DateTime<Utc>>,
+    pub created_at: DateTime<Utc>,
+    pub updated_at: DateTime<Utc>,
+    /// 命中的订阅
+    pub subscription_id: Option<i64>,
+}
+
+/**
+ * 投递目标, subscription_id 为命中的订阅
+ */
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub struct DeliveryTarget {
+    pub channel_id: i64,
+    pub subscription_id: Option<i64>,
+}
+
+/**
+ * 投递尝试审计记录
+ */
+#[derive(Debug, Clone, FromRow, Serialize, Deserialize)]
+pub struct DeliveryAttemptEntity {
+    pub id: Option<i64>,
+    pub delivery_id: i64,
+    pub notification_id: i64,
+    pub project_id: String,
+    pub pipeline_id: Option<String>,
+    pub build_id: Option<String>,
+    pub source: String,
+    pub event: Option<String>,
+    pub subscription_id: Option<i64>,
+    pub channel_id: i64,
+    /// 第几次尝试, 从 1 开始
+    pub attempt: i32,
+    pub status: DeliveryStatus,
+    /// 渲染后消息的 sha256
+    pub payload_hash: String,
+    pub response_status: Option<i32>,
+    pub response_excerpt: Option<String>,
+    pub error: Option<String>,
+    pub latency_ms: i64,
+    pub attempted_at: DateTime<Utc>,
+}
+
+/**
+ * 投递尝试查询条件
+ */
+#[derive(Debug, Clone, Default)]
+pub struct DeliveryAttemptFilter {
+    pub project_id: Option<String>,
+    /// 限定在这些项目内, 用于非管理员只查看自己的项目
+    pub project_ids: Option<Vec<String>>,
+    pub pipeline_id: Option<String>,
+    pub build_id: Option<String>,
+    pub channel_id: Option<i64>,
+    pub status: Option<DeliveryStatus>,
+    pub from: Option<DateTime<Utc>>,
+    pub to: Option<DateTime<Utc>>,
+}
+
+/**
+ * 内嵌的数据库迁移及其执行状态
+ */
+#[derive(Debug, Clone, Serialize)]
+pub struct MigrationEntity {
+    pub version: i64,
+    pub description: String,
+    /// 未执行时为空
+    pub installed_on: Option<DateTime<Utc>>,
+    /// 执行失败时为 false, 未执行时为空
+    pub success: Option<bool>,
+}
@@ -1,9 +1,41 @@
-mod entity;
-mod sqlite;
+pub mod entity;
+pub mod sqlite;
+
+use anyhow::Context;
+use sqlx::SqlitePool;
+use sqlx::migrate::Migrator;
+use sqlx::sqlite::SqlitePoolOptions;
+
+/// 内嵌的数据库迁移脚本
+pub static MIGRATOR: Migrator = sqlx::migrate!();
+
+/**
+ * Create the connection pool without touching migrations
+ */
+pub async fn open(url: &str, pool_size: u32) -> Result<SqlitePool, anyhow::Error> {
+    SqlitePoolOptions::new()
+        .max_connections(pool_size)
+        .connect(url)
+        .await
+        .context("Failed to connect database")
+}
+
+/**
+ * Create the connection pool and apply pending migrations
+ */
+pub async fn connect(url: &str, pool_size: u32) -> Result<SqlitePool, anyhow::Error> {
+    let pool = open(url, pool_size).await?;
+    MIGRATOR
+        .run(&pool)
+        .await
+        .context("Failed to run database migrations")?;
+    Ok(pool)
+}
 
 /**
  * Database Repository
  */
+#[allow(unused)]
 pub trait DatabaseRepository<T, ID> {
     /**
      * Find all entities
@@ -1,11 +1,54 @@
-use crate::repository::DatabaseRepository;
-use crate::repository::entity::PipeLineEntity;
+use crate::repository::entity::{
+    ApiKeyEntity, BuildDailyStatEntity, BuildEntity, BuildFilter, ChannelEntity,
+    DeliveryAttemptEntity, DeliveryAttemptFilter, DeliveryEntity, DeliveryStatus, DeliveryTarget,
+    MigrationEntity, NotificationEntity, PipelineEntity, ProjectMemberEntity, ProjectRole,
+    SubscriptionEntity, SubscriptionFilter,
+};
+use crate::repository::{DatabaseRepository, MIGRATOR};
+use crate::telemetry;
 use anyhow::Context;
-use sqlx::SqlitePool;
+use chrono::{DateTime, NaiveDate, Utc};
+use opentelemetry::KeyValue;
+use opentelemetry::trace::{SpanKind, TraceContextExt};
+use sqlx::{QueryBuilder, Sqlite, SqlitePool};
+use std::collections::HashSet;
+use std::fmt::Display;
 
 /**
- * Repository
+ * Run a statement inside a client span named after the repository method;
+ * statements outside any trace (e.g. periodic polling) are not recorded
  */
+trait Traced<T, E>: Future<Output = Result<T, E>> + Send + Sized
+where
+    T: Send,
+    E: Display + Send,
+{
+    fn traced(self, operation: &'static str) -> impl Future<Output = Result<T, E>> + Send {
+        async move {
+            if !opentelemetry::Context::current().has_active_span() {
+                return self.await;
+            }
+            let attributes = vec![
+                KeyValue::new("db.system.name", "sqlite"),
+                KeyValue::new("code.function.name", operation),
+            ];
+            telemetry::in_span(operation, SpanKind::Client, attributes, self).await
+        }
+    }
+}
+
+impl<F, T, E> Traced<T, E> for F
+where
+    F: Future<Output = Result<T, E>> + Send,
+    T: Send,
+    E: Display + Send,
+{
+}
+
+/**
+ * Pipeline cache repository
+ */
+#[derive(Clone)]
 pub struct PipelineRepository {
     pool: SqlitePool,
 }
@@ -14,39 +57,1500 @@ impl PipelineRepository {
     pub fn new(pool: SqlitePool) -> Self {
         Self { pool }
     }
+
+    /**
+     * Cached pipelines of a project
+     */
+    pub async fn find_by_project(
+        &self,
+        project_id: &str,
+    ) -> Result<Vec<PipelineEntity>, anyhow::Error> {
+        sqlx::query_as::<_, PipelineEntity>(
+            "SELECT * FROM pipelines WHERE project_id = ? ORDER BY name",
+        )
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .traced("PipelineRepository.find_by_project")
+        .await
+        .context("Failed to fetch pipelines")
+    }
+
+    /**
+     * Find pipelines by their DevOps pipeline id, in the source if specified
+     */
+    pub async fn find_by_pipeline_id(
+        &self,
+        source: Option<&str>,
+        pipeline_id: &str,
+    ) -> Result<Vec<PipelineEntity>, anyhow::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM pipelines WHERE pipeline_id = ");
+        query.push_bind(pipeline_id);
+        if let Some(source) = source {
+            query.push(" AND source = ").push_bind(source);
+        }
+        query
+            .push(" ORDER BY source")
+            .build_query_as::<PipelineEntity>()
+            .fetch_all(&self.pool)
+            .traced("PipelineRepository.find_by_pipeline_id")
+            .await
+            .context("Failed to fetch pipeline")
+    }
+
+    /**
+     * Remove cached pipelines of a project in the source which no longer exist
+     */
+    pub async fn delete_missing(
+        &self,
+        source: &str,
+        project_id: &str,
+        pipeline_ids: &[String],
+    ) -> Result<u64, anyhow::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("DELETE FROM pipelines WHERE source = ");
+        query.push_bind(source);
+        query.push(" AND project_id = ").push_bind(project_id);
+        if !pipeline_ids.is_empty() {
+            query.push(" AND pipeline_id NOT IN (");
+            let mut separated = query.separated(", ");
+            for pipeline_id in pipeline_ids {
+                separated.push_bind(pipeline_id);
+            }
+            query.push(")");
+        }
+        let result = query
+            .build()
+            .execute(&self.pool)
+            .traced("PipelineRepository.delete_missing")
+            .await
+            .context("Failed to delete missing pipelines")?;
+        Ok(result.rows_affected())
+    }
+}
+
+impl DatabaseRepository<PipelineEntity, i64> for PipelineRepository {
+    async fn save_or_update(
+        &self,
+        pipeline: PipelineEntity,
+    ) -> Result<PipelineEntity, anyhow::Error> {
+        sqlx::query_as::<_, PipelineEntity>(
+            r#"INSERT INTO pipelines (project_id, pipeline_id, name, description, creator, latest_build_num,
+                                   latest_build_status, latest_build_user, latest_build_start_time,
+                                   latest_build_end_time, running_build_count, created_at, updated_at,
+                                   source)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+               ON CONFLICT (source, project_id, pipeline_id) DO UPDATE SET
+                   name                    = excluded.name,
+                   description             = excluded.description,
+                   creator                 = excluded.creator,
+                   latest_build_num        = excluded.latest_build_num,
+                   latest_build_status     = excluded.latest_build_status,
+                   latest_build_user       = excluded.latest_build_user,
+                   latest_build_start_time = excluded.latest_build_start_time,
+                   latest_build_end_time   = excluded.latest_build_end_time,
+                   running_build_count     = excluded.running_build_count,
+                   updated_at              = excluded.updated_at
+               RETURNING *"#,
+        )
+        .bind(&pipeline.project_id)
+        .bind(&pipeline.pipeline_id)
+        .bind(&pipeline.name)
+        .bind(&pipeline.description)
+        .bind(&pipeline.creator)
+        .bind(pipeline.latest_build_num)
+        .bind(&pipeline.latest_build_status)
+        .bind(&pipeline.latest_build_user)
+        .bind(pipeline.latest_build_start_time)
+        .bind(pipeline.latest_build_end_time)
+        .bind(pipeline.running_build_count)
+        .bind(pipeline.created_at)
+        .bind(pipeline.updated_at)
+        .bind(&pipeline.source)
+        .fetch_one(&self.pool)
+        .traced("PipelineRepository.save_or_update")
+        .await
+        .context("Failed to save pipeline")
+    }
+}
+
+/**
+ * Build history repository
+ */
+#[derive(Clone)]
+pub struct BuildRepository {
+    pool: SqlitePool,
 }
 
-impl DatabaseRepository<PipeLineEntity, i64> for PipelineRepository {
-    async fn find_all(&self) -> Result<Vec<PipeLineEntity>, anyhow::Error> {
-        let query = sqlx::query_as::<_, PipeLineEntity>("SELECT * FROM todos");
+impl BuildRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /**
+     * Latest stored build number of a pipeline
+     */
+    pub async fn latest_build_num(
+        &self,
+        source: &str,
+        project_id: &str,
+        pipeline_id: &str,
+    ) -> Result<Option<i32>, anyhow::Error> {
+        sqlx::query_scalar::<_, Option<i32>>(
+            "SELECT MAX(build_num) FROM build_history WHERE source = ? AND project_id = ? AND pipeline_id = ?",
+        )
+        .bind(source)
+        .bind(project_id)
+        .bind(pipeline_id)
+        .fetch_one(&self.pool)
+        .traced("BuildRepository.latest_build_num")
+        .await
+        .context("Failed to fetch latest build num")
+    }
+
+    /**
+     * Fold finished builds which are not aggregated yet into the daily stats,
+     * re-run builds replace the result counted before (max_duration only grows)
+     */
+    pub async fn aggregate_daily(&self) -> Result<u64, anyhow::Error> {
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            r#"UPDATE build_daily_stats
+               SET total_count    = build_daily_stats.total_count - counted.total_count,
+                   succeed_count  = build_daily_stats.succeed_count - counted.succeed_count,
+                   failed_count   = build_daily_stats.failed_count - counted.failed_count,
+                   canceled_count = build_daily_stats.canceled_count - counted.canceled_count,
+                   total_duration = build_daily_stats.total_duration - counted.total_duration
+               FROM (SELECT source,
+                            project_id,
+                            pipeline_id,
+                            counted_day AS day,
+                            COUNT(*) AS total_count,
+                            SUM(CASE WHEN counted_status = 'SUCCEED' THEN 1 ELSE 0 END) AS succeed_count,
+                            SUM(CASE WHEN counted_status = 'FAILED' THEN 1 ELSE 0 END) AS failed_count,
+                            SUM(CASE WHEN counted_status IN ('CANCELED', 'TERMINATE') THEN 1 ELSE 0 END) AS canceled_count,
+                            COALESCE(SUM(counted_duration), 0) AS total_duration
+                     FROM build_history
+                     WHERE aggregated = FALSE AND end_time IS NOT NULL AND counted_day IS NOT NULL
+                     GROUP BY source, project_id, pipeline_id, counted_day) AS counted
+               WHERE build_daily_stats.source = counted.source
+                 AND build_daily_stats.project_id = counted.project_id
+                 AND build_daily_stats.pipeline_id = counted.pipeline_id
+                 AND build_daily_stats.day = counted.day"#,
+        )
+        .execute(&mut *tx)
+        .traced("BuildRepository.aggregate_daily")
+        .await
+        .context("Failed to subtract re-run builds from daily stats")?;
+        sqlx::query(
+            r#"INSERT INTO build_daily_stats (source, project_id, pipeline_id, day, total_count, succeed_count,
+                                           failed_count, canceled_count, total_duration, max_duration)
+               SELECT source,
+                      project_id,
+                      pipeline_id,
+                      date(start_time),
+                      COUNT(*),
+                      SUM(CASE WHEN status = 'SUCCEED' THEN 1 ELSE 0 END),
+                      SUM(CASE WHEN status = 'FAILED' THEN 1 ELSE 0 END),
+                      SUM(CASE WHEN status IN ('CANCELED', 'TERMINATE') THEN 1 ELSE 0 END),
+                      COALESCE(SUM(duration), 0),
+                      COALESCE(MAX(duration), 0)
+               FROM build_history
+               WHERE aggregated = FALSE AND end_time IS NOT NULL
+               GROUP BY source, project_id, pipeline_id, date(start_time)
+               ON CONFLICT (source, project_id, pipeline_id, day) DO UPDATE SET
+                   total_count    = total_count + excluded.total_count,
+                   succeed_count  = succeed_count + excluded.succeed_count,
+                   failed_count   = failed_count + excluded.failed_count,
+                   canceled_count = canceled_count + excluded.canceled_count,
+                   total_duration = total_duration + excluded.total_duration,
+                   max_duration   = MAX(max_duration, excluded.max_duration)"#,
+        )
+        .execute(&mut *tx)
+        .traced("BuildRepository.aggregate_daily")
+        .await
+        .context("Failed to aggregate daily stats")?;
+        let result = sqlx::query(
+            r#"UPDATE build_history
+               SET aggregated       = TRUE,
+                   counted_day      = date(start_time),
+                   counted_status   = status,
+                   counted_duration = duration
+               WHERE aggregated = FALSE AND end_time IS NOT NULL"#,
+        )
+        .execute(&mut *tx)
+        .traced("BuildRepository.aggregate_daily")
+        .await
+        .context("Failed to mark builds aggregated")?;
+        tx.commit().await?;
+        Ok(result.rows_affected())
+    }
+
+    /**
+     * Delete aggregated builds started before the cutoff, running builds are kept until counted
+     */
+    pub async fn purge_before(&self, cutoff: DateTime<Utc>) -> Result<u64, anyhow::Error> {
+        let result = sqlx::query(
+            r#"DELETE FROM build_history
+               WHERE julianday(start_time) < julianday(?) AND aggregated = TRUE"#,
+        )
+        .bind(cutoff)
+        .execute(&self.pool)
+        .traced("BuildRepository.purge_before")
+        .await
+        .context("Failed to purge expired builds")?;
+        Ok(result.rows_affected())
+    }
+
+    /**
+     * Keep only the latest `keep` builds of every pipeline, builds not aggregated yet are kept
+     */
+    pub async fn purge_exceeding(&self, keep: u32) -> Result<u64, anyhow::Error> {
+        let result = sqlx::query(
+            r#"DELETE FROM build_history
+               WHERE id IN (SELECT id
+                            FROM (SELECT id,
+                                         ROW_NUMBER() OVER (
+                                             PARTITION BY source, project_id, pipeline_id
+                                             ORDER BY build_num DESC) AS rn
+                                  FROM build_history)
+                            WHERE rn > ?)
+                 AND aggregated = TRUE"#,
+        )
+        .bind(keep)
+        .execute(&self.pool)
+        .traced("BuildRepository.purge_exceeding")
+        .await
+        .context("Failed to purge exceeding builds")?;
+        Ok(result.rows_affected())
+    }
+
+    /**
+     * Builds matching the filter, latest first
+     */
+    pub async fn find_by(&self, filter: &BuildFilter) -> Result<Vec<BuildEntity>, anyhow::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM build_history WHERE 1 = 1");
+        if let Some(source) = &filter.source {
+            query.push(" AND source = ").push_bind(source);
+        }
+        if let Some(pipeline_id) = &filter.pipeline_id {
+            query.push(" AND pipeline_id = ").push_bind(pipeline_id);
+        }
+        if let Some(status) = &filter.status {
+            query.push(" AND status = ").push_bind(status);
+        }
+        if let Some(start_user) = &filter.start_user {
+            query.push(" AND start_user = ").push_bind(start_user);
+        }
+        if let Some(start_from) = filter.start_from {
+            query
+                .push(" AND julianday(start_time) >= julianday(")
+                .push_bind(start_from)
+                .push(")");
+        }
+        if let Some(start_to) = filter.start_to {
+            query
+                .push(" AND julianday(start_time) < julianday(")
+                .push_bind(start_to)
+                .push(")");
+        }
+        query
+            .push(" ORDER BY build_num DESC LIMIT ")
+            .push_bind(filter.limit);
         query
+            .build_query_as::<BuildEntity>()
             .fetch_all(&self.pool)
+            .traced("BuildRepository.find_by")
             .await
-            .context("Failed to fetch todos")
+            .context("Failed to fetch builds")
     }
 
-    async fn find_by_id(&self, id: i64) -> Result<Option<PipeLineEntity>, anyhow::Error> {
-        let query =
-            sqlx::query_as::<_, PipeLineEntity>("SELECT * FROM todos WHERE id = ?").bind(id);
+    /**
+     * Find builds by their DevOps build id, in the source if specified
+     */
+    pub async fn find_by_build_id(
+        &self,
+        source: Option<&str>,
+        build_id: &str,
+    ) -> Result<Vec<BuildEntity>, anyhow::Error> {
+        let mut query =
+            QueryBuilder::<Sqlite>::new("SELECT * FROM build_history WHERE build_id = ");
+        query.push_bind(build_id);
+        if let Some(source) = source {
+            query.push(" AND source = ").push_bind(source);
+        }
         query
+            .push(" ORDER BY source")
+            .build_query_as::<BuildEntity>()
+            .fetch_all(&self.pool)
+            .traced("BuildRepository.find_by_build_id")
+            .await
+            .context("Failed to fetch build")
+    }
+
+    /**
+     * Find a build by its unique key
+     */
+    pub async fn find_by_key(
+        &self,
+        source: &str,
+        project_id: &str,
+        pipeline_id: &str,
+        build_id: &str,
+    ) -> Result<Option<BuildEntity>, anyhow::Error> {
+        sqlx::query_as::<_, BuildEntity>(
+            r#"SELECT * FROM build_history
+               WHERE source = ? AND project_id = ? AND pipeline_id = ? AND build_id = ?"#,
+        )
+        .bind(source)
+        .bind(project_id)
+        .bind(pipeline_id)
+        .bind(build_id)
+        .fetch_optional(&self.pool)
+        .traced("BuildRepository.find_by_key")
+        .await
+        .context("Failed to fetch build")
+    }
+
+    /**
+     * Daily stats of a pipeline in the given day range
+     */
+    #[allow(unused)]
+    pub async fn find_daily_stats(
+        &self,
+        source: &str,
+        project_id: &str,
+        pipeline_id: &str,
+        from: NaiveDate,
+        to: NaiveDate,
+    ) -> Result<Vec<BuildDailyStatEntity>, anyhow::Error> {
+        sqlx::query_as::<_, BuildDailyStatEntity>(
+            r#"SELECT * FROM build_daily_stats
+               WHERE source = ? AND project_id = ? AND pipeline_id = ? AND day BETWEEN ? AND ?
+               ORDER BY day"#,
+        )
+        .bind(source)
+        .bind(project_id)
+        .bind(pipeline_id)
+        .bind(from)
+        .bind(to)
+        .fetch_all(&self.pool)
+        .traced("BuildRepository.find_daily_stats")
+        .await
+        .context("Failed to fetch daily stats")
+    }
+}
+
+impl DatabaseRepository<BuildEntity, i64> for BuildRepository {
+    async fn find_by_id(&self, id: i64) -> Result<Option<BuildEntity>, anyhow::Error> {
+        sqlx::query_as::<_, BuildEntity>("SELECT * FROM build_history WHERE id = ?")
+            .bind(id)
             .fetch_optional(&self.pool)
+            .traced("BuildRepository.find_by_id")
             .await
-            .context("Failed to fetch todo")
+            .context("Failed to fetch build")
     }
 
-    async fn save(&self, todo: PipeLineEntity) -> Result<PipeLineEntity, anyhow::Error> {
-        let query = sqlx::query("INSERT INTO todos (title, completed) VALUES (?, ?)")
-            .bind(todo.name.clone())
-            .bind(todo.email.clone());
-        let result = query
+    async fn save_or_update(&self, build: BuildEntity) -> Result<BuildEntity, anyhow::Error> {
+        sqlx::query_as::<_, BuildEntity>(
+            r#"INSERT INTO build_history (project_id, pipeline_id, build_id, build_num, status, trigger,
+                                       start_user, start_time, end_time, duration, stages,
+                                       aggregated, created_at, updated_at, source)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, FALSE, ?, ?, ?)
+               ON CONFLICT (source, project_id, pipeline_id, build_id) DO UPDATE SET
+                   aggregated = CASE
+                                    WHEN build_history.status <> excluded.status
+                                        OR build_history.end_time IS NOT excluded.end_time
+                                        THEN FALSE
+                                    ELSE build_history.aggregated
+                                END,
+                   status     = excluded.status,
+                   end_time   = excluded.end_time,
+                   duration   = excluded.duration,
+                   stages     = excluded.stages,
+                   updated_at = excluded.updated_at
+               RETURNING *"#,
+        )
+        .bind(&build.project_id)
+        .bind(&build.pipeline_id)
+        .bind(&build.build_id)
+        .bind(build.build_num)
+        .bind(&build.status)
+        .bind(&build.trigger)
+        .bind(&build.start_user)
+        .bind(build.start_time)
+        .bind(build.end_time)
+        .bind(build.duration)
+        .bind(&build.stages)
+        .bind(build.created_at)
+        .bind(build.updated_at)
+        .bind(&build.source)
+        .fetch_one(&self.pool)
+        .traced("BuildRepository.save_or_update")
+        .await
+        .context("Failed to save build")
+    }
+}
+
+/**
+ * API key repository
+ */
+#[derive(Clone)]
+pub struct ApiKeyRepository {
+    pool: SqlitePool,
+}
+
+impl ApiKeyRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /**
+     * Find an active key by its hash
+     */
+    pub async fn find_active_by_hash(
+        &self,
+        key_hash: &str,
+    ) -> Result<Option<ApiKeyEntity>, anyhow::Error> {
+        sqlx::query_as::<_, ApiKeyEntity>(
+            r#"SELECT * FROM api_keys
+               WHERE key_hash = ?
+                 AND revoked_at IS NULL
+                 AND (expires_at IS NULL OR julianday(expires_at) > julianday(?))"#,
+        )
+        .bind(key_hash)
+        .bind(Utc::now())
+        .fetch_optional(&self.pool)
+        .traced("ApiKeyRepository.find_active_by_hash")
+        .await
+        .context("Failed to fetch api key")
+    }
+
+    /**
+     * Record a use of the key
+     */
+    pub async fn touch(&self, id: i64) -> Result<(), anyhow::Error> {
+        sqlx::query(
+            "UPDATE api_keys SET usage_count = usage_count + 1, last_used_at = ? WHERE id = ?",
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .execute(&self.pool)
+        .traced("ApiKeyRepository.touch")
+        .await
+        .context("Failed to touch api key")?;
+        Ok(())
+    }
+
+    /**
+     * Mark the key revoked
+     */
+    pub async fn revoke(&self, id: i64) -> Result<Option<ApiKeyEntity>, anyhow::Error> {
+        sqlx::query_as::<_, ApiKeyEntity>(
+            "UPDATE api_keys SET revoked_at = ? WHERE id = ? AND revoked_at IS NULL RETURNING *",
+        )
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .traced("ApiKeyRepository.revoke")
+        .await
+        .context("Failed to revoke api key")
+    }
+
+    /**
+     * Replace the secret of an active key
+     */
+    pub async fn rotate(
+        &self,
+        id: i64,
+        prefix: &str,
+        key_hash: &str,
+    ) -> Result<Option<ApiKeyEntity>, anyhow::Error> {
+        sqlx::query_as::<_, ApiKeyEntity>(
+            r#"UPDATE api_keys SET prefix = ?, key_hash = ?, rotated_at = ?
+               WHERE id = ? AND revoked_at IS NULL
+               RETURNING *"#,
+        )
+        .bind(prefix)
+        .bind(key_hash)
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .traced("ApiKeyRepository.rotate")
+        .await
+        .context("Failed to rotate api key")
+    }
+}
+
+impl DatabaseRepository<ApiKeyEntity, i64> for ApiKeyRepository {
+    async fn find_all(&self) -> Result<Vec<ApiKeyEntity>, anyhow::Error> {
+        sqlx::query_as::<_, ApiKeyEntity>("SELECT * FROM api_keys ORDER BY id")
+            .fetch_all(&self.pool)
+            .traced("ApiKeyRepository.find_all")
+            .await
+            .context("Failed to fetch api keys")
+    }
+
+    async fn find_by_id(&self, id: i64) -> Result<Option<ApiKeyEntity>, anyhow::Error> {
+        sqlx::query_as::<_, ApiKeyEntity>("SELECT * FROM api_keys WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .traced("ApiKeyRepository.find_by_id")
+            .await
+            .context("Failed to fetch api key")
+    }
+
+    async fn save(&self, key: ApiKeyEntity) -> Result<ApiKeyEntity, anyhow::Error> {
+        sqlx::query_as::<_, ApiKeyEntity>(
+            r#"INSERT INTO api_keys (name, prefix, key_hash, scopes, created_by, created_at, expires_at)
+               VALUES (?, ?, ?, ?, ?, ?, ?)
+               RETURNING *"#,
+        )
+        .bind(&key.name)
+        .bind(&key.prefix)
+        .bind(&key.key_hash)
+        .bind(&key.scopes)
+        .bind(&key.created_by)
+        .bind(key.created_at)
+        .bind(key.expires_at)
+        .fetch_one(&self.pool)
+        .traced("ApiKeyRepository.save")
+        .await
+        .context("Failed to insert api key")
+    }
+}
+
+/**
+ * Project member repository
+ */
+#[derive(Clone)]
+pub struct ProjectMemberRepository {
+    pool: SqlitePool,
+}
+
+impl ProjectMemberRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find_role(
+        &self,
+        source: &str,
+        project_id: &str,
+        subject: &str,
+    ) -> Result<Option<ProjectRole>, anyhow::Error> {
+        sqlx::query_scalar::<_, ProjectRole>(
+            "SELECT role FROM project_members WHERE source = ? AND project_id = ? AND subject = ?",
+        )
+        .bind(source)
+        .bind(project_id)
+        .bind(subject)
+        .fetch_optional(&self.pool)
+        .traced("ProjectMemberRepository.find_role")
+        .await
+        .context("Failed to fetch project role")
+    }
+
+    pub async fn find_by_project(
+        &self,
+        source: &str,
+        project_id: &str,
+    ) -> Result<Vec<ProjectMemberEntity>, anyhow::Error> {
+        sqlx::query_as::<_, ProjectMemberEntity>(
+            "SELECT * FROM project_members WHERE source = ? AND project_id = ? ORDER BY subject",
+        )
+        .bind(source)
+        .bind(project_id)
+        .fetch_all(&self.pool)
+        .traced("ProjectMemberRepository.find_by_project")
+        .await
+        .context("Failed to fetch project members")
+    }
+
+    /**
+     * Projects the subject is a member of, in the source if specified
+     */
+    pub async fn find_projects(
+        &self,
+        source: Option<&str>,
+        subject: &str,
+    ) -> Result<Vec<String>, anyhow::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new(
+            "SELECT DISTINCT project_id FROM project_members WHERE subject = ",
+        );
+        query.push_bind(subject);
+        if let Some(source) = source {
+            query.push(" AND source = ").push_bind(source);
+        }
+        query
+            .build_query_scalar::<String>()
+            .fetch_all(&self.pool)
+            .traced("ProjectMemberRepository.find_projects")
+            .await
+            .context("Failed to fetch member projects")
+    }
+
+    pub async fn delete(
+        &self,
+        source: &str,
+        project_id: &str,
+        subject: &str,
+    ) -> Result<bool, anyhow::Error> {
+        let result = sqlx::query(
+            "DELETE FROM project_members WHERE source = ? AND project_id = ? AND subject = ?",
+        )
+        .bind(source)
+        .bind(project_id)
+        .bind(subject)
+        .execute(&self.pool)
+        .traced("ProjectMemberRepository.delete")
+        .await
+        .context("Failed to delete project member")?;
+        Ok(result.rows_affected() > 0)
+    }
+
+    /**
+     * Replace the members synced from a DevOps source, manual members are kept untouched
+     */
+    pub async fn replace_synced(
+        &self,
+        source: &str,
+        project_id: &str,
+        members: Vec<(String, ProjectRole)>,
+    ) -> Result<(), anyhow::Error> {
+        let now = Utc::now();
+        let mut tx = self.pool.begin().await?;
+        sqlx::query(
+            "DELETE FROM project_members WHERE source = ? AND project_id = ? AND origin = 'devops'",
+        )
+        .bind(source)
+        .bind(project_id)
+        .execute(&mut *tx)
+        .traced("ProjectMemberRepository.replace_synced")
+        .await
+        .context("Failed to clear synced members")?;
+        for (subject, role) in members {
+            sqlx::query(
+                r#"INSERT INTO project_members (source, project_id, subject, role, origin, created_at, updated_at)
+                   VALUES (?, ?, ?, ?, 'devops', ?, ?)
+                   ON CONFLICT (source, project_id, subject) DO NOTHING"#,
+            )
+            .bind(source)
+            .bind(project_id)
+            .bind(subject)
+            .bind(role)
+            .bind(now)
+            .bind(now)
+            .execute(&mut *tx)
+            .traced("ProjectMemberRepository.replace_synced")
+            .await
+            .context("Failed to insert synced member")?;
+        }
+        tx.commit().await?;
+        Ok(())
+    }
+}
+
+impl DatabaseRepository<ProjectMemberEntity, (String, String, String)> for ProjectMemberRepository {
+    async fn save_or_update(
+        &self,
+        member: ProjectMemberEntity,
+    ) -> Result<ProjectMemberEntity, anyhow::Error> {
+        sqlx::query_as::<_, ProjectMemberEntity>(
+            r#"INSERT INTO project_members (source, project_id, subject, role, origin, created_at, updated_at)
+               VALUES (?, ?, ?, ?, ?, ?, ?)
+               ON CONFLICT (source, project_id, subject) DO UPDATE SET
+                   role       = excluded.role,
+                   origin     = excluded.origin,
+                   updated_at = excluded.updated_at
+               RETURNING *"#,
+        )
+        .bind(&member.source)
+        .bind(&member.project_id)
+        .bind(&member.subject)
+        .bind(member.role)
+        .bind(&member.origin)
+        .bind(member.created_at)
+        .bind(member.updated_at)
+        .fetch_one(&self.pool)
+        .traced("ProjectMemberRepository.save_or_update")
+        .await
+        .context("Failed to save project member")
+    }
+}
+
+/**
+ * Subscription repository
+ */
+#[derive(Clone)]
+pub struct SubscriptionRepository {
+    pool: SqlitePool,
+}
+
+impl SubscriptionRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn find_by(
+        &self,
+        filter: &SubscriptionFilter,
+    ) -> Result<Vec<SubscriptionEntity>, anyhow::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM subscriptions WHERE 1 = 1");
+        if let Some(project_id) = &filter.project_id {
+            query.push(" AND project_id = ").push_bind(project_id);
+        }
+        if let Some(project_ids) = &filter.project_ids {
+            if project_ids.is_empty() {
+                return Ok(vec![]);
+            }
+            query.push(" AND project_id IN (");
+            let mut separated = query.separated(", ");
+            for project_id in project_ids {
+                separated.push_bind(project_id);
+            }
+            query.push(")");
+        }
+        if let Some(pipeline_id) = &filter.pipeline_id {
+            query.push(" AND pipeline_id = ").push_bind(pipeline_id);
+        }
+        if let Some(channel_id) = filter.channel_id {
+            query.push(" AND channel_id = ").push_bind(channel_id);
+        }
+        if let Some(owner) = &filter.owner {
+            query.push(" AND owner = ").push_bind(owner);
+        }
+        if let Some(enabled) = filter.enabled {
+            query.push(" AND enabled = ").push_bind(enabled);
+        }
+        if let Some(source) = &filter.source {
+            query.push(" AND source = ").push_bind(source);
+        }
+        query.push(" ORDER BY id");
+        query
+            .build_query_as::<SubscriptionEntity>()
+            .fetch_all(&self.pool)
+            .traced("SubscriptionRepository.find_by")
+            .await
+            .context("Failed to fetch subscriptions")
+    }
+
+    /**
+     * Delivery targets of enabled subscriptions in the project carrying any of the tags,
+     * one per channel. Subscriptions bound to another source are skipped when the source is specified
+     */
+    pub async fn find_targets_by_tags(
+        &self,
+        source: Option<&str>,
+        project_id: &str,
+        tags: &[String],
+    ) -> Result<Vec<DeliveryTarget>, anyhow::Error> {
+        if tags.is_empty() {
+            return Ok(vec![]);
+        }
+        let mut query = QueryBuilder::<Sqlite>::new(
+            r#"SELECT s.channel_id, MIN(s.id)
+               FROM subscriptions s, json_each(s.tags) t
+               WHERE s.enabled = TRUE AND s.project_id = "#,
+        );
+        query.push_bind(project_id);
+        if let Some(source) = source {
+            query
+                .push(" AND (s.source IS NULL OR s.source = ")
+                .push_bind(source)
+                .push(")");
+        }
+        query.push(" AND t.value IN (");
+        let mut separated = query.separated(", ");
+        for tag in tags {
+            separated.push_bind(tag);
+        }
+        query.push(") GROUP BY s.channel_id ORDER BY s.channel_id");
+        let rows = query
+            .build_query_as::<(i64, i64)>()
+            .fetch_all(&self.pool)
+            .traced("SubscriptionRepository.find_targets_by_tags")
+            .await
+            .context("Failed to fetch channels by tags")?;
+        Ok(rows
+            .into_iter()
+            .map(|(channel_id, subscription_id)| DeliveryTarget {
+                channel_id,
+                subscription_id: Some(subscription_id),
+            })
+            .collect())
+    }
+
+    pub async fn set_enabled(
+        &self,
+        id: i64,
+        enabled: bool,
+    ) -> Result<Option<SubscriptionEntity>, anyhow::Error> {
+        sqlx::query_as::<_, SubscriptionEntity>(
+            "UPDATE subscriptions SET enabled = ?, updated_at = ? WHERE id = ? RETURNING *",
+        )
+        .bind(enabled)
+        .bind(Utc::now())
+        .bind(id)
+        .fetch_optional(&self.pool)
+        .traced("SubscriptionRepository.set_enabled")
+        .await
+        .context("Failed to update subscription state")
+    }
+}
+
+impl DatabaseRepository<SubscriptionEntity, i64> for SubscriptionRepository {
+    async fn find_by_id(&self, id: i64) -> Result<Option<SubscriptionEntity>, anyhow::Error> {
+        sqlx::query_as::<_, SubscriptionEntity>("SELECT * FROM subscriptions WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .traced("SubscriptionRepository.find_by_id")
+            .await
+            .context("Failed to fetch subscription")
+    }
+
+    async fn save(
+        &self,
+        subscription: SubscriptionEntity,
+    ) -> Result<SubscriptionEntity, anyhow::Error> {
+        sqlx::query_as::<_, SubscriptionEntity>(
+            r#"INSERT INTO subscriptions (name, project_id, pipeline_id, events, channel_id, owner, tags,
+                                        enabled, created_at, updated_at, source)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+               RETURNING *"#,
+        )
+        .bind(&subscription.name)
+        .bind(&subscription.project_id)
+        .bind(&subscription.pipeline_id)
+        .bind(&subscription.events)
+        .bind(subscription.channel_id)
+        .bind(&subscription.owner)
+        .bind(&subscription.tags)
+        .bind(subscription.enabled)
+        .bind(subscription.created_at)
+        .bind(subscription.updated_at)
+        .bind(&subscription.source)
+        .fetch_one(&self.pool)
+        .traced("SubscriptionRepository.save")
+        .await
+        .context("Failed to insert subscription")
+    }
+
+    async fn update(
+        &self,
+        subscription: SubscriptionEntity,
+    ) -> Result<SubscriptionEntity, anyhow::Error> {
+        sqlx::query_as::<_, SubscriptionEntity>(
+            r#"UPDATE subscriptions
+               SET name        = ?,
+                   project_id  = ?,
+                   pipeline_id = ?,
+                   events      = ?,
+                   channel_id  = ?,
+                   owner       = ?,
+                   tags        = ?,
+                   enabled     = ?,
+                   updated_at  = ?,
+                   source      = ?
+               WHERE id = ?
+               RETURNING *"#,
+        )
+        .bind(&subscription.name)
+        .bind(&subscription.project_id)
+        .bind(&subscription.pipeline_id)
+        .bind(&subscription.events)
+        .bind(subscription.channel_id)
+        .bind(&subscription.owner)
+        .bind(&subscription.tags)
+        .bind(subscription.enabled)
+        .bind(subscription.updated_at)
+        .bind(&subscription.source)
+        .bind(subscription.id)
+        .fetch_one(&self.pool)
+        .traced("SubscriptionRepository.update")
+        .await
+        .context("Failed to update subscription")
+    }
+
+    async fn delete_by_id(&self, id: i64) -> Result<SubscriptionEntity, anyhow::Error> {
+        sqlx::query_as::<_, SubscriptionEntity>(
+            "DELETE FROM subscriptions WHERE id = ? RETURNING *",
+        )
+        .bind(id)
+        .fetch_one(&self.pool)
+        .traced("SubscriptionRepository.delete_by_id")
+        .await
+        .context("Failed to delete subscription")
+    }
+}
+
+/**
+ * Channel repository
+ */
+#[derive(Clone)]
+pub struct ChannelRepository {
+    pool: SqlitePool,
+}
+
+impl ChannelRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /**
+     * Channels of the given projects, all channels when `project_ids` is None
+     */
+    pub async fn find_by_projects(
+        &self,
+        project_ids: Option<&[String]>,
+    ) -> Result<Vec<ChannelEntity>, anyhow::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM channels");
+        if let Some(project_ids) = project_ids {
+            if project_ids.is_empty() {
+                return Ok(vec![]);
+            }
+            query.push(" WHERE project_id IN (");
+            let mut separated = query.separated(", ");
+            for project_id in project_ids {
+                separated.push_bind(project_id);
+            }
+            query.push(")");
+        }
+        query.push(" ORDER BY id");
+        query
+            .build_query_as::<ChannelEntity>()
+            .fetch_all(&self.pool)
+            .traced("ChannelRepository.find_by_projects")
+            .await
+            .context("Failed to fetch channels")
+    }
+
+    /**
+     * Number of subscriptions delivering to the channel
+     */
+    pub async fn count_subscriptions(&self, id: i64) -> Result<i64, anyhow::Error> {
+        sqlx::query_scalar::<_, i64>("SELECT COUNT(*) FROM subscriptions WHERE channel_id = ?")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .traced("ChannelRepository.count_subscriptions")
+            .await
+            .context("Failed to count channel subscriptions")
+    }
+}
+
+impl DatabaseRepository<ChannelEntity, i64> for ChannelRepository {
+    async fn find_by_id(&self, id: i64) -> Result<Option<ChannelEntity>, anyhow::Error> {
+        sqlx::query_as::<_, ChannelEntity>("SELECT * FROM channels WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .traced("ChannelRepository.find_by_id")
+            .await
+            .context("Failed to fetch channel")
+    }
+
+    async fn save(&self, channel: ChannelEntity) -> Result<ChannelEntity, anyhow::Error> {
+        sqlx::query_as::<_, ChannelEntity>(
+            r#"INSERT INTO channels (name, project_id, kind, config, secret, secret_fields, created_by,
+                                   created_at, updated_at)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)
+               RETURNING *"#,
+        )
+        .bind(&channel.name)
+        .bind(&channel.project_id)
+        .bind(channel.kind)
+        .bind(&channel.config)
+        .bind(&channel.secret)
+        .bind(&channel.secret_fields)
+        .bind(&channel.created_by)
+        .bind(channel.created_at)
+        .bind(channel.updated_at)
+        .fetch_one(&self.pool)
+        .traced("ChannelRepository.save")
+        .await
+        .context("Failed to insert channel")
+    }
+
+    async fn update(&self, channel: ChannelEntity) -> Result<ChannelEntity, anyhow::Error> {
+        sqlx::query_as::<_, ChannelEntity>(
+            r#"UPDATE channels
+               SET name          = ?,
+                   project_id    = ?,
+                   kind          = ?,
+                   config        = ?,
+                   secret        = ?,
+                   secret_fields = ?,
+                   updated_at    = ?
+               WHERE id = ?
+               RETURNING *"#,
+        )
+        .bind(&channel.name)
+        .bind(&channel.project_id)
+        .bind(channel.kind)
+        .bind(&channel.config)
+        .bind(&channel.secret)
+        .bind(&channel.secret_fields)
+        .bind(channel.updated_at)
+        .bind(channel.id)
+        .fetch_one(&self.pool)
+        .traced("ChannelRepository.update")
+        .await
+        .context("Failed to update channel")
+    }
+
+    async fn delete_by_id(&self, id: i64) -> Result<ChannelEntity, anyhow::Error> {
+        sqlx::query_as::<_, ChannelEntity>("DELETE FROM channels WHERE id = ? RETURNING *")
+            .bind(id)
+            .fetch_one(&self.pool)
+            .traced("ChannelRepository.delete_by_id")
+            .await
+            .context("Failed to delete channel")
+    }
+}
+
+/**
+ * Notification outbox repository
+ */
+#[derive(Clone)]
+pub struct NotificationRepository {
+    pool: SqlitePool,
+}
+
+impl NotificationRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /**
+     * Insert a notification together with one pending delivery per target
+     */
+    pub async fn enqueue(
+        &self,
+        notification: NotificationEntity,
+        targets: &[DeliveryTarget],
+    ) -> Result<(NotificationEntity, Vec<DeliveryEntity>), anyhow::Error> {
+        let mut tx = self.pool.begin().await?;
+        let notification = sqlx::query_as::<_, NotificationEntity>(
+            r#"INSERT INTO notifications (project_id, title, content, severity, source, template_id,
+                                        created_by, created_at, pipeline_id, build_id, event)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+               RETURNING *"#,
+        )
+        .bind(&notification.project_id)
+        .bind(&notification.title)
+        .bind(&notification.content)
+        .bind(notification.severity)
+        .bind(&notification.source)
+        .bind(&notification.template_id)
+        .bind(&notification.created_by)
+        .bind(notification.created_at)
+        .bind(&notification.pipeline_id)
+        .bind(&notification.build_id)
+        .bind(&notification.event)
+        .fetch_one(&mut *tx)
+        .traced("NotificationRepository.enqueue")
+        .await
+        .context("Failed to insert notification")?;
+        let mut deliveries = Vec::with_capacity(targets.len());
+        for target in targets {
+            let delivery = sqlx::query_as::<_, DeliveryEntity>(
+                r#"INSERT INTO deliveries (notification_id, channel_id, subscription_id, status, attempts,
+                                         next_attempt_at, created_at, updated_at)
+                   VALUES (?, ?, ?, ?, 0, ?, ?, ?)
+                   RETURNING *"#,
+            )
+            .bind(notification.id)
+            .bind(target.channel_id)
+            .bind(target.subscription_id)
+            .bind(DeliveryStatus::Pending)
+            .bind(notification.created_at)
+            .bind(notification.created_at)
+            .bind(notification.created_at)
+            .fetch_one(&mut *tx)
+            .traced("NotificationRepository.enqueue")
+            .await
+            .context("Failed to insert delivery")?;
+            deliveries.push(delivery);
+        }
+        tx.commit().await?;
+        Ok((notification, deliveries))
+    }
+}
+
+impl DatabaseRepository<NotificationEntity, i64> for NotificationRepository {
+    async fn find_by_id(&self, id: i64) -> Result<Option<NotificationEntity>, anyhow::Error> {
+        sqlx::query_as::<_, NotificationEntity>("SELECT * FROM notifications WHERE id = ?")
+            .bind(id)
+            .fetch_optional(&self.pool)
+            .traced("NotificationRepository.find_by_id")
+            .await
+            .context("Failed to fetch notification")
+    }
+}
+
+/**
+ * Delivery repository
+ */
+#[derive(Clone)]
+pub struct DeliveryRepository {
+    pool: SqlitePool,
+}
+
+impl DeliveryRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /**
+     * Channels whose latest attempt since the given time failed
+     */
+    pub async fn find_failing_channels(
+        &self,
+        since: DateTime<Utc>,
+    ) -> Result<Vec<i64>, anyhow::Error> {
+        sqlx::query_scalar::<_, i64>(
+            r#"SELECT a.channel_id
+               FROM delivery_attempts a
+               WHERE julianday(a.attempted_at) >= julianday(?)
+                 AND a.status = 'failed'
+                 AND a.id = (SELECT MAX(b.id) FROM delivery_attempts b WHERE b.channel_id = a.channel_id)
+               ORDER BY a.channel_id"#,
+        )
+        .bind(since)
+        .fetch_all(&self.pool)
+        .traced("DeliveryRepository.find_failing_channels")
+        .await
+        .context("Failed to find failing channels")
+    }
+
+    pub async fn find_by_notification(
+        &self,
+        notification_id: i64,
+    ) -> Result<Vec<DeliveryEntity>, anyhow::Error> {
+        sqlx::query_as::<_, DeliveryEntity>(
+            "SELECT * FROM deliveries WHERE notification_id = ? ORDER BY id",
+        )
+        .bind(notification_id)
+        .fetch_all(&self.pool)
+        .traced("DeliveryRepository.find_by_notification")
+        .await
+        .context("Failed to fetch deliveries")
+    }
+
+    /**
+     * Claim due pending deliveries by marking them as sending
+     */
+    pub async fn claim_due(
+        &self,
+        now: DateTime<Utc>,
+        limit: u32,
+    ) -> Result<Vec<DeliveryEntity>, anyhow::Error> {
+        sqlx::query_as::<_, DeliveryEntity>(
+            r#"UPDATE deliveries
+               SET status = 'sending', attempts = attempts + 1, updated_at = ?
+               WHERE id IN (SELECT id
+                            FROM deliveries
+                            WHERE status = 'pending' AND julianday(next_attempt_at) <= julianday(?)
+                            ORDER BY next_attempt_at
+                            LIMIT ?)
+               RETURNING *"#,
+        )
+        .bind(now)
+        .bind(now)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .traced("DeliveryRepository.claim_due")
+        .await
+        .context("Failed to claim deliveries")
+    }
+
+    /**
+     * Append an attempt to the audit log
+     */
+    pub async fn record_attempt(
+        &self,
+        attempt: DeliveryAttemptEntity,
+    ) -> Result<DeliveryAttemptEntity, anyhow::Error> {
+        sqlx::query_as::<_, DeliveryAttemptEntity>(
+            r#"INSERT INTO delivery_attempts (delivery_id, notification_id, project_id, pipeline_id, build_id,
+                                            source, event, subscription_id, channel_id, attempt, status,
+                                            payload_hash, response_status, response_excerpt, error,
+                                            latency_ms, attempted_at)
+               VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?, ?)
+               RETURNING *"#,
+        )
+        .bind(attempt.delivery_id)
+        .bind(attempt.notification_id)
+        .bind(&attempt.project_id)
+        .bind(&attempt.pipeline_id)
+        .bind(&attempt.build_id)
+        .bind(&attempt.source)
+        .bind(&attempt.event)
+        .bind(attempt.subscription_id)
+        .bind(attempt.channel_id)
+        .bind(attempt.attempt)
+        .bind(attempt.status)
+        .bind(&attempt.payload_hash)
+        .bind(attempt.response_status)
+        .bind(&attempt.response_excerpt)
+        .bind(&attempt.error)
+        .bind(attempt.latency_ms)
+        .bind(attempt.attempted_at)
+        .fetch_one(&self.pool)
+        .traced("DeliveryRepository.record_attempt")
+        .await
+        .context("Failed to record delivery attempt")
+    }
+
+    /**
+     * Attempts matching the filter, latest first
+     */
+    pub async fn find_attempts(
+        &self,
+        filter: &DeliveryAttemptFilter,
+        offset: u32,
+        limit: u32,
+    ) -> Result<Vec<DeliveryAttemptEntity>, anyhow::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT * FROM delivery_attempts");
+        if !Self::push_attempt_filter(&mut query, filter) {
+            return Ok(vec![]);
+        }
+        query
+            .push(" ORDER BY attempted_at DESC, id DESC LIMIT ")
+            .push_bind(limit)
+            .push(" OFFSET ")
+            .push_bind(offset);
+        query
+            .build_query_as::<DeliveryAttemptEntity>()
+            .fetch_all(&self.pool)
+            .traced("DeliveryRepository.find_attempts")
+            .await
+            .context("Failed to fetch delivery attempts")
+    }
+
+    pub async fn count_attempts(
+        &self,
+        filter: &DeliveryAttemptFilter,
+    ) -> Result<i64, anyhow::Error> {
+        let mut query = QueryBuilder::<Sqlite>::new("SELECT COUNT(*) FROM delivery_attempts");
+        if !Self::push_attempt_filter(&mut query, filter) {
+            return Ok(0);
+        }
+        query
+            .build_query_scalar::<i64>()
+            .fetch_one(&self.pool)
+            .traced("DeliveryRepository.count_attempts")
+            .await
+            .context("Failed to count delivery attempts")
+    }
+
+    /**
+     * Append the WHERE clause, false when the filter can not match anything
+     */
+    fn push_attempt_filter(
+        query: &mut QueryBuilder<'_, Sqlite>,
+        filter: &DeliveryAttemptFilter,
+    ) -> bool {
+        query.push(" WHERE 1 = 1");
+        if let Some(project_id) = &filter.project_id {
+            query
+                .push(" AND project_id = ")
+                .push_bind(project_id.clone());
+        }
+        if let Some(project_ids) = &filter.project_ids {
+            if project_ids.is_empty() {
+                return false;
+            }
+            query.push(" AND project_id IN (");
+            let mut separated = query.separated(", ");
+            for project_id in project_ids {
+                separated.push_bind(project_id.clone());
+            }
+            query.push(")");
+        }
+        if let Some(pipeline_id) = &filter.pipeline_id {
+            query
+                .push(" AND pipeline_id = ")
+                .push_bind(pipeline_id.clone());
+        }
+        if let Some(build_id) = &filter.build_id {
+            query.push(" AND build_id = ").push_bind(build_id.clone());
+        }
+        if let Some(channel_id) = filter.channel_id {
+            query.push(" AND channel_id = ").push_bind(channel_id);
+        }
+        if let Some(status) = filter.status {
+            query.push(" AND status = ").push_bind(status);
+        }
+        if let Some(from) = filter.from {
+            query
+                .push(" AND julianday(attempted_at) >= julianday(")
+                .push_bind(from)
+                .push(")");
+        }
+        if let Some(to) = filter.to {
+            query
+                .push(" AND julianday(attempted_at) < julianday(")
+                .push_bind(to)
+                .push(")");
+        }
+        true
+    }
+
+    /**
+     * Put deliveries left in sending state back to pending, e.g. after a crash
+     */
+    pub async fn reset_sending(&self) -> Result<u64, anyhow::Error> {
+        let result = sqlx::query(
+            "UPDATE deliveries SET status = 'pending', updated_at = ? WHERE status = 'sending'",
+        )
+        .bind(Utc::now())
+        .execute(&self.pool)
+        .traced("DeliveryRepository.reset_sending")
+        .await
+        .context("Failed to reset sending deliveries")?;
+        Ok(result.rows_affected())
+    }
+}
+
+impl DatabaseRepository<DeliveryEntity, i64> for DeliveryRepository {
+    async fn update(&self, delivery: DeliveryEntity) -> Result<DeliveryEntity, anyhow::Error> {
+        sqlx::query_as::<_, DeliveryEntity>(
+            r#"UPDATE deliveries
+               SET status          = ?,
+                   attempts        = ?,
+                   next_attempt_at = ?,
+                   last_error      = ?,
+                   response_status = ?,
+                   response_body   = ?,
+                   delivered_at    = ?,
+                   updated_at      = ?
+               WHERE id = ?
+               RETURNING *"#,
+        )
+        .bind(delivery.status)
+        .bind(delivery.attempts)
+        .bind(delivery.next_attempt_at)
+        .bind(&delivery.last_error)
+        .bind(delivery.response_status)
+        .bind(&delivery.response_body)
+        .bind(delivery.delivered_at)
+        .bind(delivery.updated_at)
+        .bind(delivery.id)
+        .fetch_one(&self.pool)
+        .traced("DeliveryRepository.update")
+        .await
+        .context("Failed to update delivery")
+    }
+}
+
+/**
+ * Database health checks
+ */
+#[derive(Clone)]
+pub struct HealthRepository {
+    pool: SqlitePool,
+}
+
+impl HealthRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    pub async fn ping(&self) -> Result<(), anyhow::Error> {
+        sqlx::query("SELECT 1")
             .execute(&self.pool)
+            .traced("HealthRepository.ping")
             .await
-            .context("Failed to insert todo")?;
-        Ok(PipeLineEntity::new(
-            Some(result.last_insert_rowid()),
-            todo.name.clone().to_string(),
-            todo.email.clone().to_string(),
-            todo.age,
-        ))
+            .context("Failed to ping database")?;
+        Ok(())
+    }
+
+    /**
+     * Embedded migrations not yet applied successfully
+     */
+    pub async fn pending_migrations(&self) -> Result<Vec<i64>, anyhow::Error> {
+        let applied: HashSet<i64> =
+            sqlx::query_scalar::<_, i64>("SELECT version FROM _sqlx_migrations WHERE success = 1")
+                .fetch_all(&self.pool)
+                .traced("HealthRepository.pending_migrations")
+                .await
+                .context("Failed to query applied migrations")?
+                .into_iter()
+                .collect();
+        Ok(MIGRATOR
+            .iter()
+            .filter(|migration| migration.migration_type.is_up_migration())
+            .map(|migration| migration.version)
+            .filter(|version| !applied.contains(version))
+            .collect())
+    }
+}
+
+/**
+ * Migration repository
+ */
+#[derive(Clone)]
+pub struct MigrationRepository {
+    pool: SqlitePool,
+}
+
+impl MigrationRepository {
+    pub fn new(pool: SqlitePool) -> Self {
+        Self { pool }
+    }
+
+    /**
+     * Apply pending migrations, returning the versions applied
+     */
+    pub async fn run(&self) -> Result<Vec<i64>, anyhow::Error> {
+        let pending: Vec<i64> = self
+            .status()
+            .await?
+            .into_iter()
+            .filter(|migration| migration.success != Some(true))
+            .map(|migration| migration.version)
+            .collect();
+        MIGRATOR
+            .run(&self.pool)
+            .traced("MigrationRepository.run")
+            .await
+            .context("Failed to run database migrations")?;
+        Ok(pending)
+    }
+
+    /**
+     * Embedded migrations joined with their recorded state
+     */
+    pub async fn status(&self) -> Result<Vec<MigrationEntity>, anyhow::Error> {
+        let initialized = sqlx::query_scalar::<_, i64>(
+            "SELECT COUNT(*) FROM sqlite_master WHERE type = 'table' AND name = '_sqlx_migrations'",
+        )
+        .fetch_one(&self.pool)
+        .traced("MigrationRepository.status")
+        .await
+        .context("Failed to inspect migrations table")?
+            > 0;
+        let applied: Vec<(i64, DateTime<Utc>, bool)> = if initialized {
+            sqlx::query_as("SELECT version, installed_on, success FROM _sqlx_migrations")
+                .fetch_all(&self.pool)
+                .traced("MigrationRepository.status")
+                .await
+                .context("Failed to query applied migrations")?
+        } else {
+            vec![]
+        };
+        Ok(MIGRATOR
+            .iter()
+            .filter(|migration| migration.migration_type.is_up_migration())
+            .map(|migration| {
+                let state = applied
+                    .iter()
+                    .find(|(version, _, _)| *version == migration.version);
+                MigrationEntity {
+                    version: migration.version,
+                    description: migration.description.to_string(),
+                    installed_on: state.map(|(_, installed_on, _)| *installed_on),
+                    success: state.map(|(_, _, success)| *success),
+                }
+            })
+            .collect())
     }
 }
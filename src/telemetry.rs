@@ -0,0 +1,176 @@
+use crate::conf::TelemetryOptions;
+use anyhow::Context as _;
+use log::{info, warn};
+use once_cell::sync::OnceCell;
+use opentelemetry::context::FutureExt;
+use opentelemetry::propagation::{Extractor, Injector};
+use opentelemetry::trace::{SpanKind, Status, TraceContextExt, Tracer};
+use opentelemetry::{Context, KeyValue, global};
+use opentelemetry_otlp::{WithExportConfig, WithHttpConfig};
+use opentelemetry_sdk::Resource;
+use opentelemetry_sdk::propagation::TraceContextPropagator;
+use opentelemetry_sdk::trace::{Sampler, SdkTracerProvider};
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue};
+use std::borrow::Cow;
+use std::fmt::Display;
+use std::future::Future;
+use std::time::Duration;
+
+/// 本服务产生的 span 使用的 tracer 名称
+const TRACER: &str = "vision-notification";
+
+static PROVIDER: OnceCell<SdkTracerProvider> = OnceCell::new();
+
+/**
+ * 按配置安装 OTLP 导出, 未启用时 span 为空操作; 无论是否启用都按 W3C traceparent 传播上下文
+ */
+pub fn init(options: &TelemetryOptions) -> Result<(), anyhow::Error> {
+    global::set_text_map_propagator(TraceContextPropagator::new());
+    if !options.enabled() {
+        return Ok(());
+    }
+    let exporter = opentelemetry_otlp::SpanExporter::builder()
+        .with_http()
+        .with_endpoint(options.endpoint())
+        .with_timeout(Duration::from_secs(*options.timeout()))
+        .with_headers(
+            options
+                .headers()
+                .iter()
+                .map(|(name, value)| (name.clone(), value.expose().to_string()))
+                .collect(),
+        )
+        .build()
+        .context("create otlp exporter failed")?;
+    let provider = SdkTracerProvider::builder()
+        .with_batch_exporter(exporter)
+        .with_sampler(Sampler::ParentBased(Box::new(Sampler::TraceIdRatioBased(
+            *options.sample_ratio(),
+        ))))
+        .with_resource(
+            Resource::builder()
+                .with_service_name(options.service_name().clone())
+                .build(),
+        )
+        .build();
+    global::set_tracer_provider(provider.clone());
+    let _ = PROVIDER.set(provider);
+    info!("exporting traces to {}", options.endpoint());
+    Ok(())
+}
+
+/**
+ * 导出剩余的 span, 退出前调用; 会阻塞当前线程直到导出完成或超时
+ */
+pub fn shutdown() {
+    if let Some(provider) = PROVIDER.get()
+        && let Err(err) = provider.shutdown()
+    {
+        warn!("flush traces failed. {}", err);
+    }
+}
+
+/**
+ * 以当前上下文为父开始一个 span, 返回包含该 span 的上下文
+ */
+pub fn start_span(
+    name: impl Into<Cow<'static, str>>,
+    kind: SpanKind,
+    attributes: Vec<KeyValue>,
+) -> Context {
+    start_span_with_parent(&Context::current(), name, kind, attributes)
+}
+
+pub fn start_span_with_parent(
+    parent: &Context,
+    name: impl Into<Cow<'static, str>>,
+    kind: SpanKind,
+    attributes: Vec<KeyValue>,
+) -> Context {
+    let tracer = global::tracer(TRACER);
+    let span = tracer
+        .span_builder(name)
+        .with_kind(kind)
+        .with_attributes(attributes)
+        .start_with_context(&tracer, parent);
+    parent.with_span(span)
+}
+
+/**
+ * 在新 span 内执行, 返回错误时标记 span 失败
+ */
+pub async fn in_span<F, T, E>(
+    name: impl Into<Cow<'static, str>>,
+    kind: SpanKind,
+    attributes: Vec<KeyValue>,
+    future: F,
+) -> Result<T, E>
+where
+    F: Future<Output = Result<T, E>>,
+    E: Display,
+{
+    let cx = start_span(name, kind, attributes);
+    let result = future.with_context(cx.clone()).await;
+    if let Err(err) = &result {
+        record_error(&cx, err);
+    }
+    cx.span().end();
+    result
+}
+
+pub fn record_error(cx: &Context, err: impl Display) {
+    cx.span().set_status(Status::error(format!("{:#}", err)));
+}
+
+/**
+ * 当前 span 的 trace id, 用于关联日志
+ */
+pub fn trace_id() -> Option<String> {
+    let cx = Context::current();
+    let span = cx.span();
+    let span_context = span.span_context();
+    span_context
+        .is_valid()
+        .then(|| span_context.trace_id().to_string())
+}
+
+/**
+ * 将上下文写入出站请求头 (traceparent)
+ */
+pub fn inject(cx: &Context, headers: &mut HeaderMap) {
+    global::get_text_map_propagator(|propagator| {
+        propagator.inject_context(cx, &mut HeaderInjector(headers))
+    });
+}
+
+/**
+ * 从入站请求头恢复调用方的上下文
+ */
+pub fn extract(headers: &HeaderMap) -> Context {
+    global::get_text_map_propagator(|propagator| propagator.extract(&HeaderExtractor(headers)))
+}
+
+struct HeaderInjector<'a>(&'a mut HeaderMap);
+
+impl Injector for HeaderInjector<'_> {
+    fn set(&mut self, key: &str, value: String) {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(key.as_bytes()),
+            HeaderValue::from_str(&value),
+        ) {
+            self.0.insert(name, value);
+        }
+    }
+}
+
+struct HeaderExtractor<'a>(&'a HeaderMap);
+
+impl Extractor for HeaderExtractor<'_> {
+    fn get(&self, key: &str) -> Option<&str> {
+        self.0.get(key).and_then(|value| value.to_str().ok())
+    }
+
+    fn keys(&self) -> Vec<&str> {
+        self.0.keys().map(HeaderName::as_str).collect()
+    }
+}